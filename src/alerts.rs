@@ -0,0 +1,531 @@
+// ABOUTME: Alert engine: turns host and connectivity state into debounced firing/resolved alerts.
+// ABOUTME: Delivers them to the configured sinks (bell, shell command, webhook, log file).
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+
+use crate::config::{AlertCondition, AlertRule, SinkConfig};
+use crate::status::{ConnectivityState, HostState};
+
+/// Upper bound on a webhook request or hook command.
+const SINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+/// One notification. `subject` is the host name, or "network" for connectivity rules.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub status: AlertStatus,
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_id: Option<String>,
+    pub detail: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Alert {
+    /// One-line rendering for the log sink.
+    fn line(&self) -> String {
+        let status = match self.status {
+            AlertStatus::Firing => "FIRING",
+            AlertStatus::Resolved => "RESOLVED",
+        };
+        format!(
+            "{} {status} {}: {} \u{2014} {}",
+            self.at.to_rfc3339(),
+            self.rule,
+            self.subject,
+            self.detail
+        )
+    }
+}
+
+/// A monitored host as the rules see it.
+#[derive(Debug, Clone)]
+pub struct HostView {
+    pub id: String,
+    pub name: String,
+    /// The dual-stack host this is a half of ("Google" for "Google (IPv6)"), which is
+    /// the name a rule knows it by.
+    pub stack: Option<String>,
+    pub state: HostState,
+}
+
+impl HostView {
+    /// The name the config file gives this host.
+    fn configured_name(&self) -> &str {
+        self.stack.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Debounce and hysteresis state of one rule for one subject.
+#[derive(Debug, Default)]
+struct Track {
+    // When the condition started holding, while it holds.
+    active_since: Option<Instant>,
+    // When the condition stopped holding, while a firing alert waits to resolve.
+    clear_since: Option<Instant>,
+    firing: bool,
+    detail: String,
+    // Display name of the subject, for resolving the alert once it's gone.
+    subject: String,
+}
+
+/// Evaluates rules against each tick's states. Pure apart from the clock passed in.
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    // Keyed by (rule index, subject id): host id, or "" for the network.
+    tracks: HashMap<(usize, String), Track>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules,
+            tracks: HashMap::new(),
+        }
+    }
+
+    /// Advance every rule to `now`, given each monitored host. Returns the alerts that
+    /// fired or resolved on this tick.
+    pub fn evaluate(
+        &mut self,
+        now: Instant,
+        hosts: &[HostView],
+        connectivity: &ConnectivityState,
+    ) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (idx, rule) in self.rules.iter().enumerate() {
+            let rule_name = rule.name.clone().unwrap_or_else(|| {
+                serde_json::to_value(rule.when)
+                    .ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_default()
+            });
+            // (subject id, display name, detail when the condition holds)
+            let subjects: Vec<(String, String, Option<String>)> = match rule.when {
+                AlertCondition::Down | AlertCondition::Degraded => hosts
+                    .iter()
+                    .filter(|h| {
+                        rule.hosts.is_empty() || rule.hosts.iter().any(|n| n == h.configured_name())
+                    })
+                    .map(
+                        |HostView {
+                             id, name, state, ..
+                         }| {
+                            let detail = match (rule.when, state) {
+                                (AlertCondition::Down, HostState::Down { reason }) => {
+                                    Some(format!("down: {reason}"))
+                                }
+                                (AlertCondition::Degraded, HostState::Degraded { .. }) => state
+                                    .degraded_reason()
+                                    .map(|reason| format!("degraded: {reason}")),
+                                _ => None,
+                            };
+                            (id.clone(), name.clone(), detail)
+                        },
+                    )
+                    .collect(),
+                AlertCondition::CaptivePortal => {
+                    let detail = match connectivity {
+                        ConnectivityState::CaptivePortal { url } => {
+                            Some(format!("captive portal detected at {url}"))
+                        }
+                        _ => None,
+                    };
+                    vec![(String::new(), "network".to_string(), detail)]
+                }
+                AlertCondition::Offline => {
+                    let detail = matches!(connectivity, ConnectivityState::Offline)
+                        .then(|| "offline: no host is reachable".to_string());
+                    vec![(String::new(), "network".to_string(), detail)]
+                }
+            };
+
+            // A host that's no longer monitored (removed, or left out by a reload or
+            // profile) can't recover; resolve what was firing for it and forget it.
+            let gone: Vec<(usize, String)> = self
+                .tracks
+                .keys()
+                .filter(|(i, id)| *i == idx && !subjects.iter().any(|(s, _, _)| s == id))
+                .cloned()
+                .collect();
+            for key in gone {
+                let track = self.tracks.remove(&key).expect("listed above");
+                if track.firing {
+                    alerts.push(Alert {
+                        rule: rule_name.clone(),
+                        status: AlertStatus::Resolved,
+                        subject: track.subject,
+                        host_id: Some(key.1),
+                        detail: "no longer monitored".to_string(),
+                        at: Utc::now(),
+                    });
+                }
+            }
+
+            for (id, name, detail) in subjects {
+                let track = self.tracks.entry((idx, id.clone())).or_default();
+                track.subject.clone_from(&name);
+                let status = step(track, now, detail, rule);
+                if let Some(status) = status {
+                    alerts.push(Alert {
+                        rule: rule_name.clone(),
+                        status,
+                        subject: name,
+                        host_id: (!id.is_empty()).then_some(id),
+                        detail: track.detail.clone(),
+                        at: Utc::now(),
+                    });
+                }
+            }
+        }
+        alerts
+    }
+}
+
+/// Move one track forward. `detail` is Some while the condition holds.
+fn step(
+    track: &mut Track,
+    now: Instant,
+    detail: Option<String>,
+    rule: &AlertRule,
+) -> Option<AlertStatus> {
+    match detail {
+        Some(detail) => {
+            track.clear_since = None;
+            let since = *track.active_since.get_or_insert(now);
+            if !track.firing && now - since >= Duration::from_secs_f64(rule.for_secs) {
+                track.firing = true;
+                track.detail = detail;
+                return Some(AlertStatus::Firing);
+            }
+            None
+        }
+        None => {
+            track.active_since = None;
+            if !track.firing {
+                return None;
+            }
+            let since = *track.clear_since.get_or_insert(now);
+            if now - since >= Duration::from_secs_f64(rule.clear_secs) {
+                track.firing = false;
+                track.clear_since = None;
+                return Some(AlertStatus::Resolved);
+            }
+            None
+        }
+    }
+}
+
+/// Check sink settings up front so a typo fails at startup, not during an outage.
+pub fn check_sinks(sinks: &[SinkConfig]) -> Result<()> {
+    for sink in sinks {
+        match sink {
+            SinkConfig::Webhook { url } if crate::http::parse_url(url).is_none() => {
+                bail!("alert webhook is not an http(s) URL: {url}")
+            }
+            SinkConfig::Command { command } if command.trim().is_empty() => {
+                bail!("alert command sink has an empty command")
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Deliver an alert to every sink, each in its own task. Delivery failures are dropped:
+/// a dead webhook must not hold up the others or the monitor itself.
+pub fn dispatch(sinks: &[SinkConfig], alert: Alert) {
+    for sink in sinks {
+        let (sink, alert) = (sink.clone(), alert.clone());
+        tokio::spawn(async move {
+            let _ = tokio::time::timeout(SINK_TIMEOUT, deliver(&sink, &alert)).await;
+        });
+    }
+}
+
+async fn deliver(sink: &SinkConfig, alert: &Alert) -> Result<()> {
+    match sink {
+        // stderr, so the bell never lands in a headless JSON stream on stdout.
+        SinkConfig::Bell => {
+            let mut err = std::io::stderr();
+            err.write_all(b"\x07")?;
+            err.flush()?;
+        }
+        SinkConfig::Command { command } => {
+            let status = match alert.status {
+                AlertStatus::Firing => "firing",
+                AlertStatus::Resolved => "resolved",
+            };
+            tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .env("PINGPONG_ALERT_RULE", &alert.rule)
+                .env("PINGPONG_ALERT_STATUS", status)
+                .env("PINGPONG_ALERT_SUBJECT", &alert.subject)
+                .env(
+                    "PINGPONG_ALERT_HOST_ID",
+                    alert.host_id.as_deref().unwrap_or(""),
+                )
+                .env("PINGPONG_ALERT_DETAIL", &alert.detail)
+                .env("PINGPONG_ALERT_TIME", alert.at.to_rfc3339())
+                // Keep the hook's output off the TUI.
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .status()
+                .await?;
+        }
+        SinkConfig::Webhook { url } => {
+            let body = serde_json::to_string(alert)?;
+            let status = crate::http::post_json(url, &body, SINK_TIMEOUT).await?;
+            if !(200..300).contains(&status) {
+                bail!("webhook returned HTTP {status}");
+            }
+        }
+        SinkConfig::Log { path } => {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            file.write_all(format!("{}\n", alert.line()).as_bytes())
+                .await?;
+            file.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod alerts_tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn rule(when: AlertCondition, for_secs: f64, clear_secs: f64) -> AlertRule {
+        AlertRule {
+            name: None,
+            when,
+            hosts: vec![],
+            for_secs,
+            clear_secs,
+        }
+    }
+
+    fn host(id: &str, name: &str, state: HostState) -> HostView {
+        HostView {
+            id: id.into(),
+            name: name.into(),
+            stack: None,
+            state,
+        }
+    }
+
+    fn hosts(state: HostState) -> Vec<HostView> {
+        vec![host("h1", "VPN", state)]
+    }
+
+    fn down() -> HostState {
+        HostState::Down {
+            reason: "no replies".into(),
+        }
+    }
+
+    fn up() -> HostState {
+        HostState::Up { rtt_ms: 10.0 }
+    }
+
+    #[test]
+    fn down_fires_after_debounce_and_resolves_after_hysteresis() {
+        let mut e = AlertEngine::new(vec![rule(AlertCondition::Down, 5.0, 10.0)]);
+        let t0 = Instant::now();
+        let s = |secs: u64| t0 + Duration::from_secs(secs);
+        let online = ConnectivityState::Online;
+
+        assert!(e.evaluate(s(0), &hosts(down()), &online).is_empty());
+        assert!(e.evaluate(s(4), &hosts(down()), &online).is_empty());
+        let fired = e.evaluate(s(5), &hosts(down()), &online);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].status, AlertStatus::Firing);
+        assert_eq!(fired[0].rule, "down");
+        assert_eq!(fired[0].subject, "VPN");
+        assert_eq!(fired[0].detail, "down: no replies");
+        // Still down: no repeat.
+        assert!(e.evaluate(s(6), &hosts(down()), &online).is_empty());
+
+        // A brief recovery inside the clear window does not resolve, and a relapse
+        // restarts the clear window rather than re-firing.
+        assert!(e.evaluate(s(7), &hosts(up()), &online).is_empty());
+        assert!(e.evaluate(s(8), &hosts(down()), &online).is_empty());
+        assert!(e.evaluate(s(9), &hosts(up()), &online).is_empty());
+        assert!(e.evaluate(s(18), &hosts(up()), &online).is_empty());
+        let resolved = e.evaluate(s(19), &hosts(up()), &online);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].status, AlertStatus::Resolved);
+    }
+
+    #[test]
+    fn removed_hosts_resolve_and_are_forgotten() {
+        let mut e = AlertEngine::new(vec![rule(AlertCondition::Down, 0.0, 60.0)]);
+        let online = ConnectivityState::Online;
+        let now = Instant::now();
+        assert_eq!(e.evaluate(now, &hosts(down()), &online).len(), 1);
+        let resolved = e.evaluate(now, &[], &online);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].status, AlertStatus::Resolved);
+        assert_eq!(resolved[0].subject, "VPN");
+        assert_eq!(resolved[0].host_id.as_deref(), Some("h1"));
+        assert!(e.tracks.is_empty());
+        // Nothing left to resolve a second time.
+        assert!(e.evaluate(now, &[], &online).is_empty());
+    }
+
+    #[test]
+    fn flapping_shorter_than_debounce_never_fires() {
+        let mut e = AlertEngine::new(vec![rule(AlertCondition::Down, 5.0, 0.0)]);
+        let t0 = Instant::now();
+        let online = ConnectivityState::Online;
+        for i in 0..10u64 {
+            let state = if i % 2 == 0 { down() } else { up() };
+            let now = t0 + Duration::from_secs(i * 3);
+            assert!(e.evaluate(now, &hosts(state), &online).is_empty());
+        }
+    }
+
+    #[test]
+    fn rules_name_dual_stack_hosts_as_configured() {
+        let mut google = rule(AlertCondition::Down, 0.0, 0.0);
+        google.hosts = vec!["Google".into()];
+        let mut e = AlertEngine::new(vec![google]);
+        let half = |id: &str, name: &str, state| HostView {
+            stack: Some("Google".into()),
+            ..host(id, name, state)
+        };
+        let states = vec![
+            half("g4", "Google (IPv4)", up()),
+            half("g6", "Google (IPv6)", down()),
+            host("h2", "Google DNS", down()),
+        ];
+        let fired = e.evaluate(Instant::now(), &states, &ConnectivityState::Online);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].host_id.as_deref(), Some("g6"));
+        assert_eq!(fired[0].subject, "Google (IPv6)");
+    }
+
+    #[test]
+    fn host_filter_and_network_rules() {
+        let mut vpn_only = rule(AlertCondition::Down, 0.0, 0.0);
+        vpn_only.hosts = vec!["VPN".into()];
+        let mut e = AlertEngine::new(vec![
+            vpn_only,
+            rule(AlertCondition::CaptivePortal, 0.0, 0.0),
+        ]);
+        let states = vec![host("h1", "VPN", down()), host("h2", "Router", down())];
+        let portal = ConnectivityState::CaptivePortal {
+            url: "http://captive.apple.com".into(),
+        };
+        let fired = e.evaluate(Instant::now(), &states, &portal);
+        assert_eq!(fired.len(), 2);
+        assert_eq!(fired[0].host_id.as_deref(), Some("h1"));
+        assert_eq!(fired[1].subject, "network");
+        assert_eq!(fired[1].host_id, None);
+        assert!(fired[1].detail.contains("captive.apple.com"));
+    }
+
+    #[test]
+    fn sinks_are_checked_at_startup() {
+        assert!(check_sinks(&[SinkConfig::Webhook {
+            url: "ftp://x".into()
+        }])
+        .is_err());
+        assert!(check_sinks(&[
+            SinkConfig::Bell,
+            SinkConfig::Log {
+                path: "a.log".into()
+            }
+        ])
+        .is_ok());
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            rule: "down".into(),
+            status: AlertStatus::Firing,
+            subject: "VPN".into(),
+            host_id: Some("h1".into()),
+            detail: "down: no replies".into(),
+            at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_alert_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut req = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&req).contains("\"detail\"") {
+                let n = sock.read(&mut buf).await.unwrap();
+                req.extend_from_slice(&buf[..n]);
+            }
+            sock.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(req).unwrap()
+        });
+        deliver(&SinkConfig::Webhook { url }, &alert())
+            .await
+            .unwrap();
+        let req = server.await.unwrap();
+        assert!(req.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(req.contains("Content-Type: application/json"));
+        let body = &req[req.find("\r\n\r\n").unwrap() + 4..];
+        let v: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(v["status"], "firing");
+        assert_eq!(v["subject"], "VPN");
+    }
+
+    #[tokio::test]
+    async fn command_and_log_sinks() {
+        let dir = std::env::temp_dir().join(format!("pingpong-alerts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("hook.txt");
+        let log = dir.join("alerts.log");
+        let command = format!(
+            "printf '%s %s %s' \"$PINGPONG_ALERT_STATUS\" \"$PINGPONG_ALERT_SUBJECT\" \"$PINGPONG_ALERT_HOST_ID\" > {}",
+            out.display()
+        );
+        deliver(&SinkConfig::Command { command }, &alert())
+            .await
+            .unwrap();
+        deliver(
+            &SinkConfig::Log {
+                path: log.display().to_string(),
+            },
+            &alert(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "firing VPN h1");
+        let logged = std::fs::read_to_string(&log).unwrap();
+        assert!(logged.contains("FIRING down: VPN \u{2014} down: no replies"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
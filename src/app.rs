@@ -2,87 +2,318 @@
 // ABOUTME: Manages the event loop between ping results and UI updates
 
 use anyhow::Result;
-use std::collections::HashMap;
-use std::time::Duration;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::signal;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
 use tokio::time;
 
-use crate::config::Config;
-use crate::ping::{HostUpdate, PingEngine, PingEvent};
+use crate::alerts::{self, AlertEngine, HostView};
+use crate::config::{
+    Config, ConfigWarning, Family, Host, HostEdit, Overrides, ProbeKind, SinkConfig,
+};
+use crate::discover;
+use crate::http::HttpPhases;
+use crate::netwatch::{self, NetworkChange};
+use crate::output::JsonlWriter;
+use crate::ping::{EngineCommand, HostUpdate, PingEngine, PingEvent};
 use crate::probe::ProbeResult;
-use crate::stats::PingStats;
-use crate::status::{self, ConnectivityState, HostState};
-use crate::tui::{AnimationType, TuiApp};
+use crate::session::{self, Playback, Recorder, ReplayControl, Session, SessionEvent, SessionHost};
+use crate::stats::{Marker, PingStats};
+use crate::status::{self, ConnectivityState, HostState, Transitions};
+use crate::storage::{Record, Store};
+use crate::trace::{PathView, TraceRound};
+use crate::tui::{AnimationType, TuiApp, UiAction};
+
+/// Where events come from: the live engine (optionally recorded to a file), or a recording.
+pub enum Source {
+    Live { record: Option<PathBuf> },
+    Replay { session: Session, speed: f64 },
+}
+
+/// Startup choices from the command line.
+pub struct Options {
+    pub animation_type: Option<AnimationType>,
+    /// Leave the terminal alone and stream events to stdout as JSON lines.
+    pub headless: bool,
+    /// Serve Prometheus metrics at `/metrics` on this address.
+    pub metrics_listen: Option<SocketAddr>,
+    pub source: Source,
+    /// Where the config came from; the host editor writes back here and changes are reloaded.
+    pub config_path: PathBuf,
+    /// The config as read from the file, before the profile and other overrides;
+    /// switching profiles starts again from here.
+    pub file_config: Config,
+    /// Command-line settings to layer over a reloaded config.
+    pub overrides: Overrides,
+    /// What was wrong with the config as loaded; shown until the file is fixed.
+    pub config_warnings: Vec<ConfigWarning>,
+}
+
+/// An enabled host and the settings its loop runs under: the host itself, its
+/// effective interval, and the global timeout and packet size. A change means a restart.
+type RunningHost = (Host, f64, f64, u16);
 
 pub struct App {
     config: Config,
-    tui: TuiApp,
+    // Config file being watched, what it says before `overrides` (which carries the
+    // profile), when it was last read, and what's wrong with it (a rejected reload, or
+    // values that were ignored or replaced) for the banner.
+    config_path: PathBuf,
+    file_config: Config,
+    overrides: Overrides,
+    config_mtime: Option<SystemTime>,
+    config_notice: Option<String>,
+    // Exactly one frontend is set: the TUI, or the JSON-lines stream in headless mode.
+    tui: Option<TuiApp>,
+    jsonl: Option<JsonlWriter<io::Stdout>>,
+    // Latest Prometheus snapshot, published each tick when the metrics listener is on.
+    metrics_tx: Option<watch::Sender<String>>,
+    // History database writer, when [storage] is enabled, and the last state it recorded per host.
+    store_tx: Option<mpsc::UnboundedSender<Record>>,
+    // A read handle on the same database, for the history of hosts that start later.
+    history: Option<Store>,
+    stored_states: Transitions,
+    // Alert rules being evaluated each tick, and where their notifications go.
+    alerts: Option<AlertEngine>,
+    alert_sinks: Vec<SinkConfig>,
+    // Recording being written for `pingpong record`.
+    recorder: Option<Recorder<std::io::BufWriter<std::fs::File>>>,
+    // Recording being played back instead, and what it sends, in order.
+    replay: Option<Replay>,
+    playback_rx: Option<mpsc::Receiver<Playback>>,
+    // Control channel into the live ping engine (None while replaying), and the hosts it
+    // has reported paused.
+    engine_tx: Option<mpsc::Sender<EngineCommand>>,
+    paused: HashSet<String>,
+    // Set once the engine's events have closed (from the start when replaying; a
+    // replay's events come through playback).
+    events_done: bool,
     stats: HashMap<String, PingStats>,
+    // Stats of each address an `all_addresses` host is probing, keyed by host id; the
+    // host's own stats take one result per round, the fastest of its addresses.
+    addresses: HashMap<String, Vec<(IpAddr, PingStats)>>,
     // Per-host DNS resolution state, keyed by host id; read by the connectivity status renderer.
     resolved: HashMap<String, bool>,
+    // Per-host current resolved address, keyed by host id; the path view traces toward it.
+    resolved_ip: HashMap<String, IpAddr>,
+    // Per-host address before its last change, keyed by host id; read by the host header.
+    previous_ip: HashMap<String, IpAddr>,
     // Per-host last resolution error (None once resolved), keyed by host id; read by the error banner.
     resolve_err: HashMap<String, Option<String>>,
+    // Phase breakdown of each HTTP host's latest successful request; read by the host header.
+    http_phases: HashMap<String, HttpPhases>,
+    // Most recent DNS answer-set change per host, as "old → new"; read by the host header.
+    answer_change: HashMap<String, String>,
     // Latest captive-portal/connectivity classification from the probe; read by the connectivity banner.
     portal: ProbeResult,
     event_rx: mpsc::Receiver<PingEvent>,
     probe_rx: mpsc::Receiver<ProbeResult>,
+    // Changes to the machine's network (interfaces, addresses, default routes), and
+    // whether anything is watching for them; without a watcher `auto_hosts` are polled.
+    net_rx: mpsc::Receiver<NetworkChange>,
+    net_watched: bool,
     // (host_id, display name) pairs identifying each monitored host; used to label host rows.
     host_info: Vec<(String, String)>,
+    // Probe type per host id; decides which extra detail the host header shows.
+    probe_kinds: HashMap<String, ProbeKind>,
+    // Group name per host id, for hosts that have one; the host list is sectioned by it.
+    host_groups: HashMap<String, String>,
+    // Ids of the monitored hosts each host id depends on; read by the root-cause banner.
+    host_deps: HashMap<String, Vec<String>>,
+    // Dual-stack host name and family per id of each half; read by the root-cause banner.
+    host_stacks: HashMap<String, (String, Family)>,
+    // Open path (traceroute) view, its round receiver, and the task feeding it.
+    path: Option<PathView>,
+    trace_rx: Option<mpsc::Receiver<TraceRound>>,
+    trace_task: Option<JoinHandle<()>>,
 }
 
 impl App {
-    pub async fn new(config: Config, animation_type: Option<AnimationType>) -> Result<Self> {
-        // Create event channel
+    pub async fn new(config: Config, opts: Options) -> Result<Self> {
+        // Create event channels
         let (event_tx, event_rx) = mpsc::channel(1024);
+        let (probe_tx, probe_rx) = mpsc::channel::<ProbeResult>(8);
+        let (net_tx, net_rx) = mpsc::channel::<NetworkChange>(8);
+        let mut net_watched = false;
 
-        // Get enabled hosts
-        let hosts: Vec<_> = config.enabled_hosts().cloned().collect();
+        let (hosts, recorder, replay, playback_rx, engine_tx) = match opts.source {
+            Source::Live { record } => {
+                // Get enabled hosts, dual-stack ones as their two halves
+                let enabled = config.probed_hosts();
 
-        // Initialize ping engine (synchronous, no DNS at construction time)
-        let ping_engine = PingEngine::new(hosts, config.ping.clone(), event_tx);
+                // Initialize ping engine (synchronous, no DNS at construction time)
+                let ping_engine = PingEngine::new(enabled.clone(), config.ping.clone(), event_tx);
+                let hosts = session_hosts(&enabled);
 
-        // Get host info before moving ping_engine
-        let host_info = ping_engine.get_host_info();
+                let recorder = match record {
+                    Some(path) => Some(Recorder::create(&path, hosts.clone())?),
+                    None => None,
+                };
 
-        // Initialize TUI
-        let mut tui = TuiApp::new(animation_type).await?;
-        tui.set_host_info(host_info.clone());
-        tui.set_ui_config(
-            config.ui.theme.clone(),
-            config.ui.show_details,
-            config.ui.graph_height,
-        );
+                // Start ping engine in background
+                let (engine_tx, engine_rx) = mpsc::channel(64);
+                tokio::spawn(async move {
+                    let _ = ping_engine.start(engine_rx).await;
+                });
 
-        // Start ping engine in background
-        tokio::spawn(async move {
-            let _ = ping_engine.start().await;
-        });
+                // Start captive-portal probe loop in background
+                let portal_url = config.ping.portal_check_url.clone();
+                tokio::spawn(async move {
+                    let mut tick = tokio::time::interval(Duration::from_secs(10));
+                    loop {
+                        tick.tick().await;
+                        let r = crate::probe::probe_once(&portal_url).await;
+                        if probe_tx.send(r).await.is_err() {
+                            break;
+                        }
+                    }
+                });
 
-        // Start captive-portal probe loop in background
-        let (probe_tx, probe_rx) = mpsc::channel::<ProbeResult>(8);
-        let portal_url = config.ping.portal_check_url.clone();
-        tokio::spawn(async move {
-            let mut tick = tokio::time::interval(Duration::from_secs(10));
-            loop {
-                tick.tick().await;
-                let r = crate::probe::probe_once(&portal_url).await;
-                if probe_tx.send(r).await.is_err() {
-                    break;
-                }
+                // Without a watcher (another OS, no netlink) hosts still re-resolve
+                // once their probes have failed a few times.
+                net_watched = netwatch::spawn(net_tx).is_ok();
+
+                (hosts, recorder, None, None, Some(engine_tx))
             }
-        });
+            Source::Replay { session, speed } => {
+                // The recording says what was monitored; the config's hosts don't matter here
+                let (playback_tx, playback_rx) = mpsc::channel(1024);
+                let (control_tx, control_rx) = mpsc::channel(8);
+                let replay = Replay {
+                    started: session.header.started,
+                    speed,
+                    at_ms: 0,
+                    total_ms: session.entries.last().map_or(0, |e| e.at_ms),
+                    paused: false,
+                    ended: false,
+                    // Headless there's nobody to steer, so it plays through once and ends.
+                    control: (!opts.headless).then_some(control_tx),
+                };
+                let hosts = session.header.hosts.clone();
+                tokio::spawn(session::replay(session, speed, playback_tx, control_rx));
+                (hosts, None, Some(replay), Some(playback_rx), None)
+            }
+        };
+        let HostIndex {
+            info: host_info,
+            probe_kinds,
+            groups: host_groups,
+            deps: host_deps,
+            stacks: host_stacks,
+        } = HostIndex::new(&hosts);
+
+        // Hand the store to its writer, keeping a second connection to reload recent
+        // history from, so the sparklines start full.
+        let mut stats = HashMap::new();
+        // A replay is someone else's past, so it is never written to (or mixed with) local history.
+        let (store_tx, history) = if config.storage.enabled && replay.is_none() {
+            let mut store = Store::open(&config.storage)?;
+            store.compact(Utc::now())?;
+            let history = Store::open(&config.storage)?;
+            for (id, _) in &host_info {
+                stats.insert(
+                    id.clone(),
+                    history.recent_stats(id, config.ping.history_size)?,
+                );
+            }
+            (Some(store.spawn_writer()), Some(history))
+        } else {
+            (None, None)
+        };
+
+        // Alerts watch live monitoring only; replaying an old outage must not page anyone again.
+        let alerts = if config.alerts.rules.is_empty() || replay.is_some() {
+            None
+        } else {
+            alerts::check_sinks(&config.alerts.sinks)?;
+            Some(AlertEngine::new(config.alerts.rules.clone()))
+        };
+        let alert_sinks = config.alerts.sinks.clone();
+
+        // Bind the metrics listener before touching the terminal so a bad address reads cleanly
+        let metrics_tx = match opts.metrics_listen {
+            Some(addr) => {
+                let listener = crate::metrics::bind(addr).await?;
+                let (tx, rx) = watch::channel(String::new());
+                tokio::spawn(crate::metrics::serve(listener, rx));
+                Some(tx)
+            }
+            None => None,
+        };
+
+        // Initialize the frontend
+        let (tui, jsonl) = if opts.headless {
+            let mut out = JsonlWriter::new(io::stdout());
+            for warning in &opts.config_warnings {
+                out.config_warning(&warning.to_string())?;
+            }
+            (None, Some(out))
+        } else {
+            let mut tui = TuiApp::new(opts.animation_type).await?;
+            tui.set_host_info(host_info.clone());
+            tui.set_host_groups(host_groups.clone());
+            // Only a live engine can take edits; a replay shows what was recorded.
+            if engine_tx.is_some() {
+                tui.set_hosts(opts.file_config.hosts.clone());
+            } else {
+                tui.set_replaying();
+            }
+            tui.set_ui_config(
+                config.ui.theme.clone(),
+                config.ui.show_details,
+                config.ui.graph_height,
+            );
+            (Some(tui), None)
+        };
 
         Ok(Self {
             config,
+            config_mtime: modified(&opts.config_path),
+            config_notice: warning_notice(&opts.config_path, &opts.config_warnings),
+            config_path: opts.config_path,
+            file_config: opts.file_config,
+            overrides: opts.overrides,
             tui,
-            stats: HashMap::new(),
+            jsonl,
+            metrics_tx,
+            store_tx,
+            history,
+            stored_states: Transitions::default(),
+            alerts,
+            alert_sinks,
+            recorder,
+            events_done: replay.is_some(),
+            replay,
+            playback_rx,
+            engine_tx,
+            paused: HashSet::new(),
+            stats,
+            addresses: HashMap::new(),
             resolved: HashMap::new(),
+            resolved_ip: HashMap::new(),
+            previous_ip: HashMap::new(),
             resolve_err: HashMap::new(),
+            http_phases: HashMap::new(),
+            answer_change: HashMap::new(),
             portal: ProbeResult::Offline,
             event_rx,
             probe_rx,
+            net_rx,
+            net_watched,
             host_info,
+            probe_kinds,
+            host_groups,
+            host_deps,
+            host_stacks,
+            path: None,
+            trace_rx: None,
+            trace_task: None,
         })
     }
 
@@ -90,53 +321,72 @@ impl App {
         // Main event loop
         let mut ui_update_interval =
             time::interval(Duration::from_millis(self.config.ui.refresh_rate));
+        let mut config_check = time::interval(Duration::from_secs(2));
 
         loop {
             tokio::select! {
                 // Handle ping events
-                event = self.event_rx.recv() => {
-                    if let Some(ping_event) = event {
-                        self.handle_ping_event(ping_event);
+                event = self.event_rx.recv(), if !self.events_done => match event {
+                    Some(ping_event) => {
+                        if let Some(rec) = self.recorder.as_mut() {
+                            rec.record(SessionEvent::Ping(ping_event.clone()))?;
+                        }
+                        if let Some(out) = self.jsonl.as_mut() {
+                            out.ping_event(&ping_event)?;
+                        }
+                        self.handle_ping_event(ping_event)?;
                     }
-                }
+                    None => {
+                        self.events_done = true;
+                        if !self.source_done().await? {
+                            break;
+                        }
+                    }
+                },
+
+                // Apply a replay's events and follow its clock
+                playback = recv_opt(&mut self.playback_rx) => match playback {
+                    Some(playback) => self.handle_playback(playback).await?,
+                    None => {
+                        self.playback_rx = None;
+                        if !self.source_done().await? {
+                            break;
+                        }
+                    }
+                },
 
                 // Store the latest captive-portal probe result
-                Some(p) = self.probe_rx.recv() => { self.portal = p; }
+                Some(p) = self.probe_rx.recv() => self.handle_portal(p)?,
+
+                // Re-resolve and mark the graphs as soon as the network changes
+                Some(change) = self.net_rx.recv() => self.handle_network_change(change).await?,
 
-                // Update UI
+                // Fold each traceroute round into the open path view
+                Some(round) = recv_opt(&mut self.trace_rx) => {
+                    if let Some(view) = self.path.as_mut() {
+                        view.apply(round);
+                    }
+                }
+
+                // Update UI, or emit state transitions when headless
                 // Errors propagate out of run; App's Drop restores the terminal before main prints them.
                 _ = ui_update_interval.tick() => {
-                    let host_states: Vec<(String, HostState)> = self
-                        .host_info
-                        .iter()
-                        .map(|(id, _)| {
-                            let resolved = *self.resolved.get(id).unwrap_or(&false);
-                            let err = self.resolve_err.get(id).and_then(|o| o.as_deref());
-                            (id.clone(), status::host_state(self.stats.get(id), resolved, err))
-                        })
-                        .collect();
-                    let states: Vec<HostState> = host_states.iter().map(|(_, s)| s.clone()).collect();
-                    let conn = status::connectivity(&states, &self.portal);
-                    let agg = status::aggregate(&states);
-                    self.tui.set_title(&status::title(&conn, &agg));
-                    let banner = match &conn {
-                        ConnectivityState::CaptivePortal { url } => {
-                            Some(format!("\u{26a0}  Captive portal detected \u{2014} open {url}"))
-                        }
-                        ConnectivityState::Offline => {
-                            Some("\u{2717}  Offline \u{2014} no connectivity".to_string())
-                        }
-                        _ => None,
-                    };
-                    let opts = crate::tui::RenderOpts {
-                        theme: crate::tui::Theme::from_name(self.tui.theme_name()),
-                        show_details: self.tui.show_details(),
-                        graph_height: self.config.ui.graph_height,
-                        banner,
-                        host_states,
-                    };
-                    self.tui.draw(&self.stats, &opts).await?;
-                    if self.tui.handle_events().await? { break; }
+                    if !self.tick().await? {
+                        break;
+                    }
+                }
+
+                // Pick up edits to the config file and a move to another network
+                _ = config_check.tick() => {
+                    let refresh_rate = self.config.ui.refresh_rate;
+                    self.check_config().await?;
+                    if !self.net_watched {
+                        self.check_network().await?;
+                    }
+                    if self.config.ui.refresh_rate != refresh_rate {
+                        ui_update_interval =
+                            time::interval(Duration::from_millis(self.config.ui.refresh_rate));
+                    }
                 }
 
                 // Ctrl-C signal path for pre-/non-raw-mode window
@@ -149,7 +399,268 @@ impl App {
         Ok(())
     }
 
-    fn handle_ping_event(&mut self, event: PingEvent) {
+    /// The event source has closed: drain the last portal results, and when headless,
+    /// flush the final states. Returns false when that means it's time to exit.
+    async fn source_done(&mut self) -> Result<bool> {
+        while let Ok(p) = self.probe_rx.try_recv() {
+            self.handle_portal(p)?;
+        }
+        if self.jsonl.is_some() {
+            self.tick().await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Apply what a replay sent: a recorded event, as if it were happening now, or
+    /// where its clock is.
+    async fn handle_playback(&mut self, playback: Playback) -> Result<()> {
+        match playback {
+            Playback::Event(entry) => {
+                if let Some(replay) = self.replay.as_mut() {
+                    replay.at_ms = entry.at_ms;
+                }
+                match entry.event {
+                    SessionEvent::Ping(event) => {
+                        if let Some(out) = self.jsonl.as_mut() {
+                            out.ping_event(&event)?;
+                        }
+                        self.handle_ping_event(event)?;
+                    }
+                    SessionEvent::Portal(result) => self.handle_portal(result)?,
+                    SessionEvent::NetworkChange(change) => {
+                        self.handle_network_change(change).await?
+                    }
+                    SessionEvent::Hosts(hosts) => self.set_host_list(&hosts),
+                }
+            }
+            Playback::Restart => self.forget_hosts(),
+            Playback::At { at_ms, paused } => {
+                if let Some(replay) = self.replay.as_mut() {
+                    replay.at_ms = at_ms;
+                    replay.paused = paused;
+                }
+            }
+            Playback::Ended => {
+                if let Some(replay) = self.replay.as_mut() {
+                    replay.ended = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget everything seen of the hosts, for a replay that goes back to its start.
+    fn forget_hosts(&mut self) {
+        self.close_path();
+        self.stats.clear();
+        self.addresses.clear();
+        self.resolved.clear();
+        self.resolved_ip.clear();
+        self.previous_ip.clear();
+        self.resolve_err.clear();
+        self.http_phases.clear();
+        self.answer_change.clear();
+        self.paused.clear();
+        self.portal = ProbeResult::Offline;
+        if let Some(replay) = self.replay.as_mut() {
+            replay.ended = false;
+        }
+    }
+
+    /// Publish metrics, record state transitions, and draw the TUI or emit them as
+    /// JSON lines. Returns false when the user quits.
+    async fn tick(&mut self) -> Result<bool> {
+        let host_states = self.host_states();
+        if let Some(engine) = self.alerts.as_mut() {
+            let states: Vec<HostState> = host_states.iter().map(|(_, s)| s.clone()).collect();
+            let conn = status::connectivity(&states, &self.portal);
+            let named: Vec<_> = self
+                .host_info
+                .iter()
+                .zip(&host_states)
+                .map(|((id, name), (_, state))| HostView {
+                    id: id.clone(),
+                    name: name.clone(),
+                    stack: self.host_stacks.get(id).map(|(stack, _)| stack.clone()),
+                    state: state.clone(),
+                })
+                .collect();
+            for alert in engine.evaluate(Instant::now(), &named, &conn) {
+                alerts::dispatch(&self.alert_sinks, alert);
+            }
+        }
+        if let Some(tx) = &self.metrics_tx {
+            let states: Vec<HostState> = host_states.iter().map(|(_, s)| s.clone()).collect();
+            let conn = status::connectivity(&states, &self.portal);
+            let _ = tx.send(crate::metrics::render(
+                &self.host_info,
+                &self.stats,
+                &host_states,
+                &conn,
+            ));
+        }
+        if let Some(tx) = &self.store_tx {
+            for ((id, name), (_, state)) in self.host_info.iter().zip(&host_states) {
+                if self.stored_states.changed(id, state) {
+                    let _ = tx.send(Record::Transition {
+                        at: Utc::now(),
+                        host_id: id.clone(),
+                        host: name.clone(),
+                        state: state.clone(),
+                    });
+                }
+            }
+        }
+        if let Some(out) = self.jsonl.as_mut() {
+            for ((id, name), (_, state)) in self.host_info.iter().zip(&host_states) {
+                out.host_state(id, name, state)?;
+            }
+            return Ok(true);
+        }
+        self.render(host_states).await
+    }
+
+    fn handle_portal(&mut self, result: ProbeResult) -> Result<()> {
+        if let Some(rec) = self.recorder.as_mut() {
+            rec.record(SessionEvent::Portal(result.clone()))?;
+        }
+        if let Some(out) = self.jsonl.as_mut() {
+            out.portal(&result)?;
+        }
+        self.portal = result;
+        Ok(())
+    }
+
+    fn host_states(&self) -> Vec<(String, HostState)> {
+        self.host_info
+            .iter()
+            .map(|(id, _)| {
+                let resolved = *self.resolved.get(id).unwrap_or(&false);
+                let err = self.resolve_err.get(id).and_then(|o| o.as_deref());
+                let state = match self.address_states(id) {
+                    Some(states) if resolved && err.is_none() => status::roll_up(&states),
+                    _ => status::host_state(self.stats.get(id), resolved, err),
+                };
+                let probe = self.probe_kinds.get(id).copied().unwrap_or(ProbeKind::Icmp);
+                let state = status::with_min_mos(
+                    state,
+                    probe,
+                    self.stats.get(id),
+                    self.config.ping.min_mos,
+                );
+                (id.clone(), state)
+            })
+            .collect()
+    }
+
+    /// The state of each address a host is probing, in resolver order; None unless
+    /// it probes every address.
+    fn address_states(&self, host_id: &str) -> Option<Vec<HostState>> {
+        let addresses = self.addresses.get(host_id)?;
+        Some(
+            addresses
+                .iter()
+                .map(|(_, stats)| status::host_state(Some(stats), true, None))
+                .collect(),
+        )
+    }
+
+    /// Draw one TUI frame and handle pending input. Returns false when the user quits.
+    async fn render(&mut self, host_states: Vec<(String, HostState)>) -> Result<bool> {
+        let states: Vec<HostState> = host_states.iter().map(|(_, s)| s.clone()).collect();
+        let conn = status::connectivity(&states, &self.portal);
+        let agg = status::aggregate(&states);
+        // Hosts depending on what's down explain why, rather than just that, things are broken.
+        let cause = status::root_cause(
+            &host_states,
+            &self.host_deps,
+            &self.host_info,
+            &self.host_stacks,
+        );
+        let banner = match (&conn, cause) {
+            (ConnectivityState::CaptivePortal { url }, _) => Some(format!(
+                "\u{26a0}  Captive portal detected \u{2014} open {url}"
+            )),
+            (ConnectivityState::Offline, Some(cause)) => {
+                Some(format!("\u{2717}  Offline \u{2014} {cause}"))
+            }
+            (ConnectivityState::Offline, None) => {
+                Some("\u{2717}  Offline \u{2014} no connectivity".to_string())
+            }
+            (ConnectivityState::Degraded, Some(cause)) => Some(format!("\u{25d0}  {cause}")),
+            _ => None,
+        };
+        let blocked = status::blocked(&host_states, &self.host_deps);
+        // While replaying, say so in front of whatever the recording itself shows; a
+        // rejected config edit goes in front of that.
+        let replay = self.replay.as_ref().map(Replay::note);
+        let parts: Vec<String> = [self.config_notice.clone(), replay, banner]
+            .into_iter()
+            .flatten()
+            .collect();
+        let banner = (!parts.is_empty()).then(|| parts.join("  \u{b7}  "));
+        let host_notes = self.host_notes();
+        let host_ips = self
+            .resolved_ip
+            .iter()
+            .map(|(id, ip)| {
+                let text = match self.previous_ip.get(id) {
+                    Some(old) => format!("{ip} (was {old})"),
+                    None => ip.to_string(),
+                };
+                (id.clone(), text)
+            })
+            .collect();
+        let groups = status::by_group(&host_states, &self.host_groups, &self.portal);
+        let path = self.path.as_ref().map(|v| v.panel());
+        let address_states = self
+            .addresses
+            .keys()
+            .filter_map(|id| Some((id.clone(), self.address_states(id)?)))
+            .collect();
+        let Some(tui) = self.tui.as_mut() else {
+            return Ok(true);
+        };
+        tui.set_title(&status::title(&conn, &agg));
+        let opts = crate::tui::RenderOpts {
+            theme: crate::tui::Theme::from_name(tui.theme_name()),
+            show_details: tui.show_details(),
+            graph_height: self.config.ui.graph_height,
+            banner,
+            host_states,
+            address_states,
+            host_ips,
+            host_notes,
+            groups,
+            blocked,
+            path,
+            paused: self.paused.clone(),
+            profile: self.config.profile.clone(),
+            icmp_hosts: self
+                .probe_kinds
+                .iter()
+                .filter(|(_, kind)| **kind == ProbeKind::Icmp)
+                .map(|(id, _)| id.clone())
+                .collect(),
+        };
+        tui.draw(&self.stats, &self.addresses, &opts).await?;
+        match tui.handle_events().await? {
+            UiAction::Quit => return Ok(false),
+            UiAction::OpenPath(host_id) => self.open_path(host_id),
+            UiAction::ClosePath => self.close_path(),
+            UiAction::TogglePause(host_id) => self.toggle_pause(host_id),
+            UiAction::Step => self.steer(ReplayControl::Step),
+            UiAction::Seek(offset_ms) => self.steer(ReplayControl::Seek(offset_ms)),
+            UiAction::EditHost(edit) => self.edit_hosts(edit).await?,
+            UiAction::SaveHosts => self.save_hosts(),
+            UiAction::CycleProfile => self.cycle_profile().await?,
+            UiAction::None => {}
+        }
+        Ok(true)
+    }
+
+    fn handle_ping_event(&mut self, event: PingEvent) -> Result<()> {
         match event.update {
             HostUpdate::Resolving => {
                 self.resolved.insert(event.host_id.clone(), false);
@@ -161,17 +672,592 @@ impl App {
                 self.resolved.insert(event.host_id.clone(), false);
                 self.resolve_err.insert(event.host_id.clone(), Some(e));
             }
-            HostUpdate::Resolved(_) => {
+            HostUpdate::Resolved(ip) => {
                 self.resolved.insert(event.host_id.clone(), true);
                 self.resolve_err.insert(event.host_id.clone(), None);
+                // A host probing every address may just lead with a different one of
+                // them (round-robin DNS); only a new address is a move.
+                let known = self
+                    .addresses
+                    .get(&event.host_id)
+                    .is_some_and(|a| a.iter().any(|(addr, _)| *addr == ip));
+                let previous = self.resolved_ip.insert(event.host_id.clone(), ip);
+                if let Some(old) = previous.filter(|old| *old != ip && !known) {
+                    // Failovers and GeoDNS flips explain most RTT step changes; mark them.
+                    self.previous_ip.insert(event.host_id.clone(), old);
+                    if let Some(out) = self.jsonl.as_mut() {
+                        out.address_change(&event.host_id, &event.host_name, old, ip)?;
+                    }
+                    self.stats
+                        .entry(event.host_id.clone())
+                        .or_insert_with(|| PingStats::new(self.config.ping.history_size))
+                        .add_marker(Marker::AddressChange);
+                }
+            }
+            HostUpdate::HttpTiming(phases) => {
+                self.http_phases.insert(event.host_id.clone(), phases);
+            }
+            HostUpdate::AnswersChanged { previous, current } => {
+                self.answer_change.insert(
+                    event.host_id.clone(),
+                    format!("{} \u{2192} {}", previous.join(","), current.join(",")),
+                );
+                self.stats
+                    .entry(event.host_id.clone())
+                    .or_insert_with(|| PingStats::new(self.config.ping.history_size))
+                    .record_answer_change();
+            }
+            HostUpdate::Addresses(ips) => {
+                // Addresses the host still has keep their history across a re-resolve.
+                let history = self.config.ping.history_size;
+                let mut previous = self.addresses.remove(&event.host_id).unwrap_or_default();
+                let current = ips
+                    .into_iter()
+                    .map(|ip| match previous.iter().position(|(old, _)| *old == ip) {
+                        Some(i) => previous.swap_remove(i),
+                        None => (ip, PingStats::new(history)),
+                    })
+                    .collect();
+                self.addresses.insert(event.host_id.clone(), current);
+            }
+            HostUpdate::AddressPinged { ip, result } => {
+                if let Some((_, stats)) = self
+                    .addresses
+                    .get_mut(&event.host_id)
+                    .and_then(|a| a.iter_mut().find(|(addr, _)| *addr == ip))
+                {
+                    stats.add_result(&result);
+                }
             }
             HostUpdate::Pinged(result) => {
+                if let Some(tx) = &self.store_tx {
+                    let _ = tx.send(Record::Ping {
+                        at: Utc::now(),
+                        host_id: event.host_id.clone(),
+                        host: event.host_name.clone(),
+                        result: result.clone(),
+                    });
+                }
                 let entry = self
                     .stats
                     .entry(event.host_id.clone())
                     .or_insert_with(|| PingStats::new(self.config.ping.history_size));
                 entry.add_result(&result);
+                // Consecutive rounds may be answered by different addresses; jitter is
+                // only meaningful along one path, so it comes from the addresses' own.
+                if let Some(addresses) = self.addresses.get(&event.host_id) {
+                    entry.take_jitter_from(addresses.iter().map(|(_, s)| s));
+                }
+            }
+            // A restarted host reports its pause again; one marker per pause is enough.
+            HostUpdate::Paused if self.paused.contains(&event.host_id) => {}
+            HostUpdate::Paused => {
+                self.paused.insert(event.host_id.clone());
+                self.stats
+                    .entry(event.host_id.clone())
+                    .or_insert_with(|| PingStats::new(self.config.ping.history_size))
+                    .add_marker(Marker::Pause);
+                for (_, stats) in self.addresses.get_mut(&event.host_id).into_iter().flatten() {
+                    stats.add_marker(Marker::Pause);
+                }
+            }
+            HostUpdate::Resumed => {
+                self.paused.remove(&event.host_id);
             }
         }
+        Ok(())
+    }
+
+    /// The machine's network changed: mark every graph (or start its stats over), pick
+    /// up a new gateway or DNS servers, and have every host resolve again now rather
+    /// than after its probes have failed a few times.
+    async fn handle_network_change(&mut self, change: NetworkChange) -> Result<()> {
+        if let Some(rec) = self.recorder.as_mut() {
+            rec.record(SessionEvent::NetworkChange(change.clone()))?;
+        }
+        if let Some(out) = self.jsonl.as_mut() {
+            out.network_change(&change)?;
+        }
+        let addresses = self.addresses.values_mut().flatten().map(|(_, s)| s);
+        for stats in self.stats.values_mut().chain(addresses) {
+            if self.config.ping.reset_on_network_change {
+                stats.start_epoch(Marker::NetworkChange);
+            } else {
+                stats.add_marker(Marker::NetworkChange);
+            }
+        }
+        self.check_network().await?;
+        if let Some(tx) = &self.engine_tx {
+            let _ = tx.send(EngineCommand::Reresolve).await;
+        }
+        Ok(())
+    }
+
+    /// Pause one host, or every host, or resume them if already paused. With some but
+    /// not all hosts paused, the all-hosts toggle pauses the rest.
+    fn toggle_pause(&mut self, host_id: Option<String>) {
+        // A replay pauses its playback as a whole; there are no probes to hold.
+        if self.replay.is_some() {
+            if host_id.is_none() {
+                self.steer(ReplayControl::TogglePause);
+            }
+            return;
+        }
+        let Some(tx) = &self.engine_tx else {
+            return;
+        };
+        let paused = match &host_id {
+            Some(id) => !self.paused.contains(id),
+            None => self.paused.len() < self.host_info.len(),
+        };
+        let _ = tx.try_send(EngineCommand::SetPaused { host_id, paused });
+    }
+
+    /// Pause, step or seek the replay, if one is playing and can be steered.
+    fn steer(&self, command: ReplayControl) {
+        if let Some(tx) = self.replay.as_ref().and_then(|r| r.control.as_ref()) {
+            let _ = tx.try_send(command);
+        }
+    }
+
+    /// Reload the config file if it changed on disk since it was last read. A file that
+    /// doesn't parse leaves everything running as it was and says why in the banner.
+    async fn check_config(&mut self) -> Result<()> {
+        // A replay shows the recording's hosts; there is nothing to reconfigure.
+        if self.engine_tx.is_none() {
+            return Ok(());
+        }
+        let mtime = modified(&self.config_path);
+        if mtime.is_none() || mtime == self.config_mtime {
+            return Ok(());
+        }
+        self.config_mtime = mtime;
+        // The profile in use must still be there; without it there's nothing sensible to run.
+        let loaded = Config::load_checked(&self.config_path).and_then(|(file, mut warnings)| {
+            let mut config = file.clone();
+            warnings.extend(self.overrides.apply(&mut config)?);
+            config.check_hosts()?;
+            Ok((file, config, warnings))
+        });
+        let (file, config, warnings) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                let reason = format!("{e:#}");
+                if let Some(out) = self.jsonl.as_mut() {
+                    out.config_error(&reason)?;
+                }
+                self.config_notice =
+                    Some(format!("\u{26a0}  Config not reloaded \u{2014} {reason}"));
+                return Ok(());
+            }
+        };
+        self.file_config = file;
+        self.switch_config(config, warnings).await
+    }
+
+    /// Move to the next monitoring profile the file defines, or back to none after
+    /// the last one.
+    async fn cycle_profile(&mut self) -> Result<()> {
+        if self.engine_tx.is_none() || self.file_config.profiles.is_empty() {
+            return Ok(());
+        }
+        let mut names = self.file_config.profiles.keys();
+        self.overrides.profile = match &self.overrides.profile {
+            Some(current) => names.skip_while(|n| *n != current).nth(1),
+            None => names.next(),
+        }
+        .cloned();
+        self.rederive().await
+    }
+
+    /// Look at the network again; when the gateway or DNS servers changed, swap the
+    /// `auto_hosts` over to the new ones.
+    async fn check_network(&mut self) -> Result<()> {
+        if self.engine_tx.is_none() || self.file_config.auto_hosts.is_empty() {
+            return Ok(());
+        }
+        let found = discover::discover();
+        if found == self.overrides.discovered {
+            return Ok(());
+        }
+        self.overrides.discovered = found;
+        self.rederive().await
+    }
+
+    /// Run the file's config again under the current overrides, after one of them changed.
+    async fn rederive(&mut self) -> Result<()> {
+        let mut config = self.file_config.clone();
+        let warnings = self.overrides.apply(&mut config)?;
+        self.switch_config(config, warnings).await
+    }
+
+    /// Validate a config derived from the file, report what was wrong with it, and run it.
+    async fn switch_config(
+        &mut self,
+        mut config: Config,
+        mut warnings: Vec<ConfigWarning>,
+    ) -> Result<()> {
+        warnings.extend(config.validate());
+        if let Some(out) = self.jsonl.as_mut() {
+            for warning in &warnings {
+                out.config_warning(&warning.to_string())?;
+            }
+        }
+        self.config_notice = warning_notice(&self.config_path, &warnings);
+        self.apply_config(config).await
+    }
+
+    /// Switch to a reloaded config: start and stop hosts, restart only the loops whose
+    /// settings changed, and update UI settings and alert rules in place. Everything
+    /// else (storage, the portal URL, history size of running hosts) needs a restart.
+    async fn apply_config(&mut self, config: Config) -> Result<()> {
+        if config.alerts != self.config.alerts {
+            match alerts::check_sinks(&config.alerts.sinks) {
+                Ok(()) => {
+                    self.alerts = (!config.alerts.rules.is_empty())
+                        .then(|| AlertEngine::new(config.alerts.rules.clone()));
+                    self.alert_sinks = config.alerts.sinks.clone();
+                }
+                Err(e) => {
+                    self.config_notice =
+                        Some(format!("\u{26a0}  Alerts not reloaded \u{2014} {e:#}"))
+                }
+            }
+        }
+        if config.ui != self.config.ui {
+            if let Some(tui) = self.tui.as_mut() {
+                tui.set_ui_config(
+                    config.ui.theme.clone(),
+                    config.ui.show_details,
+                    config.ui.graph_height,
+                );
+            }
+        }
+        let before = Self::running_hosts(&self.config);
+        let ping_changed = config.ping != self.config.ping;
+        self.config = config;
+        if let (true, Some(tx)) = (ping_changed, &self.engine_tx) {
+            let _ = tx
+                .send(EngineCommand::SetPingConfig(self.config.ping.clone()))
+                .await;
+        }
+        self.reconcile(before).await
+    }
+
+    /// Apply a host-editor change to the config, then bring the engine and every
+    /// per-host view in line with it.
+    async fn edit_hosts(&mut self, edit: HostEdit) -> Result<()> {
+        // The editor works on the file's own hosts, so nothing from `--host` or
+        // `auto_hosts` can end up saved. The file itself changes only on save.
+        let mut edited = self.file_config.clone();
+        // An added host joins the profile in use, and stays in it across a reload.
+        edited.profile = self.config.profile.clone();
+        edited.apply_edit(edit);
+        edited.profile = None;
+        if let Err(e) = edited.check_hosts() {
+            if let Some(tui) = self.tui.as_mut() {
+                tui.set_editor_note(format!("Not applied \u{2014} {e:#}"));
+            }
+            return Ok(());
+        }
+        // Say what validation made of the edit (a clamped interval, an unknown
+        // dependency) where it was made, not only in the banner.
+        let known: Vec<String> = self
+            .file_config
+            .clone()
+            .validate()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let fresh: Vec<String> = edited
+            .clone()
+            .validate()
+            .iter()
+            .map(ToString::to_string)
+            .filter(|w| !known.contains(w))
+            .collect();
+        self.file_config = edited;
+        self.rederive().await?;
+        if let Some(tui) = self.tui.as_mut() {
+            if !fresh.is_empty() {
+                tui.set_editor_note(fresh.join("; "));
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop hosts that are gone, start new ones, and restart those whose settings
+    /// changed; a restarted host keeps its id, and with it its history.
+    async fn reconcile(&mut self, before: HashMap<String, RunningHost>) -> Result<()> {
+        let after = Self::running_hosts(&self.config);
+        if let Some(tx) = &self.engine_tx {
+            for id in before.keys().filter(|id| !after.contains_key(*id)) {
+                let _ = tx.send(EngineCommand::RemoveHost(id.clone())).await;
+            }
+            for (id, running) in &after {
+                if before.get(id) != Some(running) {
+                    let _ = tx.send(EngineCommand::AddHost(running.0.clone())).await;
+                }
+            }
+        }
+        self.sync_host_list()
+    }
+
+    fn running_hosts(config: &Config) -> HashMap<String, RunningHost> {
+        let ping = &config.ping;
+        config
+            .probed_hosts()
+            .into_iter()
+            .map(|h| {
+                let interval = h.interval.unwrap_or(ping.interval);
+                let running = (h.clone(), interval, ping.timeout, ping.packet_size);
+                (PingEngine::host_id(&h), running)
+            })
+            .collect()
+    }
+
+    /// Rebuild the host list from the config, forget hosts that are no longer
+    /// monitored, and record the change when recording.
+    fn sync_host_list(&mut self) -> Result<()> {
+        let hosts = session_hosts(&self.config.probed_hosts());
+        self.set_host_list(&hosts);
+        if let Some(rec) = self.recorder.as_mut() {
+            rec.set_hosts(hosts)?;
+        }
+        // A host that stopped probing every address goes back to a single row.
+        let every: HashSet<String> = self
+            .config
+            .probed_hosts()
+            .iter()
+            .filter(|h| h.all_addresses)
+            .map(PingEngine::host_id)
+            .collect();
+        self.addresses.retain(|id, _| every.contains(id));
+        if let Some(tui) = self.tui.as_mut() {
+            tui.set_hosts(self.file_config.hosts.clone());
+        }
+        Ok(())
+    }
+
+    /// Show these hosts, forgetting any others: the config's, or a replay's as they
+    /// were at that point of the recording.
+    fn set_host_list(&mut self, hosts: &[SessionHost]) {
+        let index = HostIndex::new(hosts);
+        self.host_info = index.info;
+        self.probe_kinds = index.probe_kinds;
+        self.host_groups = index.groups;
+        self.host_deps = index.deps;
+        self.host_stacks = index.stacks;
+        let live: HashSet<String> = self.host_info.iter().map(|(id, _)| id.clone()).collect();
+        self.stats.retain(|id, _| live.contains(id));
+        // A host that starts now (added, reloaded in, or another profile's) gets its past
+        // too. Failing to read it only costs the graph its head start.
+        if let Some(history) = &self.history {
+            for (id, _) in &self.host_info {
+                if !self.stats.contains_key(id) {
+                    if let Ok(s) = history.recent_stats(id, self.config.ping.history_size) {
+                        self.stats.insert(id.clone(), s);
+                    }
+                }
+            }
+        }
+        self.addresses.retain(|id, _| live.contains(id));
+        self.resolved.retain(|id, _| live.contains(id));
+        self.resolved_ip.retain(|id, _| live.contains(id));
+        self.previous_ip.retain(|id, _| live.contains(id));
+        self.resolve_err.retain(|id, _| live.contains(id));
+        self.http_phases.retain(|id, _| live.contains(id));
+        self.answer_change.retain(|id, _| live.contains(id));
+        self.paused.retain(|id| live.contains(id));
+        if let Some(tui) = self.tui.as_mut() {
+            tui.set_host_info(self.host_info.clone());
+            tui.set_host_groups(self.host_groups.clone());
+        }
+    }
+
+    fn save_hosts(&mut self) {
+        let note = match self.file_config.save_hosts(&self.config_path) {
+            Ok(saved) => format!("Saved {saved} hosts to {}", self.config_path.display()),
+            Err(e) => format!("Save failed: {e:#}"),
+        };
+        if let Some(tui) = self.tui.as_mut() {
+            tui.set_editor_note(note);
+        }
+    }
+
+    /// Start tracing the path to a host. Needs a resolved address; until the host
+    /// has one there is nothing to trace toward, so the request is ignored.
+    fn open_path(&mut self, host_id: String) {
+        let Some(&target) = self.resolved_ip.get(&host_id) else {
+            return;
+        };
+        self.close_path();
+        let name = self
+            .host_info
+            .iter()
+            .find(|(id, _)| *id == host_id)
+            .map(|(_, n)| n.clone())
+            .unwrap_or_default();
+        let (tx, rx) = mpsc::channel(8);
+        // One round per ping interval (at least a second), each waiting up to the ping timeout.
+        let interval = Duration::from_secs_f64(self.config.ping.interval.max(1.0));
+        let timeout = Duration::from_secs_f64(self.config.ping.timeout);
+        self.trace_task = Some(tokio::spawn(crate::trace::run(
+            target, interval, timeout, tx,
+        )));
+        self.trace_rx = Some(rx);
+        self.path = Some(PathView::new(name, target, self.config.ping.history_size));
+    }
+
+    fn close_path(&mut self) {
+        if let Some(task) = self.trace_task.take() {
+            task.abort();
+        }
+        self.trace_rx = None;
+        self.path = None;
+    }
+
+    /// Extra per-host detail for the header: HTTP phase timings, or the DNS error tally.
+    fn host_notes(&self) -> HashMap<String, String> {
+        self.probe_kinds
+            .iter()
+            .filter_map(|(id, kind)| {
+                let note = match kind {
+                    ProbeKind::Http => self.http_phases.get(id)?.summary(),
+                    ProbeKind::Dns => {
+                        let tally = self.stats.get(id)?.outcome_summary();
+                        match self.answer_change.get(id) {
+                            Some(change) => format!("{tally}; last {change}"),
+                            None => tally,
+                        }
+                    }
+                    _ => return None,
+                };
+                (!note.is_empty()).then(|| (id.clone(), note))
+            })
+            .collect()
+    }
+}
+
+/// The monitored hosts as a recording lists them: by id, with dependencies on hosts
+/// outside the list dropped (they're not being probed, so they can't explain anything).
+/// A dependency on a dual-stack host is on both of its halves.
+fn session_hosts(hosts: &[Host]) -> Vec<SessionHost> {
+    let ids_of = |name: &String| {
+        hosts
+            .iter()
+            .filter(|h| h.name == *name || h.stack.as_ref() == Some(name))
+            .map(PingEngine::host_id)
+            .collect::<Vec<_>>()
+    };
+    hosts
+        .iter()
+        .map(|h| SessionHost {
+            id: PingEngine::host_id(h),
+            name: h.name.clone(),
+            probe: h.probe,
+            group: h.group.clone(),
+            depends_on: h.depends_on.iter().flat_map(ids_of).collect(),
+            stack: h.stack.clone().zip(h.family),
+        })
+        .collect()
+}
+
+/// A recording being played back: where its clock is, and the channel steering it
+/// (None when headless).
+struct Replay {
+    started: DateTime<Utc>,
+    speed: f64,
+    at_ms: u64,
+    total_ms: u64,
+    paused: bool,
+    ended: bool,
+    control: Option<mpsc::Sender<ReplayControl>>,
+}
+
+impl Replay {
+    /// Banner line: which recording, how fast, and how far into it.
+    fn note(&self) -> String {
+        let state = if self.ended {
+            " \u{2014} finished"
+        } else if self.paused {
+            " \u{2014} paused"
+        } else {
+            ""
+        };
+        format!(
+            "\u{25b6}  Replaying session from {} at {}\u{d7}, {} of {}{state}",
+            self.started.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.speed,
+            playback_time(self.at_ms),
+            playback_time(self.total_ms),
+        )
+    }
+}
+
+/// An offset into a recording as H:MM:SS.
+fn playback_time(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Per-host lookups built from the monitored host list.
+struct HostIndex {
+    info: Vec<(String, String)>,
+    probe_kinds: HashMap<String, ProbeKind>,
+    groups: HashMap<String, String>,
+    deps: HashMap<String, Vec<String>>,
+    stacks: HashMap<String, (String, Family)>,
+}
+
+impl HostIndex {
+    fn new(hosts: &[SessionHost]) -> Self {
+        Self {
+            info: hosts
+                .iter()
+                .map(|h| (h.id.clone(), h.name.clone()))
+                .collect(),
+            probe_kinds: hosts.iter().map(|h| (h.id.clone(), h.probe)).collect(),
+            groups: hosts
+                .iter()
+                .filter_map(|h| Some((h.id.clone(), h.group.clone()?)))
+                .collect(),
+            deps: hosts
+                .iter()
+                .filter(|h| !h.depends_on.is_empty())
+                .map(|h| (h.id.clone(), h.depends_on.clone()))
+                .collect(),
+            stacks: hosts
+                .iter()
+                .filter_map(|h| Some((h.id.clone(), h.stack.clone()?)))
+                .collect(),
+        }
+    }
+}
+
+/// Banner text for config warnings: the first one, and how many more there are.
+fn warning_notice(path: &std::path::Path, warnings: &[ConfigWarning]) -> Option<String> {
+    let first = warnings.first()?;
+    let more = match warnings.len() - 1 {
+        0 => String::new(),
+        n => format!(" (+{n} more)"),
+    };
+    Some(format!(
+        "\u{26a0}  {}: {first}{more} \u{2014} see `pingpong config check`",
+        path.display()
+    ))
+}
+
+/// Modification time of a file, or None if it can't be read (e.g. not there yet).
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Receive from an optional channel; pends forever when there is none, so a
+/// `select!` arm for it simply never fires.
+async fn recv_opt<T>(rx: &mut Option<mpsc::Receiver<T>>) -> Option<T> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
     }
 }
@@ -3,17 +3,35 @@
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use crate::discover::Discovered;
+use crate::dns::RecordType;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Network hosts to find and monitor automatically: "gateway", "dns"
+    #[serde(default)]
+    pub auto_hosts: Vec<AutoHost>,
     pub ping: PingConfig,
     pub hosts: Vec<Host>,
     pub ui: UiConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub alerts: AlertConfig,
+    /// Named host selections and settings, e.g. `[profiles.office]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// The profile in use, if any; chosen at run time, never saved
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PingConfig {
     /// Ping interval in seconds
     pub interval: f64,
@@ -26,22 +44,549 @@ pub struct PingConfig {
     /// URL used to detect captive portals (plain HTTP; default Apple's endpoint).
     #[serde(default = "default_portal_url")]
     pub portal_check_url: String,
+    /// Start every host's stats afresh when the network changes (e.g. another Wi-Fi).
+    #[serde(default)]
+    pub reset_on_network_change: bool,
+    /// Count an up ICMP host as degraded while its estimated call quality (MOS, 1-4.5)
+    /// is below this, e.g. 3.6 where video calls matter.
+    #[serde(default)]
+    pub min_mos: Option<f64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How a host is measured: ICMP echo (default), a TCP connect handshake, an HTTP(S) GET,
+/// or a DNS query sent straight to the host as a resolver.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeKind {
+    #[default]
+    Icmp,
+    Tcp,
+    Http,
+    Dns,
+}
+
+/// Which IP version to probe a host over. Without one, the first address the
+/// resolver returns is used, whatever its family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Family {
+    V4,
+    V6,
+    /// Probe the IPv4 and IPv6 addresses side by side, as two rows.
+    Both,
+}
+
+impl Family {
+    /// "IPv4" or "IPv6"; "IPv4/IPv6" for both.
+    pub fn label(self) -> &'static str {
+        match self {
+            Family::V4 => "IPv4",
+            Family::V6 => "IPv6",
+            Family::Both => "IPv4/IPv6",
+        }
+    }
+
+    /// Whether an address is of this family.
+    pub fn fits(self, ip: &std::net::IpAddr) -> bool {
+        match self {
+            Family::V4 => ip.is_ipv4(),
+            Family::V6 => ip.is_ipv6(),
+            Family::Both => true,
+        }
+    }
+}
+
+/// A kind of host found on the current network rather than listed in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoHost {
+    /// The default route's gateway
+    Gateway,
+    /// The system's DNS servers
+    Dns,
+}
+
+/// Group and tag discovered hosts are shown and selected by.
+const AUTO_GROUP: &str = "Auto";
+const AUTO_TAG: &str = "auto";
+const GATEWAY_NAME: &str = "Default gateway";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Host {
     /// Display name for the host
     pub name: String,
-    /// Hostname or IP address
+    /// Hostname or IP address (the full http:// or https:// URL for HTTP probes,
+    /// the resolver to query for DNS probes)
     pub address: String,
     /// Whether this host is enabled
     #[serde(default = "default_true")]
     pub enabled: bool,
     /// Custom ping interval for this host (overrides global)
     pub interval: Option<f64>,
+    /// Probe type: "icmp" (default), "tcp", "http" or "dns"
+    #[serde(default)]
+    pub probe: ProbeKind,
+    /// Destination port for TCP and DNS probes
+    pub port: Option<u16>,
+    /// Regex the response body must match for an HTTP probe to succeed
+    pub expect: Option<String>,
+    /// Name to look up for DNS probes
+    pub query: Option<String>,
+    /// Record type to ask for in DNS probes (default A)
+    #[serde(default)]
+    pub record_type: RecordType,
+    /// IP version to probe over: "v4", "v6" or "both" (default: whichever resolves first)
+    pub family: Option<Family>,
+    /// Probe every address the name resolves to, each with its own stats under the
+    /// host's row, instead of just the first
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub all_addresses: bool,
+    /// Section of the host list this host is shown in, e.g. "LAN" or "VPN"
+    pub group: Option<String>,
+    /// Labels that profiles can select this host by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Names of hosts this one is reached through (e.g. the gateway); while one of
+    /// them is down this host is shown as unreachable behind it rather than as failing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Found on the network by `auto_hosts`; never saved to the file
+    #[serde(skip)]
+    pub discovered: bool,
+    /// Name of the dual-stack host this is the IPv4 or IPv6 half of (see `Host::split`)
+    #[serde(skip)]
+    pub stack: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Host {
+    /// The hosts to probe for this one: itself, or with `family = "both"`, an IPv4 and an
+    /// IPv6 half named after it, e.g. "Google DNS (IPv6)".
+    pub fn split(&self) -> Vec<Host> {
+        if self.family != Some(Family::Both) {
+            return vec![self.clone()];
+        }
+        [Family::V4, Family::V6]
+            .into_iter()
+            .map(|family| Host {
+                name: format!("{} ({})", self.name, family.label()),
+                family: Some(family),
+                stack: Some(self.name.clone()),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// The address this host names directly, if it's an IP literal rather than a name.
+    fn literal_ip(&self) -> Option<std::net::IpAddr> {
+        match self.probe {
+            ProbeKind::Http => crate::http::parse_url(&self.address)?.host.parse().ok(),
+            _ => self.address.parse().ok(),
+        }
+    }
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            address: String::new(),
+            enabled: true,
+            interval: None,
+            probe: ProbeKind::Icmp,
+            port: None,
+            expect: None,
+            query: None,
+            record_type: RecordType::A,
+            family: None,
+            all_addresses: false,
+            group: None,
+            tags: Vec::new(),
+            depends_on: Vec::new(),
+            discovered: false,
+            stack: None,
+        }
+    }
+}
+
+/// A monitoring profile: which hosts to probe, and settings that differ from the
+/// file's `[ping]` and `[ui]` sections when it's in use.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Host names or tags to monitor; empty means every enabled host
+    pub hosts: Vec<String>,
+    pub ping: PingOverrides,
+    pub ui: UiOverrides,
+}
+
+/// `[ping]` fields a profile can override.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PingOverrides {
+    pub interval: Option<f64>,
+    pub timeout: Option<f64>,
+    pub history_size: Option<usize>,
+    pub packet_size: Option<u16>,
+    pub portal_check_url: Option<String>,
+    pub reset_on_network_change: Option<bool>,
+    pub min_mos: Option<f64>,
+}
+
+/// `[ui]` fields a profile can override.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiOverrides {
+    pub refresh_rate: Option<u64>,
+    pub theme: Option<String>,
+    pub show_details: Option<bool>,
+    pub graph_height: Option<u16>,
+}
+
+impl Profile {
+    /// Whether this profile monitors `host` (enabled or not; that's checked separately).
+    pub fn selects(&self, host: &Host) -> bool {
+        self.hosts.is_empty()
+            || self
+                .hosts
+                .iter()
+                .any(|s| *s == host.name || host.tags.contains(s))
+    }
+}
+
+/// Settings layered over the file, kept so a reloaded config gets them too: the
+/// `--profile` first, then `PINGPONG_*` environment variables, then the other flags.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// Monitoring profile from `--profile`
+    pub profile: Option<String>,
+    /// The network as last looked at, for `auto_hosts`
+    pub discovered: Discovered,
+    /// `PINGPONG_<SECTION>_<FIELD>` variables for `[ping]` and `[ui]`, e.g. `PINGPONG_PING_INTERVAL`
+    pub env: Vec<(String, String)>,
+    /// Extra hosts from `--host`
+    pub hosts: Vec<String>,
+    /// Global interval from `--interval`
+    pub interval: Option<f64>,
+}
+
+/// Config sections that `PINGPONG_<SECTION>_<FIELD>` variables can set.
+const ENV_SECTIONS: [&str; 2] = ["ping", "ui"];
+
+impl Overrides {
+    /// Layer these over `config`, returning the variables that couldn't be used. The
+    /// `auto_hosts` found on the network are added after the environment. Hosts
+    /// already in the file (say, saved there from the host editor) aren't added twice,
+    /// and are monitored whatever the profile. Naming a profile the file doesn't define
+    /// is an error.
+    pub fn apply(&self, config: &mut Config) -> Result<Vec<ConfigWarning>> {
+        if let Some(name) = &self.profile {
+            config.use_profile(name)?;
+        }
+        let warnings = self.apply_env(config);
+        config.add_discovered(&self.discovered);
+        for address in &self.hosts {
+            match config.hosts.iter().find(|h| &h.address == address) {
+                Some(host) => {
+                    let name = host.name.clone();
+                    config.select(name);
+                }
+                None => config.add_host(address.clone()),
+            }
+        }
+        if let Some(interval) = self.interval {
+            config.set_interval(interval);
+        }
+        Ok(warnings)
+    }
+
+    /// Set fields from the environment, reading each value as the type the field is
+    /// declared with. A variable that names no field, or doesn't fit it, is skipped.
+    fn apply_env(&self, config: &mut Config) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+        let Ok(mut value) = toml::Value::try_from(&*config) else {
+            return warnings;
+        };
+        for (var, raw) in &self.env {
+            let Some((section, key)) = ENV_SECTIONS.iter().find_map(|s| {
+                let prefix = format!("PINGPONG_{}_", s.to_uppercase());
+                var.strip_prefix(&prefix).map(|k| (*s, k.to_lowercase()))
+            }) else {
+                continue;
+            };
+            // An unset optional field isn't in the serialized config at all; read the
+            // value every way it could be meant and let the field pick.
+            let readings = match value.get(section).and_then(|t| t.get(key.as_str())) {
+                Some(like) => parse_like(like, raw).into_iter().collect(),
+                None => parse_any(raw),
+            };
+            let mut outcome = Err("not a valid value");
+            for reading in readings {
+                let mut candidate = value.clone();
+                if let Some(table) = candidate.get_mut(section).and_then(|t| t.as_table_mut()) {
+                    table.insert(key.clone(), reading);
+                }
+                // The value must also fit the field (e.g. a u16 packet size).
+                outcome = check_fields(&candidate).map(|()| candidate);
+                if outcome.is_ok() || outcome == Err(UNKNOWN_SETTING) {
+                    break;
+                }
+            }
+            match outcome {
+                Ok(candidate) => value = candidate,
+                Err(reason) => warnings.push(ConfigWarning::BadEnv {
+                    var: var.clone(),
+                    reason,
+                }),
+            }
+        }
+        // Only these sections can change; the rest has state that doesn't round-trip
+        // through TOML (the active profile, discovered hosts).
+        if let Ok(updated) = value.try_into::<Config>() {
+            config.ping = updated.ping;
+            config.ui = updated.ui;
+        }
+        warnings
+    }
+}
+
+const UNKNOWN_SETTING: &str = "not a pingpong setting";
+
+/// Whether `value` reads as a config with nothing left over: an error names a key the
+/// schema doesn't have, or a value that doesn't fit its field.
+fn check_fields(value: &toml::Value) -> std::result::Result<(), &'static str> {
+    let mut unknown = false;
+    let read: std::result::Result<Config, _> =
+        serde_ignored::deserialize(value.clone(), |_| unknown = true);
+    match read {
+        _ if unknown => Err(UNKNOWN_SETTING),
+        Ok(_) => Ok(()),
+        Err(_) => Err("not a valid value"),
+    }
+}
+
+/// Read an environment string as the same TOML type as `like`.
+fn parse_like(like: &toml::Value, raw: &str) -> Option<toml::Value> {
+    use toml::Value;
+    let raw = raw.trim();
+    match like {
+        Value::String(_) => Some(Value::String(raw.to_string())),
+        Value::Integer(_) => raw.parse().ok().map(Value::Integer),
+        Value::Float(_) => raw.parse().ok().map(Value::Float),
+        Value::Boolean(_) => match raw {
+            "true" | "1" | "yes" => Some(Value::Boolean(true)),
+            "false" | "0" | "no" => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Every way an environment string can be read, most specific first.
+fn parse_any(raw: &str) -> Vec<toml::Value> {
+    use toml::Value;
+    [
+        Value::Integer(0),
+        Value::Float(0.0),
+        Value::Boolean(false),
+        Value::String(String::new()),
+    ]
+    .iter()
+    .filter_map(|like| parse_like(like, raw))
+    .collect()
+}
+
+/// Config file locations in search order: `$PINGPONG_CONFIG`,
+/// `$XDG_CONFIG_HOME/pingpong/config.toml`, `~/.config/pingpong/config.toml`, then
+/// `/etc/pingpong/config.toml`. `env` looks up a variable; unset and empty are the same.
+pub fn search_paths(env: impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    let env = |name: &str| env(name).filter(|v| !v.is_empty());
+    let mut paths = Vec::new();
+    if let Some(path) = env("PINGPONG_CONFIG") {
+        paths.push(PathBuf::from(path));
+    }
+    if let Some(dir) = env("XDG_CONFIG_HOME") {
+        paths.push(Path::new(&dir).join("pingpong/config.toml"));
+    }
+    if let Some(home) = env("HOME") {
+        let path = Path::new(&home).join(".config/pingpong/config.toml");
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths.push(PathBuf::from("/etc/pingpong/config.toml"));
+    paths
+}
+
+/// The config file to use: the first search path that exists, or failing that the
+/// first one, which is where `config init` and the host editor will create it.
+pub fn locate(paths: &[PathBuf]) -> PathBuf {
+    paths
+        .iter()
+        .find(|p| p.exists())
+        .or(paths.first())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Comments `Config::save` puts above the first occurrence of each field, keyed by
+/// table header and field name.
+const FIELD_DOCS: &[(&str, &str, &str)] = &[
+    (
+        "",
+        "auto_hosts",
+        "Also monitor the current network's \"gateway\" and \"dns\" servers",
+    ),
+    ("[ping]", "interval", "Seconds between probes of each host"),
+    ("[ping]", "timeout", "Seconds to wait for each reply"),
+    (
+        "[ping]",
+        "history_size",
+        "Results kept per host for the graphs and stats",
+    ),
+    ("[ping]", "packet_size", "ICMP payload size in bytes"),
+    (
+        "[ping]",
+        "portal_check_url",
+        "Plain-HTTP URL used to detect captive portals",
+    ),
+    (
+        "[ping]",
+        "reset_on_network_change",
+        "Start stats afresh when the network changes",
+    ),
+    (
+        "[ping]",
+        "min_mos",
+        "Count pinged hosts as degraded below this call-quality score (1-4.5)",
+    ),
+    ("[[hosts]]", "name", "Display name"),
+    (
+        "[[hosts]]",
+        "address",
+        "Hostname or IP (the full URL for http probes, the resolver for dns)",
+    ),
+    (
+        "[[hosts]]",
+        "enabled",
+        "Set to false to keep a host in the file without probing it",
+    ),
+    (
+        "[[hosts]]",
+        "probe",
+        "How to measure: \"icmp\", \"tcp\", \"http\" or \"dns\"",
+    ),
+    ("[[hosts]]", "record_type", "Record type for dns probes"),
+    (
+        "[[hosts]]",
+        "family",
+        "IP version to probe over: \"v4\", \"v6\" or \"both\" (two rows)",
+    ),
+    (
+        "[[hosts]]",
+        "all_addresses",
+        "Probe every resolved address, each as a sub-row of the host",
+    ),
+    (
+        "[[hosts]]",
+        "group",
+        "Host list section this host is shown in",
+    ),
+    (
+        "[[hosts]]",
+        "tags",
+        "Labels profiles can select this host by",
+    ),
+    (
+        "[[hosts]]",
+        "depends_on",
+        "Hosts this one is reached through, e.g. [\"Router\"]",
+    ),
+    (
+        "[ui]",
+        "refresh_rate",
+        "Milliseconds between screen updates",
+    ),
+    ("[ui]", "theme", "\"dark\", \"light\" or \"auto\""),
+    ("[ui]", "show_details", "Show per-host graphs"),
+    ("[ui]", "graph_height", "Graph height in terminal rows"),
+    (
+        "[storage]",
+        "enabled",
+        "Record results and state changes to a SQLite database",
+    ),
+    ("[storage]", "path", "Database file"),
+    ("[storage]", "retention_days", "Days of history to keep"),
+    (
+        "[storage]",
+        "raw_hours",
+        "Hours of full-resolution results before rolling them up",
+    ),
+    (
+        "[storage]",
+        "rollup_secs",
+        "Width of a rolled-up bucket in seconds",
+    ),
+];
+
+/// Optional settings that are left out of serialized TOML while unset, keyed by table
+/// header and field name, with an example value to show them commented out.
+const UNSET_EXAMPLES: &[(&str, &str, &str)] = &[("[ping]", "min_mos", "3.6")];
+
+/// Add `FIELD_DOCS` comments to serialized TOML, once per field, and show the unset
+/// `UNSET_EXAMPLES` commented out after the last field of their table.
+fn annotate(toml: &str) -> String {
+    let mut out =
+        String::from("# pingpong configuration. Run `pingpong config check` after editing.\n\n");
+    let unset = |table: &str, keys: &[&str], out: &mut String| {
+        for &(t, key, example) in UNSET_EXAMPLES {
+            if t != table || keys.contains(&key) {
+                continue;
+            }
+            if let Some((_, _, doc)) = FIELD_DOCS.iter().find(|(d, k, _)| *d == t && *k == key) {
+                out.push_str(&format!("# {doc}\n"));
+            }
+            out.push_str(&format!("# {key} = {example}\n"));
+        }
+    };
+    let mut table = "";
+    let mut keys = Vec::new();
+    let mut seen = Vec::new();
+    for line in toml.lines() {
+        if (line.trim().is_empty() || line.starts_with('[')) && !keys.is_empty() {
+            unset(table, &keys, &mut out);
+            keys.clear();
+        }
+        if line.starts_with('[') {
+            table = line.trim();
+        } else if let Some((key, _)) = line.split_once(" = ") {
+            keys.push(key.trim());
+            let doc = FIELD_DOCS
+                .iter()
+                .find(|(t, k, _)| *t == table && *k == key.trim());
+            if let Some(&(t, k, doc)) = doc.filter(|d| !seen.contains(*d)) {
+                out.push_str(&format!("# {doc}\n"));
+                seen.push((t, k, doc));
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    unset(table, &keys, &mut out);
+    out
+}
+
+/// A change to the host list made from the TUI's host editor. Indexes refer to `Config::hosts`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostEdit {
+    Add(String),
+    Rename(usize, String),
+    /// Per-host interval in seconds; None falls back to the global interval.
+    SetInterval(usize, Option<f64>),
+    ToggleEnabled(usize),
+    Delete(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UiConfig {
     /// Refresh rate for the UI in milliseconds
     pub refresh_rate: u64,
@@ -54,6 +599,163 @@ pub struct UiConfig {
     pub graph_height: u16,
 }
 
+/// On-disk history. Results are kept at full resolution for `raw_hours`, then rolled up
+/// into `rollup_secs` buckets (count, loss, min/avg/max RTT) until `retention_days`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Write every result and state transition to a SQLite database
+    pub enabled: bool,
+    /// Database file path
+    pub path: String,
+    /// Days of history to keep; older rows are deleted
+    pub retention_days: u32,
+    /// Hours of full-resolution results to keep before rolling them up
+    pub raw_hours: u32,
+    /// Width of a rolled-up bucket in seconds
+    pub rollup_secs: u32,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "pingpong.db".to_string(),
+            retention_days: 30,
+            raw_hours: 24,
+            rollup_secs: 60,
+        }
+    }
+}
+
+/// Alert rules and where their notifications go.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+    pub rules: Vec<AlertRule>,
+    pub sinks: Vec<SinkConfig>,
+}
+
+/// Fire when `when` has held for `for_secs`; resolve once it has been clear for `clear_secs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    /// Label used in notifications (defaults to the condition)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Condition: "down", "degraded", "captive_portal" or "offline"
+    pub when: AlertCondition,
+    /// Host names the rule applies to; empty means every host (host conditions only)
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Seconds the condition must hold before the alert fires (debounce)
+    #[serde(default)]
+    pub for_secs: f64,
+    /// Seconds the condition must stay clear before the alert resolves (hysteresis)
+    #[serde(default)]
+    pub clear_secs: f64,
+}
+
+/// A host state (`down`, `degraded`) checked per host, or a network-wide connectivity state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertCondition {
+    Down,
+    Degraded,
+    CaptivePortal,
+    Offline,
+}
+
+/// Where alert notifications are sent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    /// Ring the terminal bell
+    Bell,
+    /// Run a shell command with PINGPONG_ALERT_* variables set
+    Command { command: String },
+    /// POST the alert as JSON
+    Webhook { url: String },
+    /// Append a line per alert to a file
+    Log { path: String },
+}
+
+/// Something in a config file worth telling the user about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigWarning {
+    /// A key that isn't part of the schema, probably a typo; it was ignored.
+    UnknownKey(String),
+    /// A value `validate` had to replace.
+    Clamped {
+        field: String,
+        was: String,
+        now: String,
+    },
+    /// A `PINGPONG_*` environment variable that couldn't be applied.
+    BadEnv { var: String, reason: &'static str },
+    /// A profile's host selector that names no host and no tag.
+    UnmatchedSelector { profile: String, selector: String },
+    /// A reference to a host name that isn't in the file.
+    UnknownHost { field: String, name: String },
+    /// A `family` an IP-literal address can't be probed over.
+    WrongFamily {
+        field: String,
+        address: String,
+        family: Family,
+    },
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownKey(key) => write!(f, "{key}: unknown key, ignored"),
+            Self::Clamped { field, was, now } => {
+                write!(f, "{field}: {was} is out of range, using {now}")
+            }
+            Self::BadEnv { var, reason } => write!(f, "{var}: {reason}, ignored"),
+            Self::UnmatchedSelector { profile, selector } => write!(
+                f,
+                "profiles.{profile}.hosts: \"{selector}\" matches no host name or tag"
+            ),
+            Self::UnknownHost { field, name } => {
+                write!(f, "{field}: no host named \"{name}\", ignored")
+            }
+            Self::WrongFamily {
+                field,
+                address,
+                family,
+            } => write!(
+                f,
+                "{field}: {address} has no {} address, ignored",
+                family.label()
+            ),
+        }
+    }
+}
+
+/// Render a serde_ignored path the way the warnings name fields: `hosts[2].intervall`.
+fn field_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{index}]", field_path(parent)),
+        Path::Map { parent, key } => match field_path(parent) {
+            p if p.is_empty() => key.clone(),
+            p => format!("{p}.{key}"),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+/// 1-based line and column of a byte offset.
+fn line_col(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, col)
+}
+
 fn default_true() -> bool {
     true
 }
@@ -65,31 +767,31 @@ fn default_portal_url() -> String {
 impl Default for Config {
     fn default() -> Self {
         Self {
+            auto_hosts: Vec::new(),
             ping: PingConfig {
                 interval: 1.0,
                 timeout: 3.0,
                 history_size: 300, // 5 minutes at 1s intervals
                 packet_size: 32,
                 portal_check_url: default_portal_url(),
+                reset_on_network_change: false,
+                min_mos: None,
             },
             hosts: vec![
                 Host {
                     name: "Google DNS".to_string(),
                     address: "8.8.8.8".to_string(),
-                    enabled: true,
-                    interval: None,
+                    ..Host::default()
                 },
                 Host {
                     name: "Cloudflare DNS".to_string(),
                     address: "1.1.1.1".to_string(),
-                    enabled: true,
-                    interval: None,
+                    ..Host::default()
                 },
                 Host {
                     name: "Google".to_string(),
                     address: "google.com".to_string(),
-                    enabled: true,
-                    interval: None,
+                    ..Host::default()
                 },
             ],
             ui: UiConfig {
@@ -98,77 +800,416 @@ impl Default for Config {
                 show_details: true,
                 graph_height: 10,
             },
+            storage: StorageConfig::default(),
+            alerts: AlertConfig::default(),
+            profiles: BTreeMap::new(),
+            profile: None,
         }
     }
 }
 
 impl Config {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read config file: {}", path.as_ref().display()))?;
+    /// Load a config file, also returning the keys it has that pingpong doesn't know.
+    /// A TOML error comes back as `path:line:column: message`.
+    pub fn load_checked<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<ConfigWarning>)> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Self::parse(&content).map_err(|e| anyhow::anyhow!("{}:{e}", path.display()))
+    }
 
-        toml::from_str(&content)
-            .with_context(|| format!("Failed to parse config file: {}", path.as_ref().display()))
+    /// Load the file if there is one; the defaults are only for when there isn't.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<ConfigWarning>)> {
+        if path.as_ref().exists() {
+            Self::load_checked(path)
+        } else {
+            Ok((Self::default(), Vec::new()))
+        }
     }
 
-    #[allow(dead_code)]
+    /// Parse TOML text. Unknown keys are skipped and reported; an error is rendered as
+    /// `line:column: message`.
+    pub fn parse(content: &str) -> std::result::Result<(Self, Vec<ConfigWarning>), String> {
+        let mut unknown = Vec::new();
+        let de = toml::Deserializer::new(content);
+        let config = serde_ignored::deserialize(de, |path| {
+            unknown.push(ConfigWarning::UnknownKey(field_path(&path)));
+        })
+        .map_err(|e| {
+            let (line, col) = e
+                .span()
+                .map(|span| line_col(content, span.start))
+                .unwrap_or((1, 1));
+            let message = e.message().trim_end().replace('\n', ", ");
+            format!("{line}:{col}: {message}")
+        })?;
+        Ok((config, unknown))
+    }
+
+    /// Write the config as commented TOML, creating its directory if needed.
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let content = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        if let Some(dir) = path.as_ref().parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create config directory: {}", dir.display()))?;
+        }
 
-        fs::write(&path, content)
+        fs::write(&path, annotate(&content))
             .with_context(|| format!("Failed to write config file: {}", path.as_ref().display()))
     }
 
+    /// Write this host list into the config file, leaving the rest of the file as it
+    /// is: other settings (so CLI overrides like `--interval` don't get baked in) and
+    /// comments alike. A missing file starts from the defaults; one that doesn't parse
+    /// is left alone. Returns how many hosts were written.
+    pub fn save_hosts<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        #[derive(Serialize)]
+        struct HostList<'a> {
+            hosts: Vec<&'a Host>,
+        }
+        let path = path.as_ref();
+        let hosts: Vec<&Host> = self.hosts.iter().filter(|h| !h.discovered).collect();
+        let saved = hosts.len();
+        if !path.exists() {
+            let config = Config {
+                hosts: hosts.into_iter().cloned().collect(),
+                ..Self::default()
+            };
+            config.save(path)?;
+            return Ok(saved);
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let mut doc: toml_edit::DocumentMut = content
+            .parse()
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        let list: toml_edit::DocumentMut = toml::to_string(&HostList { hosts })
+            .context("Failed to serialize hosts")?
+            .parse()
+            .context("Failed to serialize hosts")?;
+        let mut item = list["hosts"].clone();
+        // Fresh tables would sort ahead of the file's own; keep them where the old
+        // host list was, or at the end.
+        if let Some(tables) = item.as_array_of_tables_mut() {
+            let at = doc
+                .get("hosts")
+                .and_then(|old| old.as_array_of_tables())
+                .and_then(|old| old.iter().find_map(|t| t.position()))
+                .unwrap_or(usize::MAX / 2);
+            for (i, table) in tables.iter_mut().enumerate() {
+                table.set_position(at + i);
+            }
+        }
+        doc.insert("hosts", item);
+        fs::write(path, doc.to_string())
+            .with_context(|| format!("Failed to write config file: {}", path.display()))?;
+        Ok(saved)
+    }
+
+    /// Apply an edit from the host editor. Out-of-range indexes are ignored; run
+    /// `validate` afterwards, as for a loaded file.
+    pub fn apply_edit(&mut self, edit: HostEdit) {
+        match edit {
+            HostEdit::Add(address) => self.add_host(address),
+            HostEdit::Rename(i, name) => {
+                if let Some(h) = self.hosts.get_mut(i) {
+                    h.name = name;
+                }
+            }
+            HostEdit::SetInterval(i, interval) => {
+                if let Some(h) = self.hosts.get_mut(i) {
+                    h.interval = interval;
+                }
+            }
+            HostEdit::ToggleEnabled(i) => {
+                if let Some(h) = self.hosts.get_mut(i) {
+                    h.enabled = !h.enabled;
+                }
+            }
+            HostEdit::Delete(i) => {
+                if i < self.hosts.len() {
+                    self.hosts.remove(i);
+                }
+            }
+        }
+    }
+
+    /// Add a host by address. With a profile in use it joins the profile, so it's
+    /// monitored straight away (until the profile is next loaded from the file).
     pub fn add_host(&mut self, address: String) {
         use std::net::IpAddr;
         let name = match address.parse::<IpAddr>() {
             Ok(IpAddr::V4(_)) => format!("IP {address}"),
             _ => address.clone(), // hostname or IPv6 -> use as-is
         };
+        self.select(name.clone());
         self.hosts.push(Host {
             name,
             address,
-            enabled: true,
-            interval: None,
+            ..Host::default()
         });
     }
 
-    /// Clamp nonsensical values so a hand-edited config can't wedge the app.
-    pub fn validate(&mut self) {
+    /// Add the `auto_hosts` kinds found on the network, in the "Auto" group and tagged
+    /// "auto" for profiles. Addresses the file already lists aren't added twice, and
+    /// DNS servers depend on the gateway when it's monitored too.
+    pub fn add_discovered(&mut self, found: &Discovered) {
+        let add = |config: &mut Self, name: String, ip: &std::net::IpAddr, deps| {
+            let address = ip.to_string();
+            if config.hosts.iter().any(|h| h.address == address) {
+                return false;
+            }
+            config.hosts.push(Host {
+                name,
+                address,
+                group: Some(AUTO_GROUP.to_string()),
+                tags: vec![AUTO_TAG.to_string()],
+                depends_on: deps,
+                discovered: true,
+                ..Host::default()
+            });
+            true
+        };
+        let mut gateway = false;
+        if self.auto_hosts.contains(&AutoHost::Gateway) {
+            if let Some(ip) = &found.gateway {
+                gateway = add(self, GATEWAY_NAME.to_string(), ip, Vec::new());
+            }
+        }
+        if self.auto_hosts.contains(&AutoHost::Dns) {
+            for ip in &found.dns {
+                let deps = if gateway {
+                    vec![GATEWAY_NAME.to_string()]
+                } else {
+                    Vec::new()
+                };
+                add(self, format!("DNS {ip}"), ip, deps);
+            }
+        }
+    }
+
+    /// Switch to the named profile: its `[ping]` and `[ui]` values replace the file's,
+    /// and only the hosts it selects are monitored.
+    pub fn use_profile(&mut self, name: &str) -> Result<()> {
+        let Some(profile) = self.profiles.get(name) else {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "no profile named '{name}' (defined: {})",
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            );
+        };
+        let (ping, ui) = (profile.ping.clone(), profile.ui.clone());
+        let p = &mut self.ping;
+        p.interval = ping.interval.unwrap_or(p.interval);
+        p.timeout = ping.timeout.unwrap_or(p.timeout);
+        p.history_size = ping.history_size.unwrap_or(p.history_size);
+        p.packet_size = ping.packet_size.unwrap_or(p.packet_size);
+        p.portal_check_url = ping.portal_check_url.unwrap_or(p.portal_check_url.clone());
+        p.reset_on_network_change = ping
+            .reset_on_network_change
+            .unwrap_or(p.reset_on_network_change);
+        p.min_mos = ping.min_mos.or(p.min_mos);
+        let u = &mut self.ui;
+        u.refresh_rate = ui.refresh_rate.unwrap_or(u.refresh_rate);
+        u.theme = ui.theme.unwrap_or(u.theme.clone());
+        u.show_details = ui.show_details.unwrap_or(u.show_details);
+        u.graph_height = ui.graph_height.unwrap_or(u.graph_height);
+        self.profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Make sure the active profile, if it picks hosts at all, picks the one named `name`.
+    fn select(&mut self, name: String) {
+        let active = self.profile.as_ref().and_then(|p| self.profiles.get_mut(p));
+        if let Some(profile) = active.filter(|p| !p.hosts.is_empty()) {
+            if !profile.hosts.contains(&name) {
+                profile.hosts.push(name);
+            }
+        }
+    }
+
+    /// Clamp nonsensical values so a hand-edited config can't wedge the app. Returns
+    /// what was replaced, so the user can fix the file rather than wonder. Values left
+    /// out of the file get their defaults quietly; only ones given and unusable are reported.
+    pub fn validate(&mut self) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+        let mut clamped = |field: &str, was: &dyn fmt::Display, now: &dyn fmt::Display| {
+            warnings.push(ConfigWarning::Clamped {
+                field: field.to_string(),
+                was: was.to_string(),
+                now: now.to_string(),
+            });
+        };
         if !self.ping.interval.is_finite() || self.ping.interval < 0.1 {
+            clamped("ping.interval", &self.ping.interval, &1.0);
             self.ping.interval = 1.0;
         }
         if !self.ping.timeout.is_finite() || self.ping.timeout < 0.1 {
+            clamped("ping.timeout", &self.ping.timeout, &3.0);
             self.ping.timeout = 3.0;
         }
         if self.ping.history_size == 0 {
+            clamped("ping.history_size", &0, &300);
             self.ping.history_size = 300;
         }
         if self.ping.packet_size == 0 {
+            clamped("ping.packet_size", &0, &32);
             self.ping.packet_size = 32;
         }
+        if let Some(mos) = self.ping.min_mos.filter(|m| !(1.0..=4.5).contains(m)) {
+            let now = if mos > 4.5 { 4.5 } else { 1.0 };
+            clamped("ping.min_mos", &mos, &now);
+            self.ping.min_mos = Some(now);
+        }
         if self.ui.refresh_rate == 0 {
+            clamped("ui.refresh_rate", &0, &100);
             self.ui.refresh_rate = 100;
         }
         // Per-host interval overrides feed Duration::from_secs_f64 in the ping loop;
         // drop any that are non-finite or too small so they fall back to the global interval.
-        for host in &mut self.hosts {
-            if host.interval.is_some_and(|i| !i.is_finite() || i < 0.1) {
+        for (i, host) in self.hosts.iter_mut().enumerate() {
+            if let Some(interval) = host.interval.filter(|i| !i.is_finite() || *i < 0.1) {
+                clamped(
+                    &format!("hosts[{i}].interval"),
+                    &interval,
+                    &"the global interval",
+                );
                 host.interval = None;
             }
+            // A TCP probe without a usable port has nothing to connect to; assume HTTP.
+            if host.probe == ProbeKind::Tcp && host.port.unwrap_or(0) == 0 {
+                if host.port.is_some() {
+                    clamped(&format!("hosts[{i}].port"), &0, &80);
+                }
+                host.port = Some(80);
+            }
+            // A DNS probe needs a port and a name to ask for.
+            if host.probe == ProbeKind::Dns {
+                if host.port.unwrap_or(0) == 0 {
+                    if host.port.is_some() {
+                        clamped(&format!("hosts[{i}].port"), &0, &53);
+                    }
+                    host.port = Some(53);
+                }
+                if host.query.as_deref().is_none_or(|q| q.trim().is_empty()) {
+                    if host.query.is_some() {
+                        clamped(&format!("hosts[{i}].query"), &"\"\"", &"example.com");
+                    }
+                    host.query = Some("example.com".to_string());
+                }
+            }
+        }
+        // Debounce/hysteresis windows feed Duration::from_secs_f64 in the alert engine.
+        for (i, rule) in self.alerts.rules.iter_mut().enumerate() {
+            if !rule.for_secs.is_finite() || rule.for_secs < 0.0 {
+                clamped(&format!("alerts.rules[{i}].for_secs"), &rule.for_secs, &0);
+                rule.for_secs = 0.0;
+            }
+            if !rule.clear_secs.is_finite() || rule.clear_secs < 0.0 {
+                clamped(
+                    &format!("alerts.rules[{i}].clear_secs"),
+                    &rule.clear_secs,
+                    &0,
+                );
+                rule.clear_secs = 0.0;
+            }
+        }
+        if self.storage.retention_days == 0 {
+            clamped("storage.retention_days", &0, &30);
+            self.storage.retention_days = 30;
+        }
+        if self.storage.rollup_secs == 0 {
+            clamped("storage.rollup_secs", &0, &60);
+            self.storage.rollup_secs = 60;
         }
         // Keep the graph height within a sane range of terminal rows.
         if self.ui.graph_height == 0 || self.ui.graph_height > 50 {
+            clamped("ui.graph_height", &self.ui.graph_height, &10);
             self.ui.graph_height = 10;
         }
+        // An IP literal is the one address there is; it can't be probed over the other family.
+        for (i, host) in self.hosts.iter_mut().enumerate() {
+            let Some(ip) = host.family.and(host.literal_ip()) else {
+                continue;
+            };
+            let missing = if ip.is_ipv4() { Family::V6 } else { Family::V4 };
+            if !host
+                .family
+                .is_some_and(|f| f.fits(&ip) && f != Family::Both)
+            {
+                warnings.push(ConfigWarning::WrongFamily {
+                    field: format!("hosts[{i}].family"),
+                    address: ip.to_string(),
+                    family: missing,
+                });
+                host.family = None;
+            }
+        }
+        // Dependencies are by name; one that names no host can't explain anything.
+        for (i, host) in self.hosts.iter().enumerate() {
+            for dep in &host.depends_on {
+                if !self.hosts.iter().any(|h| h.name == *dep) {
+                    warnings.push(ConfigWarning::UnknownHost {
+                        field: format!("hosts[{i}].depends_on"),
+                        name: dep.clone(),
+                    });
+                }
+            }
+        }
+        // A selector that matches nothing is most likely a misspelt host name.
+        for (name, profile) in &self.profiles {
+            for selector in &profile.hosts {
+                let matched = self
+                    .hosts
+                    .iter()
+                    .any(|h| h.name == *selector || h.tags.contains(selector));
+                if !matched {
+                    warnings.push(ConfigWarning::UnmatchedSelector {
+                        profile: name.clone(),
+                        selector: selector.clone(),
+                    });
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Refuse hosts that can only fail once probing starts: an HTTP address that isn't
+    /// an http(s) URL, or an `expect` pattern that isn't a valid regex. Nothing
+    /// sensible can stand in for either, so unlike `validate` this is an error.
+    pub fn check_hosts(&self) -> Result<()> {
+        for (i, host) in self.hosts.iter().enumerate() {
+            if host.probe == ProbeKind::Http && crate::http::parse_url(&host.address).is_none() {
+                anyhow::bail!("hosts[{i}].address: not an http(s) URL: {}", host.address);
+            }
+            if let Some(expect) = &host.expect {
+                if let Err(e) = regex::Regex::new(expect) {
+                    anyhow::bail!("hosts[{i}].expect: invalid pattern: {e}");
+                }
+            }
+        }
+        Ok(())
     }
 
     pub fn set_interval(&mut self, interval: f64) {
         self.ping.interval = interval;
     }
 
+    /// Hosts to probe: the enabled ones, narrowed to the active profile's selection.
     pub fn enabled_hosts(&self) -> impl Iterator<Item = &Host> {
-        self.hosts.iter().filter(|h| h.enabled)
+        let profile = self.profile.as_ref().and_then(|p| self.profiles.get(p));
+        self.hosts
+            .iter()
+            .filter(move |h| h.enabled && profile.is_none_or(|p| p.selects(h)))
+    }
+
+    /// What gets probed: the enabled hosts, with each dual-stack one split into its halves.
+    pub fn probed_hosts(&self) -> Vec<Host> {
+        self.enabled_hosts().flat_map(Host::split).collect()
     }
 }
 
@@ -186,20 +1227,359 @@ mod tests {
     #[test]
     fn add_host_names_ipv4() {
         let mut c = Config {
-            ping: Config::default().ping,
             hosts: vec![],
-            ui: Config::default().ui,
+            ..Config::default()
         };
         c.add_host("8.8.8.8".to_string());
         assert_eq!(c.hosts[0].name, "IP 8.8.8.8");
     }
 
+    #[test]
+    fn parse_errors_carry_line_and_column() {
+        let toml = "[ping]\ninterval = 1.0\ntimeout = \"soon\"\n";
+        let err = Config::parse(toml).unwrap_err();
+        assert!(err.starts_with("3:11: "), "{err}");
+        assert!(err.contains("invalid type"), "{err}");
+    }
+
+    #[test]
+    fn unknown_keys_and_clamps_are_reported() {
+        let text = "[ping]\ninterval = 1.0\ntimeout = 3.0\nhistory_size = 10\npacket_size = 32\nintreval = 5\n\n\
+                    [ui]\nrefresh_rate = 100\ntheme = \"dark\"\ngraph_height = 10\n\n\
+                    [[hosts]]\nname = \"a\"\naddress = \"1.1.1.1\"\n\n\
+                    [[hosts]]\nname = \"b\"\nadress = \"x\"\naddress = \"8.8.8.8\"\n";
+        let (parsed, unknown) = Config::parse(text).unwrap();
+        assert_eq!(
+            unknown,
+            vec![
+                ConfigWarning::UnknownKey("ping.intreval".into()),
+                ConfigWarning::UnknownKey("hosts[1].adress".into()),
+            ]
+        );
+        assert_eq!(parsed.hosts.len(), 2);
+
+        let mut c = Config::default();
+        c.ping.interval = 0.0;
+        c.hosts[0].interval = Some(f64::NAN);
+        let warnings = c.validate();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0].to_string(),
+            "ping.interval: 0 is out of range, using 1"
+        );
+        assert!(warnings[1]
+            .to_string()
+            .starts_with("hosts[0].interval: NaN"));
+        // Values the file simply leaves out get their defaults without complaint.
+        assert!(Config::default().validate().is_empty());
+    }
+
+    #[test]
+    fn overrides_apply_once() {
+        let overrides = Overrides {
+            hosts: vec!["9.9.9.9".to_string()],
+            interval: Some(0.5),
+            ..Overrides::default()
+        };
+        let mut c = Config::default();
+        let n = c.hosts.len();
+        overrides.apply(&mut c).unwrap();
+        overrides.apply(&mut c).unwrap();
+        assert_eq!(c.hosts.len(), n + 1);
+        assert_eq!(c.ping.interval, 0.5);
+    }
+
+    #[test]
+    fn env_overrides_sit_between_file_and_flags() {
+        let env = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let mut c = Config::default();
+        let warnings = Overrides {
+            env: env(&[
+                ("PINGPONG_PING_TIMEOUT", "2"),
+                ("PINGPONG_PING_INTERVAL", "5"),
+                ("PINGPONG_UI_SHOW_DETAILS", "no"),
+                ("PINGPONG_UI_THEME", "light"),
+                ("PINGPONG_PING_PACKET_SIZE", "70000"), // too big for a u16
+                ("PINGPONG_PING_TIMEOTU", "1"),
+                ("PINGPONG_CONFIG", "/elsewhere.toml"), // not a field override
+            ]),
+            interval: Some(0.5),
+            ..Overrides::default()
+        }
+        .apply(&mut c)
+        .unwrap();
+        assert_eq!(c.ping.timeout, 2.0);
+        assert_eq!(c.ping.interval, 0.5); // --interval beats the environment
+        assert!(!c.ui.show_details);
+        assert_eq!(c.ui.theme, "light");
+        assert_eq!(c.ping.packet_size, Config::default().ping.packet_size);
+        let shown: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            shown,
+            vec![
+                "PINGPONG_PING_PACKET_SIZE: not a valid value, ignored",
+                "PINGPONG_PING_TIMEOTU: not a pingpong setting, ignored",
+            ]
+        );
+    }
+
+    #[test]
+    fn env_sets_fields_left_unset_in_the_file() {
+        let min_mos = |raw: &str| {
+            let mut c = Config::default();
+            let warnings = Overrides {
+                env: vec![("PINGPONG_PING_MIN_MOS".to_string(), raw.to_string())],
+                ..Overrides::default()
+            }
+            .apply(&mut c)
+            .unwrap();
+            let shown: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+            (c.ping.min_mos, shown)
+        };
+        assert_eq!(min_mos("3.6"), (Some(3.6), vec![]));
+        assert_eq!(min_mos("4"), (Some(4.0), vec![]));
+        assert_eq!(
+            min_mos("great"),
+            (
+                None,
+                vec!["PINGPONG_PING_MIN_MOS: not a valid value, ignored".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn profiles_pick_hosts_by_name_or_tag_and_override_settings() {
+        let text = "[ping]\ninterval = 1.0\ntimeout = 3.0\nhistory_size = 10\npacket_size = 32\n\n\
+                    [ui]\nrefresh_rate = 100\ntheme = \"dark\"\ngraph_height = 10\n\n\
+                    [[hosts]]\nname = \"Router\"\naddress = \"10.0.0.1\"\n\n\
+                    [[hosts]]\nname = \"Wiki\"\naddress = \"wiki.corp\"\ntags = [\"office\"]\n\n\
+                    [[hosts]]\nname = \"Mail\"\naddress = \"mail.corp\"\ntags = [\"office\"]\nenabled = false\n\n\
+                    [profiles.office]\nhosts = [\"office\", \"Router\", \"Printer\"]\n\
+                    ping = { interval = 5.0 }\nui = { theme = \"light\" }\n\n\
+                    [profiles.home]\nhosts = [\"Router\"]\n";
+        let (file, _) = Config::parse(text).unwrap();
+        let names = |c: &Config| {
+            c.enabled_hosts()
+                .map(|h| h.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&file), ["Router", "Wiki"]);
+
+        let mut office = file.clone();
+        let overrides = Overrides {
+            profile: Some("office".to_string()),
+            hosts: vec!["9.9.9.9".to_string()],
+            ..Overrides::default()
+        };
+        overrides.apply(&mut office).unwrap();
+        assert_eq!(office.ping.interval, 5.0);
+        assert_eq!(office.ping.timeout, 3.0);
+        assert_eq!(office.ui.theme, "light");
+        // Disabled hosts stay off; --host hosts are monitored whatever the profile.
+        assert_eq!(names(&office), ["Router", "Wiki", "IP 9.9.9.9"]);
+
+        let mut home = file.clone();
+        home.use_profile("home").unwrap();
+        assert_eq!(names(&home), ["Router"]);
+        assert_eq!(home.ping.interval, 1.0);
+
+        let err = file.clone().use_profile("vpn").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no profile named 'vpn' (defined: home, office)"
+        );
+        let shown: Vec<String> = office.validate().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            shown,
+            ["profiles.office.hosts: \"Printer\" matches no host name or tag"]
+        );
+    }
+
+    #[test]
+    fn search_paths_follow_env_then_xdg_then_home_then_etc() {
+        let env = |name: &str| match name {
+            "XDG_CONFIG_HOME" => Some("/x".to_string()),
+            "HOME" => Some("/home/me".to_string()),
+            "PINGPONG_CONFIG" => Some(String::new()), // empty counts as unset
+            _ => None,
+        };
+        let paths = search_paths(env);
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/x/pingpong/config.toml"),
+                PathBuf::from("/home/me/.config/pingpong/config.toml"),
+                PathBuf::from("/etc/pingpong/config.toml"),
+            ]
+        );
+        // Nothing exists there, so the first location is where a new file goes.
+        assert_eq!(locate(&paths), paths[0]);
+    }
+
+    #[test]
+    fn saved_config_is_commented_and_loads_back() {
+        let dir = std::env::temp_dir().join(format!("pingpong-init-{}", std::process::id()));
+        let path = dir.join("nested/config.toml");
+        Config::default().save(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = Config::load_checked(&path);
+        let _ = fs::remove_dir_all(&dir);
+        assert!(text.contains("# Seconds between probes of each host\ninterval = 1.0"));
+        // Unset optional settings are still documented, commented out.
+        assert_eq!(text.matches("# min_mos = 3.6\n").count(), 1);
+        // One comment per field, not one per host.
+        assert_eq!(text.matches("# Display name").count(), 1);
+        let (loaded, unknown) = loaded.unwrap();
+        assert!(unknown.is_empty());
+        assert_eq!(loaded.hosts, Config::default().hosts);
+    }
+
+    #[test]
+    fn host_edits_apply_by_index() {
+        let mut c = Config {
+            hosts: vec![],
+            ..Config::default()
+        };
+        c.apply_edit(HostEdit::Add("1.1.1.1".to_string()));
+        c.apply_edit(HostEdit::Add("example.com".to_string()));
+        c.apply_edit(HostEdit::Rename(0, "Cloudflare".to_string()));
+        c.apply_edit(HostEdit::SetInterval(0, Some(0.01)));
+        c.apply_edit(HostEdit::ToggleEnabled(1));
+        c.apply_edit(HostEdit::Delete(7)); // out of range: ignored
+        c.validate();
+        assert_eq!(c.hosts.len(), 2);
+        assert_eq!(c.hosts[0].name, "Cloudflare");
+        assert_eq!(c.hosts[0].interval, None); // too small, dropped by validate
+        assert!(!c.hosts[1].enabled);
+        c.apply_edit(HostEdit::Delete(0));
+        assert_eq!(c.hosts[0].address, "example.com");
+    }
+
+    #[test]
+    fn save_hosts_keeps_other_settings_in_the_file() {
+        let path = std::env::temp_dir().join(format!("pingpong-save-{}.toml", std::process::id()));
+        let mut on_disk = Config::default();
+        on_disk.ping.interval = 5.0;
+        on_disk.save(&path).unwrap();
+
+        let mut running = Config::default();
+        running.ping.interval = 0.5; // e.g. from --interval
+        running.hosts.truncate(1);
+        running.hosts[0].name = "Renamed".to_string();
+        running.save_hosts(&path).unwrap();
+
+        let saved = Config::load_checked(&path).unwrap().0;
+        let _ = fs::remove_file(&path);
+        assert_eq!(saved.ping.interval, 5.0);
+        assert_eq!(saved.hosts, running.hosts);
+    }
+
+    #[test]
+    fn save_hosts_keeps_the_files_comments() {
+        let path = std::env::temp_dir().join(format!("pingpong-notes-{}.toml", std::process::id()));
+        let mut on_disk = Config::default();
+        on_disk.ping.interval = 5.0;
+        on_disk.save(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let text = text.replace("interval = 5.0", "interval = 5.0 # slow on purpose");
+        fs::write(&path, format!("# my notes\n{text}")).unwrap();
+
+        let mut c = Config::default();
+        c.hosts.truncate(2);
+        assert_eq!(c.save_hosts(&path).unwrap(), 2);
+
+        let text = fs::read_to_string(&path).unwrap();
+        let saved = Config::load_checked(&path).unwrap().0;
+        let _ = fs::remove_file(&path);
+        assert!(text.starts_with("# my notes\n"));
+        assert!(text.contains("interval = 5.0 # slow on purpose"));
+        assert_eq!(text.matches("[[hosts]]").count(), 2);
+        assert_eq!(saved.hosts, c.hosts);
+    }
+
+    #[test]
+    fn dual_stack_hosts_split_in_two_but_literals_keep_their_family() {
+        let host = |name: &str, address: &str, family| Host {
+            name: name.into(),
+            address: address.into(),
+            family: Some(family),
+            ..Host::default()
+        };
+        let mut c = Config {
+            hosts: vec![
+                host("Google", "google.com", Family::Both),
+                host("Quad9", "9.9.9.9", Family::Both),
+                host("Quad9 v6", "2620:fe::fe", Family::V6),
+            ],
+            ..Config::default()
+        };
+        let warnings: Vec<String> = c.validate().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec!["hosts[1].family: 9.9.9.9 has no IPv6 address, ignored"]
+        );
+        let probed = c.probed_hosts();
+        let names: Vec<&str> = probed.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Google (IPv4)", "Google (IPv6)", "Quad9", "Quad9 v6"]
+        );
+        assert_eq!(probed[1].family, Some(Family::V6));
+        assert_eq!(probed[1].stack.as_deref(), Some("Google"));
+        assert_eq!(probed[2].family, None);
+    }
+
+    #[test]
+    fn discovered_hosts_join_the_auto_group_and_are_never_saved() {
+        let found = Discovered {
+            gateway: Some("192.168.1.1".parse().unwrap()),
+            dns: vec!["192.168.1.1".parse().unwrap(), "9.9.9.9".parse().unwrap()],
+        };
+        let overrides = Overrides {
+            discovered: found.clone(),
+            ..Overrides::default()
+        };
+        let mut c = Config {
+            auto_hosts: vec![AutoHost::Gateway, AutoHost::Dns],
+            ..Config::default()
+        };
+        let n = c.hosts.len();
+        overrides.apply(&mut c).unwrap();
+        overrides.apply(&mut c).unwrap();
+        // The router is also the resolver; it's monitored once, as the gateway.
+        let auto: Vec<&Host> = c.hosts.iter().filter(|h| h.discovered).collect();
+        assert_eq!(c.hosts.len(), n + 2);
+        assert_eq!(auto[0].name, "Default gateway");
+        assert_eq!(auto[1].name, "DNS 9.9.9.9");
+        assert_eq!(auto[1].group.as_deref(), Some("Auto"));
+        assert_eq!(auto[1].depends_on, ["Default gateway"]);
+        assert!(c.validate().is_empty());
+
+        let path = std::env::temp_dir().join(format!("pingpong-auto-{}.toml", std::process::id()));
+        assert_eq!(c.save_hosts(&path).unwrap(), n);
+        let saved = Config::load_checked(&path).unwrap().0;
+        let _ = fs::remove_file(&path);
+        assert_eq!(saved.hosts.len(), n);
+
+        // Only the kinds the file asks for are added.
+        let mut gateway_only = Config {
+            auto_hosts: vec![AutoHost::Gateway],
+            ..Config::default()
+        };
+        gateway_only.add_discovered(&found);
+        assert_eq!(gateway_only.hosts.len(), n + 1);
+    }
+
     #[test]
     fn add_host_keeps_hostname_and_ipv6() {
         let mut c = Config {
-            ping: Config::default().ping,
             hosts: vec![],
-            ui: Config::default().ui,
+            ..Config::default()
         };
         c.add_host("example.com".to_string());
         c.add_host("2606:4700:4700::1111".to_string());
@@ -216,12 +1596,14 @@ mod tests {
         c.ping.history_size = 0;
         c.ping.packet_size = 0;
         c.ui.refresh_rate = 0;
+        c.ping.min_mos = Some(5.0);
         c.validate();
         assert!(c.ping.interval >= 0.1);
         assert!(c.ping.timeout >= 0.1);
         assert!(c.ping.history_size >= 1);
         assert!(c.ping.packet_size >= 1);
         assert!(c.ui.refresh_rate >= 1);
+        assert_eq!(c.ping.min_mos, Some(4.5));
     }
 
     #[test]
@@ -243,32 +1625,31 @@ mod tests {
                 Host {
                     name: "nan".into(),
                     address: "1.1.1.1".into(),
-                    enabled: true,
                     interval: Some(f64::NAN),
+                    ..Host::default()
                 },
                 Host {
                     name: "inf".into(),
                     address: "8.8.8.8".into(),
-                    enabled: true,
                     interval: Some(f64::INFINITY),
+                    ..Host::default()
                 },
                 Host {
                     name: "tiny".into(),
                     address: "9.9.9.9".into(),
-                    enabled: true,
                     interval: Some(0.0),
+                    ..Host::default()
                 },
                 Host {
                     name: "ok".into(),
                     address: "google.com".into(),
-                    enabled: true,
                     interval: Some(2.0),
+                    ..Host::default()
                 },
                 Host {
                     name: "none".into(),
                     address: "1.0.0.1".into(),
-                    enabled: true,
-                    interval: None,
+                    ..Host::default()
                 },
             ],
             ..Config::default()
@@ -294,6 +1675,125 @@ mod tests {
         assert_eq!(c.hosts[4].interval, None, "absent interval stays absent");
     }
 
+    #[test]
+    fn tcp_probe_parses_and_gets_default_port() {
+        let mut c: Config = toml::from_str(
+            r#"
+            [ping]
+            interval = 1.0
+            timeout = 3.0
+            history_size = 300
+            packet_size = 32
+
+            [ui]
+            refresh_rate = 100
+            theme = "auto"
+            graph_height = 10
+
+            [[hosts]]
+            name = "web"
+            address = "example.com"
+            probe = "tcp"
+            port = 443
+
+            [[hosts]]
+            name = "no port"
+            address = "example.org"
+            probe = "tcp"
+
+            [[hosts]]
+            name = "icmp"
+            address = "1.1.1.1"
+            "#,
+        )
+        .unwrap();
+        c.validate();
+        assert_eq!(c.hosts[0].probe, ProbeKind::Tcp);
+        assert_eq!(c.hosts[0].port, Some(443));
+        assert_eq!(c.hosts[1].port, Some(80), "TCP host without a port gets 80");
+        assert_eq!(c.hosts[2].probe, ProbeKind::Icmp, "probe defaults to icmp");
+        assert_eq!(c.hosts[2].port, None);
+    }
+
+    #[test]
+    fn http_probe_parses_with_expect() {
+        let c: Config = toml::from_str(
+            r#"
+            hosts = [{ name = "api", address = "https://api.example.com/health", probe = "http", expect = "ok" }]
+            [ping]
+            interval = 1.0
+            timeout = 3.0
+            history_size = 300
+            packet_size = 32
+            [ui]
+            refresh_rate = 100
+            theme = "auto"
+            graph_height = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(c.hosts[0].probe, ProbeKind::Http);
+        assert_eq!(c.hosts[0].expect.as_deref(), Some("ok"));
+    }
+
+    #[test]
+    fn hosts_that_cannot_be_probed_are_refused() {
+        let http = |address: &str, expect: Option<&str>| Config {
+            hosts: vec![Host {
+                name: "api".into(),
+                address: address.into(),
+                probe: ProbeKind::Http,
+                expect: expect.map(Into::into),
+                ..Host::default()
+            }],
+            ..Config::default()
+        };
+        assert!(http("https://api.example.com/health", Some("ok|up"))
+            .check_hosts()
+            .is_ok());
+        let err = http("api.example.com", None).check_hosts().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "hosts[0].address: not an http(s) URL: api.example.com"
+        );
+        let err = http("https://api.example.com", Some("(ok"))
+            .check_hosts()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("hosts[0].expect: invalid pattern"));
+    }
+
+    #[test]
+    fn dns_probe_gets_default_port_and_query() {
+        let mut c = Config {
+            hosts: vec![
+                Host {
+                    name: "resolver".into(),
+                    address: "9.9.9.9".into(),
+                    probe: ProbeKind::Dns,
+                    ..Host::default()
+                },
+                Host {
+                    name: "mx".into(),
+                    address: "1.1.1.1".into(),
+                    probe: ProbeKind::Dns,
+                    port: Some(5353),
+                    query: Some("example.org".into()),
+                    record_type: RecordType::Mx,
+                    ..Host::default()
+                },
+            ],
+            ..Config::default()
+        };
+        c.validate();
+        assert_eq!(c.hosts[0].port, Some(53));
+        assert_eq!(c.hosts[0].query.as_deref(), Some("example.com"));
+        assert_eq!(c.hosts[0].record_type, RecordType::A);
+        assert_eq!(c.hosts[1].port, Some(5353), "explicit port is kept");
+        assert_eq!(c.hosts[1].query.as_deref(), Some("example.org"));
+    }
+
     #[test]
     fn validate_clamps_graph_height() {
         let mut c = Config::default();
@@ -0,0 +1,113 @@
+// ABOUTME: Finds the local network's default gateway and system DNS servers.
+// ABOUTME: Reads /proc/net/route and resolv.conf so they can be monitored as hosts.
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// `RTF_UP | RTF_GATEWAY` from the kernel's route flags.
+const ROUTE_UP_VIA_GATEWAY: u32 = 0x1 | 0x2;
+
+/// Where the network currently sends traffic and name lookups.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Discovered {
+    pub gateway: Option<IpAddr>,
+    pub dns: Vec<IpAddr>,
+}
+
+/// Look at the system as it is now. Anything unreadable (another OS, a sandbox)
+/// simply isn't found.
+pub fn discover() -> Discovered {
+    let gateway = fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|route| default_gateway(&route))
+        .map(IpAddr::V4);
+    // With systemd-resolved, /etc/resolv.conf only names the local stub; the servers
+    // it forwards to are listed in its own copy.
+    let mut dns = read_nameservers("/etc/resolv.conf");
+    if dns.is_empty() {
+        dns = read_nameservers("/run/systemd/resolve/resolv.conf");
+    }
+    Discovered { gateway, dns }
+}
+
+fn read_nameservers(path: &str) -> Vec<IpAddr> {
+    fs::read_to_string(path)
+        .map(|text| nameservers(&text))
+        .unwrap_or_default()
+}
+
+/// The gateway of the default route with the lowest metric, from `/proc/net/route`
+/// (addresses there are hex in the kernel's byte order).
+pub fn default_gateway(route: &str) -> Option<Ipv4Addr> {
+    route
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let hex = |i: usize| u32::from_str_radix(cols.get(i)?, 16).ok();
+            let (dest, gateway, flags) = (hex(1)?, hex(2)?, hex(3)?);
+            let metric: u32 = cols.get(6)?.parse().ok()?;
+            let is_default = dest == 0 && flags & ROUTE_UP_VIA_GATEWAY == ROUTE_UP_VIA_GATEWAY;
+            is_default.then(|| (metric, Ipv4Addr::from(gateway.to_ne_bytes())))
+        })
+        .min_by_key(|(metric, _)| *metric)
+        .map(|(_, gateway)| gateway)
+}
+
+/// `nameserver` entries from resolv.conf, in order, leaving out loopback stubs (they
+/// answer from the local machine and say nothing about the network).
+pub fn nameservers(resolv: &str) -> Vec<IpAddr> {
+    resolv
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next()? == "nameserver").then_some(())?;
+            // A link-local IPv6 server may carry a zone (fe80::1%eth0).
+            let addr = words.next()?.split('%').next()?;
+            addr.parse::<IpAddr>().ok()
+        })
+        .filter(|ip| !ip.is_loopback())
+        .collect()
+}
+
+#[cfg(test)]
+mod discover_tests {
+    use super::*;
+
+    const ROUTE: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+eth0\t00000000\tFE00000A\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+
+    #[test]
+    fn default_route_with_lowest_metric_wins() {
+        assert_eq!(default_gateway(ROUTE), Some(Ipv4Addr::new(10, 0, 0, 254)));
+        // Without the wired route, the wireless one is the default.
+        let wifi_only: String = ROUTE.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            default_gateway(&wifi_only),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(default_gateway("Iface\tDestination\n"), None);
+    }
+
+    #[test]
+    fn nameservers_skip_comments_loopback_and_zones() {
+        let resolv = "# Generated by NetworkManager\n\
+                      search lan\n\
+                      nameserver 192.168.1.1\n\
+                      nameserver 127.0.0.53\n\
+                      ; nameserver 9.9.9.9\n\
+                      nameserver fe80::1%wlan0\n\
+                      options edns0\n";
+        assert_eq!(
+            nameservers(resolv),
+            vec![
+                "192.168.1.1".parse::<IpAddr>().unwrap(),
+                "fe80::1".parse().unwrap()
+            ]
+        );
+    }
+}
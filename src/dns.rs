@@ -0,0 +1,353 @@
+// ABOUTME: DNS query latency probe that asks one chosen resolver directly.
+// ABOUTME: Sends a query over UDP (falling back to TCP when truncated) and parses the answer set.
+
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// Record types the probe can ask for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RecordType {
+    #[default]
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Ns,
+    Txt,
+    Ptr,
+    Soa,
+}
+
+impl RecordType {
+    fn code(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Ns => 2,
+            RecordType::Cname => 5,
+            RecordType::Soa => 6,
+            RecordType::Ptr => 12,
+            RecordType::Mx => 15,
+            RecordType::Txt => 16,
+            RecordType::Aaaa => 28,
+        }
+    }
+}
+
+/// Outcome of one query that got a reply (timeouts are reported separately).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DnsOutcome {
+    /// NOERROR with the (sorted) answers of the requested type; may be empty (NODATA).
+    Answers(Vec<String>),
+    /// Any other response code, as a short label such as "NXDOMAIN" or "SERVFAIL".
+    Rcode(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DnsFailure {
+    Timeout,
+    Failed(String),
+}
+
+/// Short label for a DNS response code.
+fn rcode_label(rcode: u8) -> String {
+    match rcode {
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        n => format!("RCODE {n}"),
+    }
+}
+
+/// Encode a standard recursive query for `name`/`rtype`.
+fn build_query(id: u16, name: &str, rtype: RecordType) -> Option<Vec<u8>> {
+    let mut msg = Vec::with_capacity(512);
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&0x0100u16.to_be_bytes()); // RD
+    msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // QD=1, AN=NS=AR=0
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&rtype.code().to_be_bytes());
+    msg.extend_from_slice(&1u16.to_be_bytes()); // IN
+    Some(msg)
+}
+
+/// Read a (possibly compressed) domain name at `pos`. Returns the name and the offset
+/// just past it in the original position (pointers don't advance past their target).
+fn read_name(msg: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bound pointer chasing so a malicious loop can't spin forever.
+    for _ in 0..128 {
+        let len = *msg.get(pos)? as usize;
+        if len & 0xC0 == 0xC0 {
+            let ptr = ((len & 0x3F) << 8) | *msg.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = ptr;
+        } else if len == 0 {
+            let name = if labels.is_empty() {
+                ".".to_string()
+            } else {
+                labels.join(".")
+            };
+            return Some((name, end.unwrap_or(pos + 1)));
+        } else {
+            let label = msg.get(pos + 1..pos + 1 + len)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        }
+    }
+    None
+}
+
+/// Render one record's data for the answer set.
+fn render_rdata(msg: &[u8], rtype: u16, start: usize, rdata: &[u8]) -> Option<String> {
+    Some(match rtype {
+        1 if rdata.len() == 4 => Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).to_string(),
+        28 if rdata.len() == 16 => {
+            let octets: [u8; 16] = rdata.try_into().ok()?;
+            Ipv6Addr::from(octets).to_string()
+        }
+        2 | 5 | 12 => read_name(msg, start)?.0,
+        15 if rdata.len() > 2 => {
+            let pref = u16::from_be_bytes([rdata[0], rdata[1]]);
+            format!("{pref} {}", read_name(msg, start + 2)?.0)
+        }
+        16 => {
+            let mut parts = Vec::new();
+            let mut i = 0;
+            while i < rdata.len() {
+                let n = rdata[i] as usize;
+                let s = rdata.get(i + 1..i + 1 + n)?;
+                parts.push(String::from_utf8_lossy(s).into_owned());
+                i += 1 + n;
+            }
+            parts.join("")
+        }
+        _ => rdata.iter().map(|b| format!("{b:02x}")).collect(),
+    })
+}
+
+/// Parse a response to query `id`. Returns the truncation bit and the outcome.
+fn parse_response(msg: &[u8], id: u16, rtype: RecordType) -> Option<(bool, DnsOutcome)> {
+    if msg.len() < 12 || u16::from_be_bytes([msg[0], msg[1]]) != id {
+        return None;
+    }
+    let flags = u16::from_be_bytes([msg[2], msg[3]]);
+    if flags & 0x8000 == 0 {
+        return None; // not a response
+    }
+    let truncated = flags & 0x0200 != 0;
+    let rcode = (flags & 0x000F) as u8;
+    if rcode != 0 {
+        return Some((truncated, DnsOutcome::Rcode(rcode_label(rcode))));
+    }
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    let ancount = u16::from_be_bytes([msg[6], msg[7]]);
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        pos = read_name(msg, pos)?.1 + 4;
+    }
+    let mut answers = Vec::new();
+    for _ in 0..ancount {
+        pos = read_name(msg, pos)?.1;
+        let head = msg.get(pos..pos + 10)?;
+        let atype = u16::from_be_bytes([head[0], head[1]]);
+        let rdlen = u16::from_be_bytes([head[8], head[9]]) as usize;
+        let start = pos + 10;
+        let rdata = msg.get(start..start + rdlen)?;
+        // CNAME chains come back alongside the final records; keep only what was asked for.
+        if atype == rtype.code() {
+            answers.push(render_rdata(msg, atype, start, rdata)?);
+        }
+        pos = start + rdlen;
+    }
+    answers.sort();
+    answers.dedup();
+    Some((truncated, DnsOutcome::Answers(answers)))
+}
+
+/// One configured DNS check: ask `resolver` for `name`/`rtype` and time the reply.
+pub struct DnsProbe {
+    resolver: SocketAddr,
+    name: String,
+    rtype: RecordType,
+    timeout: Duration,
+}
+
+impl DnsProbe {
+    pub fn new(resolver: SocketAddr, name: &str, rtype: RecordType, timeout: Duration) -> Self {
+        Self {
+            resolver,
+            name: name.to_string(),
+            rtype,
+            timeout,
+        }
+    }
+
+    /// Perform one query; returns the round-trip time and outcome.
+    pub async fn run(&self) -> Result<(Duration, DnsOutcome), DnsFailure> {
+        let start = Instant::now();
+        match tokio::time::timeout(self.timeout, self.exchange()).await {
+            Ok(Ok(outcome)) => Ok((start.elapsed(), outcome)),
+            Ok(Err(e)) => Err(DnsFailure::Failed(e)),
+            Err(_) => Err(DnsFailure::Timeout),
+        }
+    }
+
+    async fn exchange(&self) -> Result<DnsOutcome, String> {
+        let id: u16 = rand::random();
+        let query = build_query(id, &self.name, self.rtype)
+            .ok_or_else(|| format!("invalid query name: {}", self.name))?;
+
+        let bind: SocketAddr = if self.resolver.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let sock = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+        sock.connect(self.resolver)
+            .await
+            .map_err(|e| e.to_string())?;
+        sock.send(&query).await.map_err(|e| e.to_string())?;
+        let mut buf = vec![0u8; 4096];
+        let (truncated, outcome) = loop {
+            let n = sock.recv(&mut buf).await.map_err(|e| e.to_string())?;
+            // Ignore stray or mismatched datagrams; the overall timeout bounds the wait.
+            if let Some(parsed) = parse_response(&buf[..n], id, self.rtype) {
+                break parsed;
+            }
+        };
+        if !truncated {
+            return Ok(outcome);
+        }
+
+        // The answer didn't fit in a datagram: repeat the query over TCP.
+        let mut stream = TcpStream::connect(self.resolver)
+            .await
+            .map_err(|e| format!("tcp fallback: {e}"))?;
+        let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&query);
+        stream
+            .write_all(&framed)
+            .await
+            .map_err(|e| format!("tcp fallback: {e}"))?;
+        let len = stream
+            .read_u16()
+            .await
+            .map_err(|e| format!("tcp fallback: {e}"))? as usize;
+        let mut msg = vec![0u8; len];
+        stream
+            .read_exact(&mut msg)
+            .await
+            .map_err(|e| format!("tcp fallback: {e}"))?;
+        parse_response(&msg, id, self.rtype)
+            .map(|(_, outcome)| outcome)
+            .ok_or_else(|| "malformed response".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a response to `query` carrying `rcode`, the TC bit, and A records.
+    fn response(query: &[u8], rcode: u8, truncated: bool, ips: &[[u8; 4]]) -> Vec<u8> {
+        let mut msg = query.to_vec();
+        let flags: u16 = 0x8180 | rcode as u16 | if truncated { 0x0200 } else { 0 };
+        msg[2..4].copy_from_slice(&flags.to_be_bytes());
+        msg[6..8].copy_from_slice(&(ips.len() as u16).to_be_bytes());
+        for ip in ips {
+            msg.extend_from_slice(&[0xC0, 12]); // pointer to the question name
+            msg.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+            msg.extend_from_slice(ip);
+        }
+        msg
+    }
+
+    #[test]
+    fn query_encodes_labels_and_type() {
+        let q = build_query(0xABCD, "example.com.", RecordType::Aaaa).unwrap();
+        assert_eq!(&q[..2], &[0xAB, 0xCD]);
+        assert_eq!(&q[12..25], b"\x07example\x03com\x00");
+        assert_eq!(&q[25..27], &[0, 28]);
+        assert!(build_query(1, "bad..name", RecordType::A).is_none());
+    }
+
+    #[test]
+    fn parses_sorted_answers_through_compression() {
+        let q = build_query(7, "example.com", RecordType::A).unwrap();
+        let r = response(&q, 0, false, &[[5, 6, 7, 8], [1, 2, 3, 4]]);
+        assert_eq!(
+            parse_response(&r, 7, RecordType::A),
+            Some((
+                false,
+                DnsOutcome::Answers(vec!["1.2.3.4".into(), "5.6.7.8".into()])
+            ))
+        );
+    }
+
+    #[test]
+    fn rcodes_and_mismatched_ids() {
+        let q = build_query(9, "nope.example", RecordType::A).unwrap();
+        assert_eq!(
+            parse_response(&response(&q, 3, false, &[]), 9, RecordType::A),
+            Some((false, DnsOutcome::Rcode("NXDOMAIN".into())))
+        );
+        assert_eq!(
+            parse_response(&response(&q, 2, false, &[]), 9, RecordType::A),
+            Some((false, DnsOutcome::Rcode("SERVFAIL".into())))
+        );
+        assert_eq!(
+            parse_response(&response(&q, 0, false, &[]), 10, RecordType::A),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn truncated_udp_reply_falls_back_to_tcp() {
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = udp.local_addr().unwrap();
+        let tcp = tokio::net::TcpListener::bind(addr).await.unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (n, peer) = udp.recv_from(&mut buf).await.unwrap();
+            let r = response(&buf[..n], 0, true, &[]);
+            udp.send_to(&r, peer).await.unwrap();
+
+            let (mut s, _) = tcp.accept().await.unwrap();
+            let len = s.read_u16().await.unwrap() as usize;
+            let mut q = vec![0u8; len];
+            s.read_exact(&mut q).await.unwrap();
+            let r = response(&q, 0, false, &[[9, 9, 9, 9]]);
+            s.write_all(&(r.len() as u16).to_be_bytes()).await.unwrap();
+            s.write_all(&r).await.unwrap();
+        });
+        let probe = DnsProbe::new(addr, "example.com", RecordType::A, Duration::from_secs(2));
+        let (_, outcome) = probe.run().await.unwrap();
+        assert_eq!(outcome, DnsOutcome::Answers(vec!["9.9.9.9".into()]));
+    }
+
+    #[tokio::test]
+    async fn silent_resolver_times_out() {
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let probe = DnsProbe::new(
+            udp.local_addr().unwrap(),
+            "example.com",
+            RecordType::A,
+            Duration::from_millis(100),
+        );
+        assert_eq!(probe.run().await, Err(DnsFailure::Timeout));
+    }
+}
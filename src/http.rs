@@ -0,0 +1,539 @@
+// ABOUTME: HTTP(S) request-timing probe for continuously monitored web services.
+// ABOUTME: Times the DNS, connect, TLS, time-to-first-byte and total phases of one GET.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{self, pki_types::ServerName};
+use tokio_rustls::TlsConnector;
+
+use crate::config::Family;
+use crate::ping::PingEngine;
+use crate::stats::{deserialize_ms, deserialize_opt_ms, serialize_ms, serialize_opt_ms, REFUSED};
+
+/// Upper bound on how much of a response we buffer; enough for a health-check body.
+const MAX_RESPONSE: usize = 64 * 1024;
+
+/// A parsed `http://` or `https://` URL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpTarget {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+/// Duration of each phase of one request. Phases are sequential, not cumulative:
+/// `ttfb` is the wait between sending the request and the first response byte.
+/// `total` covers the whole request from DNS to the last body byte.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HttpPhases {
+    #[serde(
+        rename = "dns_ms",
+        serialize_with = "serialize_ms",
+        deserialize_with = "deserialize_ms"
+    )]
+    pub dns: Duration,
+    #[serde(
+        rename = "connect_ms",
+        serialize_with = "serialize_ms",
+        deserialize_with = "deserialize_ms"
+    )]
+    pub connect: Duration,
+    #[serde(
+        rename = "tls_ms",
+        serialize_with = "serialize_opt_ms",
+        deserialize_with = "deserialize_opt_ms"
+    )]
+    pub tls: Option<Duration>,
+    #[serde(
+        rename = "ttfb_ms",
+        serialize_with = "serialize_ms",
+        deserialize_with = "deserialize_ms"
+    )]
+    pub ttfb: Duration,
+    #[serde(
+        rename = "total_ms",
+        serialize_with = "serialize_ms",
+        deserialize_with = "deserialize_ms"
+    )]
+    pub total: Duration,
+}
+
+impl HttpPhases {
+    /// Compact one-line breakdown for the host header, e.g. "dns 1 · tcp 9 · tls 21 · ttfb 40 ms".
+    pub fn summary(&self) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mut parts = vec![
+            format!("dns {:.0}", ms(self.dns)),
+            format!("tcp {:.0}", ms(self.connect)),
+        ];
+        if let Some(tls) = self.tls {
+            parts.push(format!("tls {:.0}", ms(tls)));
+        }
+        parts.push(format!("ttfb {:.0} ms", ms(self.ttfb)));
+        parts.join(" \u{b7} ")
+    }
+}
+
+/// Why a request did not count as a success.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HttpFailure {
+    Timeout,
+    Failed(String),
+}
+
+/// Split an http(s) URL into scheme, host, port and request target (path and query; a
+/// fragment is never sent). Returns None for other schemes.
+pub fn parse_url(url: &str) -> Option<HttpTarget> {
+    let (tls, rest) = if let Some(r) = url.strip_prefix("https://") {
+        (true, r)
+    } else if let Some(r) = url.strip_prefix("http://") {
+        (false, r)
+    } else {
+        return None;
+    };
+    // The authority ends at the path, the query or the fragment, whichever comes first.
+    let (authority, target) = rest.split_at(rest.find(['/', '?', '#']).unwrap_or(rest.len()));
+    let target = target.split('#').next().unwrap_or_default();
+    let path = if target.starts_with('/') {
+        target.to_string()
+    } else {
+        format!("/{target}")
+    };
+    let default_port = if tls { 443 } else { 80 };
+    // Bracketed IPv6 literal, optionally followed by :port.
+    let (host, port) = if let Some(v6) = authority.strip_prefix('[') {
+        let (h, tail) = v6.split_once(']')?;
+        let port = match tail.strip_prefix(':') {
+            Some(p) => p.parse().ok()?,
+            None => default_port,
+        };
+        (h.to_string(), port)
+    } else {
+        match authority.rsplit_once(':') {
+            Some((h, p)) => (h.to_string(), p.parse().ok()?),
+            None => (authority.to_string(), default_port),
+        }
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some(HttpTarget {
+        tls,
+        host,
+        port,
+        path,
+    })
+}
+
+/// One configured HTTP(S) check: GET `url`, require a 2xx status and, optionally,
+/// a body matching `expect`.
+pub struct HttpProbe {
+    target: HttpTarget,
+    family: Option<Family>,
+    /// Connect here rather than looking the host up on every request.
+    pinned: Option<IpAddr>,
+    expect: Option<Regex>,
+    timeout: Duration,
+}
+
+impl HttpProbe {
+    pub fn new(
+        url: &str,
+        expect: Option<&str>,
+        family: Option<Family>,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let target = parse_url(url).ok_or_else(|| anyhow::anyhow!("not an http(s) URL: {url}"))?;
+        let expect = expect
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("invalid expect pattern: {e}"))?;
+        Ok(Self {
+            target,
+            family,
+            pinned: None,
+            expect,
+            timeout,
+        })
+    }
+
+    /// Always connect to `ip`, skipping the lookup (its phase reads as zero). The
+    /// request still names the URL's host, for TLS and virtual hosting.
+    pub fn pinned(self, ip: IpAddr) -> Self {
+        Self {
+            pinned: Some(ip),
+            ..self
+        }
+    }
+
+    /// Perform one request. The whole exchange is bounded by the probe timeout.
+    pub async fn run(&self) -> Result<HttpPhases, HttpFailure> {
+        match tokio::time::timeout(self.timeout, self.exchange()).await {
+            Ok(r) => r,
+            Err(_) => Err(HttpFailure::Timeout),
+        }
+    }
+
+    async fn exchange(&self) -> Result<HttpPhases, HttpFailure> {
+        let start = Instant::now();
+        let t = &self.target;
+
+        // Unlike the ICMP/TCP loops, an HTTP probe resolves on every request: DNS is one
+        // of the phases being measured, the same way a browser pays for it.
+        let ip = match self.pinned {
+            Some(ip) => ip,
+            None => PingEngine::resolve_hostname(&t.host, self.family)
+                .await
+                .map_err(|e| HttpFailure::Failed(format!("dns: {e}")))?,
+        };
+        let dns = start.elapsed();
+
+        let connect_start = Instant::now();
+        let tcp = TcpStream::connect(SocketAddr::new(ip, t.port))
+            .await
+            .map_err(|e| {
+                HttpFailure::Failed(if e.kind() == std::io::ErrorKind::ConnectionRefused {
+                    REFUSED.to_string()
+                } else {
+                    e.to_string()
+                })
+            })?;
+        let connect = connect_start.elapsed();
+
+        let (tls, response) = if t.tls {
+            let tls_start = Instant::now();
+            let server_name = ServerName::try_from(t.host.clone())
+                .map_err(|e| HttpFailure::Failed(format!("tls: {e}")))?;
+            let stream = TlsConnector::from(tls_config())
+                .connect(server_name, tcp)
+                .await
+                .map_err(|e| HttpFailure::Failed(format!("tls: {e}")))?;
+            let tls = tls_start.elapsed();
+            (Some(tls), self.request(stream).await?)
+        } else {
+            (None, self.request(tcp).await?)
+        };
+        let (status, ttfb, body) = response;
+        let total = start.elapsed();
+
+        if !(200..300).contains(&status) {
+            return Err(HttpFailure::Failed(format!("HTTP {status}")));
+        }
+        if let Some(re) = &self.expect {
+            if !re.is_match(&String::from_utf8_lossy(&body)) {
+                return Err(HttpFailure::Failed("body mismatch".to_string()));
+            }
+        }
+        Ok(HttpPhases {
+            dns,
+            connect,
+            tls,
+            ttfb,
+            total,
+        })
+    }
+
+    /// Send the GET and read the response. Returns (status, time to first byte, body).
+    async fn request<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: S,
+    ) -> Result<(u16, Duration, Vec<u8>), HttpFailure> {
+        let t = &self.target;
+        let req = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: pingpong\r\nAccept: */*\r\n\r\n",
+            t.path,
+            host_header(t)
+        );
+        send(stream, &req).await
+    }
+}
+
+/// POST a JSON body to `url` and return the response status. Used by the webhook
+/// alert sink; the whole exchange is bounded by `timeout`.
+pub async fn post_json(url: &str, body: &str, timeout: Duration) -> anyhow::Result<u16> {
+    let t = parse_url(url).ok_or_else(|| anyhow::anyhow!("not an http(s) URL: {url}"))?;
+    let req = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: pingpong\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        t.path,
+        host_header(&t),
+        body.len()
+    );
+    let exchange = async {
+        let ip = PingEngine::resolve_hostname(&t.host, None).await?;
+        let tcp = TcpStream::connect(SocketAddr::new(ip, t.port)).await?;
+        let response = if t.tls {
+            let server_name = ServerName::try_from(t.host.clone())?;
+            let stream = TlsConnector::from(tls_config())
+                .connect(server_name, tcp)
+                .await?;
+            send(stream, &req).await
+        } else {
+            send(tcp, &req).await
+        };
+        match response {
+            Ok((status, _, _)) => Ok(status),
+            Err(HttpFailure::Failed(e)) => Err(anyhow::anyhow!(e)),
+            Err(HttpFailure::Timeout) => Err(anyhow::anyhow!("timed out")),
+        }
+    };
+    tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out"))?
+}
+
+/// Host header value: brackets for IPv6 literals, port only when not the scheme default.
+fn host_header(t: &HttpTarget) -> String {
+    let host = if t.host.contains(':') {
+        format!("[{}]", t.host) // IPv6 literal
+    } else {
+        t.host.clone()
+    };
+    if (t.tls && t.port == 443) || (!t.tls && t.port == 80) {
+        host
+    } else {
+        format!("{host}:{}", t.port)
+    }
+}
+
+/// Write a request and read the response. Returns (status, time to first byte, body).
+async fn send<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    req: &str,
+) -> Result<(u16, Duration, Vec<u8>), HttpFailure> {
+    let io_err = |e: std::io::Error| HttpFailure::Failed(e.to_string());
+    stream.write_all(req.as_bytes()).await.map_err(io_err)?;
+    let sent = Instant::now();
+
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let mut ttfb = None;
+    loop {
+        let n = stream.read(&mut chunk).await.map_err(io_err)?;
+        if n == 0 {
+            break;
+        }
+        ttfb.get_or_insert_with(|| sent.elapsed());
+        buf.extend_from_slice(&chunk[..n]);
+        // Stop as soon as the response is complete so a server that ignores
+        // `Connection: close` can't hold us until the timeout.
+        if buf.len() >= MAX_RESPONSE || response_complete(&buf) {
+            break;
+        }
+    }
+    let ttfb = ttfb.ok_or_else(|| HttpFailure::Failed("empty response".to_string()))?;
+    let (status, body) = parse_response(&buf)
+        .ok_or_else(|| HttpFailure::Failed("malformed response".to_string()))?;
+    Ok((status, ttfb, body))
+}
+
+/// Shared rustls client config with the bundled Mozilla root store.
+fn tls_config() -> Arc<rustls::ClientConfig> {
+    static CONFIG: OnceLock<Arc<rustls::ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let roots = rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let mut config = rustls::ClientConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default TLS versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+            config.alpn_protocols = vec![b"http/1.1".to_vec()];
+            Arc::new(config)
+        })
+        .clone()
+}
+
+/// Byte offset just past the blank line ending the response headers.
+fn header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)
+}
+
+fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (k, v) = line.split_once(':')?;
+        k.trim().eq_ignore_ascii_case(name).then(|| v.trim())
+    })
+}
+
+/// True once the buffered bytes hold the full response per Content-Length or chunked framing.
+fn response_complete(buf: &[u8]) -> bool {
+    let Some(end) = header_end(buf) else {
+        return false;
+    };
+    let head = String::from_utf8_lossy(&buf[..end]);
+    if let Some(len) = header_value(&head, "content-length").and_then(|v| v.parse::<usize>().ok()) {
+        return buf.len() - end >= len;
+    }
+    if header_value(&head, "transfer-encoding").is_some_and(|v| v.contains("chunked")) {
+        return buf[end..].ends_with(b"0\r\n\r\n");
+    }
+    false
+}
+
+/// Split a raw response into status code and (de-chunked) body.
+fn parse_response(buf: &[u8]) -> Option<(u16, Vec<u8>)> {
+    let end = header_end(buf)?;
+    let head = String::from_utf8_lossy(&buf[..end]);
+    let status = head
+        .lines()
+        .next()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|c| c.parse::<u16>().ok())?;
+    let raw = &buf[end..];
+    let body = if header_value(&head, "transfer-encoding").is_some_and(|v| v.contains("chunked")) {
+        dechunk(raw)
+    } else {
+        raw.to_vec()
+    };
+    Some((status, body))
+}
+
+/// Decode a chunked body; a truncated final chunk keeps whatever arrived.
+fn dechunk(mut raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(line_end) = raw.windows(2).position(|w| w == b"\r\n") {
+        let size_field = String::from_utf8_lossy(&raw[..line_end]);
+        let size_hex = size_field.split(';').next().unwrap_or("").trim();
+        let Ok(size) = usize::from_str_radix(size_hex, 16) else {
+            break;
+        };
+        if size == 0 {
+            break;
+        }
+        let data = &raw[line_end + 2..];
+        let take = size.min(data.len());
+        out.extend_from_slice(&data[..take]);
+        raw = data.get(take + 2..).unwrap_or(&[]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Serve one canned response on a local port and return the URL to fetch.
+    async fn serve_once(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = sock.read(&mut buf).await;
+            let _ = sock.write_all(response.as_bytes()).await;
+        });
+        format!("http://{addr}/health")
+    }
+
+    #[test]
+    fn parse_url_handles_schemes_ports_and_ipv6() {
+        assert_eq!(
+            parse_url("https://example.com"),
+            Some(HttpTarget {
+                tls: true,
+                host: "example.com".into(),
+                port: 443,
+                path: "/".into(),
+            })
+        );
+        assert_eq!(
+            parse_url("http://10.0.0.5:8080/status?x=1"),
+            Some(HttpTarget {
+                tls: false,
+                host: "10.0.0.5".into(),
+                port: 8080,
+                path: "/status?x=1".into(),
+            })
+        );
+        assert_eq!(parse_url("http://[::1]:9000/").map(|t| t.port), Some(9000));
+        assert_eq!(
+            parse_url("http://[::1]/").map(|t| t.host),
+            Some("::1".into())
+        );
+        // A query or fragment straight after the authority isn't part of the host.
+        let target = |url| parse_url(url).map(|t| (t.host, t.port, t.path));
+        assert_eq!(
+            target("http://host?x=1"),
+            Some(("host".into(), 80, "/?x=1".into()))
+        );
+        assert_eq!(
+            target("https://host:8443#frag"),
+            Some(("host".into(), 8443, "/".into()))
+        );
+        assert_eq!(
+            target("http://host/a?b=1#c"),
+            Some(("host".into(), 80, "/a?b=1".into()))
+        );
+        assert_eq!(parse_url("ftp://example.com"), None);
+        assert_eq!(parse_url("http://host:notaport/"), None);
+    }
+
+    #[test]
+    fn dechunk_joins_chunks() {
+        assert_eq!(
+            dechunk(b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n"),
+            b"Wikipedia"
+        );
+    }
+
+    #[test]
+    fn completion_follows_content_length_and_chunking() {
+        assert!(!response_complete(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nab"
+        ));
+        assert!(response_complete(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nab"
+        ));
+        assert!(response_complete(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn success_records_phases_and_matches_body() {
+        let url = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nstatus: ok\n").await;
+        let probe = HttpProbe::new(&url, Some("status: ok"), None, Duration::from_secs(2)).unwrap();
+        let phases = probe.run().await.unwrap();
+        assert_eq!(phases.tls, None, "plain http has no TLS phase");
+        assert!(phases.total >= phases.connect + phases.ttfb);
+    }
+
+    #[tokio::test]
+    async fn non_2xx_is_a_failure() {
+        let url = serve_once("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n").await;
+        let probe = HttpProbe::new(&url, None, None, Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            probe.run().await,
+            Err(HttpFailure::Failed("HTTP 503".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn body_mismatch_is_a_failure() {
+        let url = serve_once(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\ndown\r\n0\r\n\r\n",
+        )
+        .await;
+        let probe = HttpProbe::new(&url, Some("^up$"), None, Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            probe.run().await,
+            Err(HttpFailure::Failed("body mismatch".into()))
+        );
+    }
+
+    #[test]
+    fn invalid_pattern_is_rejected() {
+        assert!(HttpProbe::new("http://x/", Some("("), None, Duration::from_secs(1)).is_err());
+    }
+}
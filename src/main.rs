@@ -1,19 +1,31 @@
 // ABOUTME: Main entry point for pingpong TUI ping utility
 // ABOUTME: Orchestrates the async runtime, configuration loading, and TUI initialization
 
-use anyhow::Result;
-use clap::{Parser, ValueEnum};
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 
+mod alerts;
 mod app;
 mod config;
+mod discover;
+mod dns;
+mod http;
+mod metrics;
+mod netwatch;
+mod output;
 mod ping;
 mod probe;
+mod session;
 mod stats;
 mod status;
+mod storage;
+mod trace;
 mod tui;
 
-use app::App;
-use config::Config;
+use app::{App, Options, Source};
+use config::{Config, Overrides};
 use tui::AnimationType;
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -39,18 +51,61 @@ impl From<AnimationChoice> for AnimationType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputChoice {
+    /// Interactive terminal UI
+    Tui,
+    /// One JSON object per event on stdout, no terminal UI
+    Jsonl,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Monitor as usual while recording every event to FILE (e.g. session.pplog)
+    Record { file: PathBuf },
+    /// Play a recording back through the UI instead of probing the network
+    Replay {
+        file: PathBuf,
+        /// Playback speed, e.g. 10x
+        #[arg(long, default_value = "1x", value_parser = session::parse_speed)]
+        speed: f64,
+    },
+    /// Work with the configuration file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Report TOML errors, unknown keys and out-of-range values in the config file
+    Check,
+    /// Write a commented default config file (to the first search location unless --config is given)
+    Init {
+        /// Replace an existing file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
 #[derive(Parser)]
 #[command(name = "pingpong")]
 #[command(about = "A beautiful TUI ping utility for monitoring network connectivity")]
 struct Cli {
-    /// Configuration file path
-    #[arg(short, long, default_value = "pingpong.toml")]
-    config: String,
+    /// Configuration file path [default: $PINGPONG_CONFIG, $XDG_CONFIG_HOME/pingpong/config.toml,
+    /// ~/.config/pingpong/config.toml or /etc/pingpong/config.toml, whichever exists first]
+    #[arg(short, long)]
+    config: Option<PathBuf>,
 
     /// Ping interval in seconds (overrides config when set)
     #[arg(short, long)]
     interval: Option<f64>,
 
+    /// Monitoring profile to use, from the config's [profiles.NAME] sections
+    #[arg(short, long)]
+    profile: Option<String>,
+
     /// Additional hosts to ping (can be used multiple times)
     #[arg(long)]
     host: Vec<String>,
@@ -58,36 +113,143 @@ struct Cli {
     /// Animation type: plasma, globe, bounce, matrix, dna, or waveform
     #[arg(short, long, value_enum)]
     animation: Option<AnimationChoice>,
+
+    /// Run without the TUI, streaming events to stdout (same as --output jsonl)
+    #[arg(long)]
+    headless: bool,
+
+    /// Output format: tui or jsonl
+    #[arg(long, value_enum, default_value = "tui")]
+    output: OutputChoice,
+
+    /// Serve Prometheus metrics at http://ADDR/metrics (e.g. 127.0.0.1:9898)
+    #[arg(long, value_name = "ADDR")]
+    metrics_listen: Option<SocketAddr>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let default_hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |info| {
-        tui::terminal_leave();
-        default_hook(info);
-    }));
-
     let cli = Cli::parse();
-
-    // Load configuration
-    let mut config = Config::load(&cli.config).unwrap_or_else(|_| Config::default());
-
-    // Add CLI hosts to config
-    for host in cli.host {
-        config.add_host(host);
+    let search = config::search_paths(|name| std::env::var(name).ok());
+    let config_path = match &cli.config {
+        Some(path) => path.clone(),
+        None => config::locate(&search),
+    };
+    // A --profile overrides the file, PINGPONG_* variables override that, and the other
+    // flags override everything.
+    let overrides = Overrides {
+        profile: cli.profile,
+        discovered: discover::discover(),
+        env: std::env::vars()
+            .filter(|(k, _)| k.starts_with("PINGPONG_"))
+            .collect(),
+        hosts: cli.host,
+        interval: cli.interval,
+    };
+    if let Some(Command::Config { action }) = &cli.command {
+        return match action {
+            ConfigCommand::Check => check_config(&config_path, &overrides),
+            ConfigCommand::Init { force } => {
+                // A fresh file belongs with the user (or wherever --config says), not in /etc.
+                let path = cli
+                    .config
+                    .as_ref()
+                    .or(search.first())
+                    .unwrap_or(&config_path);
+                init_config(path, *force)
+            }
+        };
     }
-
-    // Override interval if specified
-    if let Some(interval) = cli.interval {
-        config.set_interval(interval);
+    let headless = cli.headless || cli.output == OutputChoice::Jsonl;
+
+    // Only the TUI owns the terminal; headless output must not get escape codes mixed in.
+    if !headless {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            tui::terminal_leave();
+            default_hook(info);
+        }));
     }
-    config.validate();
+
+    // Load configuration. Defaults stand in only when there is no file; a broken one
+    // would quietly swap in someone else's host list, so stop instead.
+    let (mut config, mut config_warnings) = Config::load_or_default(&config_path)
+        .context("Not starting with a broken config (see `pingpong config check`)")?;
+
+    // Apply the profile, environment and CLI overrides; kept so a reloaded config gets them too
+    let file_config = config.clone();
+    config_warnings.extend(overrides.apply(&mut config)?);
+    config_warnings.extend(config.validate());
+    config
+        .check_hosts()
+        .context("Not starting with a broken config (see `pingpong config check`)")?;
 
     // Convert animation choice if provided
     let animation_type = cli.animation.map(|choice| choice.into());
 
+    let source = match cli.command {
+        None | Some(Command::Config { .. }) => Source::Live { record: None },
+        Some(Command::Record { file }) => Source::Live { record: Some(file) },
+        Some(Command::Replay { file, speed }) => Source::Replay {
+            session: session::Session::load(&file)?,
+            speed,
+        },
+    };
+
     // Initialize and run the app
-    let app = App::new(config, animation_type).await?;
+    let opts = Options {
+        animation_type,
+        headless,
+        metrics_listen: cli.metrics_listen,
+        source,
+        config_path,
+        file_config,
+        overrides,
+        config_warnings,
+    };
+    let app = App::new(config, opts).await?;
     app.run().await
 }
+
+/// `pingpong config check`: list every problem with the config file (and any
+/// `PINGPONG_*` overrides), failing if it can't be used at all.
+fn check_config(path: &Path, overrides: &Overrides) -> Result<()> {
+    let shown = path.display();
+    if !path.exists() {
+        bail!("{shown}: not found (pingpong would start with the built-in defaults)");
+    }
+    let (mut config, mut warnings) = Config::load_checked(path)?;
+    warnings.extend(
+        overrides
+            .apply(&mut config)
+            .with_context(|| shown.to_string())?,
+    );
+    warnings.extend(config.validate());
+    config.check_hosts().with_context(|| shown.to_string())?;
+    alerts::check_sinks(&config.alerts.sinks).with_context(|| shown.to_string())?;
+    for warning in &warnings {
+        println!("{shown}: {warning}");
+    }
+    println!(
+        "{shown}: ok, {} hosts monitored, {} warnings",
+        config.enabled_hosts().count(),
+        warnings.len()
+    );
+    Ok(())
+}
+
+/// `pingpong config init`: write the defaults, with comments, for editing.
+fn init_config(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!(
+            "{}: already exists (use --force to replace it)",
+            path.display()
+        );
+    }
+    Config::default().save(path)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,353 @@
+// ABOUTME: Prometheus text-format exporter for per-host stats and connectivity state.
+// ABOUTME: A minimal HTTP listener serves the latest rendered snapshot at /metrics.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+use crate::stats::{PingStats, RttStats};
+use crate::status::{ConnectivityState, HostState};
+
+/// Longest request head we read before answering; scrapers send a few hundred bytes.
+const MAX_REQUEST: usize = 8 * 1024;
+
+type Counter = fn(&PingStats) -> u64;
+type RttGauge = fn(&RttStats) -> Duration;
+
+const COUNTERS: [(&str, &str, Counter); 4] = [
+    ("pings_total", "Probes sent", PingStats::total_pings),
+    (
+        "pings_successful_total",
+        "Probes answered",
+        PingStats::successful_pings,
+    ),
+    (
+        "timeouts_total",
+        "Probes that timed out",
+        PingStats::timeouts,
+    ),
+    (
+        "errors_total",
+        "Probes that failed with an error",
+        PingStats::errors,
+    ),
+];
+
+const RTT_GAUGES: [(&str, &str, RttGauge); 6] = [
+    (
+        "rtt_min_seconds",
+        "Minimum RTT over the retained history",
+        |r| r.min,
+    ),
+    (
+        "rtt_avg_seconds",
+        "Mean RTT over the retained history",
+        |r| r.avg,
+    ),
+    (
+        "rtt_median_seconds",
+        "Median RTT over the retained history",
+        |r| r.median,
+    ),
+    (
+        "rtt_max_seconds",
+        "Maximum RTT over the retained history",
+        |r| r.max,
+    ),
+    (
+        "rtt_stddev_seconds",
+        "Standard deviation of RTT over the retained history",
+        |r| r.stddev,
+    ),
+    (
+        "rtt_jitter_seconds",
+        "Interarrival jitter (RFC 3550): smoothed difference between consecutive RTTs",
+        |r| r.jitter,
+    ),
+];
+
+const HOST_STATES: [&str; 4] = ["resolving", "up", "degraded", "down"];
+const CONNECTIVITY_STATES: [&str; 4] = ["online", "degraded", "captive_portal", "offline"];
+
+/// Bind the metrics listener. Done up front so a bad address fails before the TUI starts.
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind metrics listener on {addr}"))
+}
+
+/// Serve `GET /metrics` with the latest snapshot from `body` until the sender is dropped.
+pub async fn serve(listener: TcpListener, body: watch::Receiver<String>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        if body.has_changed().is_err() {
+            break;
+        }
+        let snapshot = body.borrow().clone();
+        tokio::spawn(async move {
+            let _ = tokio::time::timeout(Duration::from_secs(5), respond(stream, snapshot)).await;
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, snapshot: String) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut parts = head.split_whitespace();
+    let (method, path) = (parts.next(), parts.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or("");
+    let (status, body) = match (method, path) {
+        (Some("GET"), "/metrics") => ("200 OK", snapshot),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Render every metric in the Prometheus text exposition format. Hosts without stats
+/// yet still get their state; RTT gauges appear once a host has a reply in its history.
+pub fn render(
+    hosts: &[(String, String)],
+    stats: &HashMap<String, PingStats>,
+    states: &[(String, HostState)],
+    connectivity: &ConnectivityState,
+) -> String {
+    let mut out = String::new();
+    let labels =
+        |id: &str, name: &str| format!("host_id=\"{}\",host=\"{}\"", escape(id), escape(name));
+    let with_stats = || {
+        hosts
+            .iter()
+            .filter_map(|(id, name)| Some((labels(id, name), stats.get(id)?)))
+    };
+
+    for (name, help, value) in COUNTERS {
+        family(&mut out, name, "counter", help);
+        for (l, s) in with_stats() {
+            let _ = writeln!(out, "pingpong_{name}{{{l}}} {}", value(s));
+        }
+    }
+
+    family(
+        &mut out,
+        "loss_recent_ratio",
+        "gauge",
+        "Loss over the last 20 probes (0-1)",
+    );
+    for (l, s) in with_stats() {
+        let ratio = s.packet_loss_percent_recent(20) / 100.0;
+        let _ = writeln!(out, "pingpong_loss_recent_ratio{{{l}}} {ratio}");
+    }
+
+    let with_rtt: Vec<_> = with_stats()
+        .filter(|(_, s)| s.recent_results(usize::MAX).iter().any(|r| r.is_success()))
+        .map(|(l, s)| (l, s.rtt_stats()))
+        .collect();
+    for (name, help, value) in RTT_GAUGES {
+        family(&mut out, name, "gauge", help);
+        for (l, r) in &with_rtt {
+            let _ = writeln!(out, "pingpong_{name}{{{l}}} {}", value(r).as_secs_f64());
+        }
+    }
+
+    family(
+        &mut out,
+        "rtt_seconds",
+        "histogram",
+        "RTT of every answered probe",
+    );
+    for (l, s) in with_stats() {
+        let h = s.rtt_histogram();
+        for (le_ms, n) in &h.buckets {
+            let le = le_ms / 1000.0;
+            let _ = writeln!(out, "pingpong_rtt_seconds_bucket{{{l},le=\"{le}\"}} {n}");
+        }
+        let _ = writeln!(
+            out,
+            "pingpong_rtt_seconds_bucket{{{l},le=\"+Inf\"}} {}",
+            h.count
+        );
+        let _ = writeln!(
+            out,
+            "pingpong_rtt_seconds_sum{{{l}}} {}",
+            h.sum.as_secs_f64()
+        );
+        let _ = writeln!(out, "pingpong_rtt_seconds_count{{{l}}} {}", h.count);
+    }
+
+    family(
+        &mut out,
+        "host_state",
+        "gauge",
+        "Current host state (1 for the active state)",
+    );
+    for (id, state) in states {
+        let name = hosts
+            .iter()
+            .find(|(h, _)| h == id)
+            .map_or("", |(_, n)| n.as_str());
+        let l = labels(id, name);
+        let current = state.label();
+        for s in HOST_STATES {
+            let v = u8::from(s == current);
+            let _ = writeln!(out, "pingpong_host_state{{{l},state=\"{s}\"}} {v}");
+        }
+    }
+
+    family(
+        &mut out,
+        "connectivity_state",
+        "gauge",
+        "Overall connectivity (1 for the active state)",
+    );
+    let current = connectivity_label(connectivity);
+    for s in CONNECTIVITY_STATES {
+        let v = u8::from(s == current);
+        let _ = writeln!(out, "pingpong_connectivity_state{{state=\"{s}\"}} {v}");
+    }
+
+    out
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP pingpong_{name} {help}");
+    let _ = writeln!(out, "# TYPE pingpong_{name} {kind}");
+}
+
+fn connectivity_label(state: &ConnectivityState) -> &'static str {
+    match state {
+        ConnectivityState::Online => "online",
+        ConnectivityState::Degraded => "degraded",
+        ConnectivityState::CaptivePortal { .. } => "captive_portal",
+        ConnectivityState::Offline => "offline",
+    }
+}
+
+/// Escape a label value: backslash, double quote and newline.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+    use crate::stats::PingResult;
+    use std::time::Instant;
+
+    fn fixture() -> String {
+        let hosts = vec![
+            ("a".to_string(), "Google \"DNS\"".to_string()),
+            ("b".to_string(), "Router".to_string()),
+        ];
+        let mut s = PingStats::new(10);
+        for ms in [10, 30] {
+            s.add_result(&PingResult::Success {
+                rtt: Duration::from_millis(ms),
+                sequence: 0,
+                timestamp: Instant::now(),
+            });
+        }
+        s.add_result(&PingResult::Timeout {
+            sequence: 2,
+            timestamp: Instant::now(),
+        });
+        let stats = HashMap::from([("a".to_string(), s)]);
+        let states = vec![
+            (
+                "a".to_string(),
+                HostState::Degraded {
+                    loss_pct: 33.3,
+                    mos: None,
+                },
+            ),
+            ("b".to_string(), HostState::Resolving),
+        ];
+        render(&hosts, &stats, &states, &ConnectivityState::Degraded)
+    }
+
+    #[test]
+    fn counters_and_gauges_carry_escaped_host_labels() {
+        let out = fixture();
+        let l = r#"host_id="a",host="Google \"DNS\"""#;
+        assert!(out.contains("# TYPE pingpong_pings_total counter"));
+        assert!(out.contains(&format!("pingpong_pings_total{{{l}}} 3")));
+        assert!(out.contains(&format!("pingpong_timeouts_total{{{l}}} 1")));
+        assert!(out.contains(&format!("pingpong_rtt_avg_seconds{{{l}}} 0.02")));
+        assert!(out.contains(&format!("pingpong_rtt_max_seconds{{{l}}} 0.03")));
+        // Spread and jitter are separate series: 10 and 30 ms are 10 ms either side of
+        // the mean, and one 20 ms step smooths to 20/16 ms of jitter.
+        assert!(out.contains(&format!("pingpong_rtt_stddev_seconds{{{l}}} 0.01")));
+        assert!(out.contains(&format!("pingpong_rtt_jitter_seconds{{{l}}} 0.00125")));
+        // A host with no results yet has a state but no stats series.
+        assert!(!out.contains(r#"pingpong_pings_total{host_id="b""#));
+    }
+
+    #[test]
+    fn histogram_ends_with_inf_sum_and_count() {
+        let out = fixture();
+        let l = r#"host_id="a",host="Google \"DNS\"""#;
+        assert!(out.contains(&format!("pingpong_rtt_seconds_bucket{{{l},le=\"0.01\"}} 1")));
+        assert!(out.contains(&format!(
+            "pingpong_rtt_seconds_bucket{{{l},le=\"0.025\"}} 1"
+        )));
+        assert!(out.contains(&format!("pingpong_rtt_seconds_bucket{{{l},le=\"+Inf\"}} 2")));
+        assert!(out.contains(&format!("pingpong_rtt_seconds_count{{{l}}} 2")));
+    }
+
+    #[test]
+    fn states_are_exposed_as_one_hot_sets() {
+        let out = fixture();
+        assert!(
+            out.contains(r#"pingpong_host_state{host_id="b",host="Router",state="resolving"} 1"#)
+        );
+        assert!(out.contains(r#"pingpong_host_state{host_id="b",host="Router",state="up"} 0"#));
+        assert!(out.contains(r#"pingpong_connectivity_state{state="degraded"} 1"#));
+        assert!(out.contains(r#"pingpong_connectivity_state{state="online"} 0"#));
+    }
+
+    #[tokio::test]
+    async fn serves_snapshot_at_metrics_path_only() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = watch::channel("pingpong_up 1\n".to_string());
+        tokio::spawn(serve(listener, rx));
+
+        let get = |path: &'static str| async move {
+            let mut s = TcpStream::connect(addr).await.unwrap();
+            s.write_all(format!("GET {path} HTTP/1.1\r\nHost: x\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+            let mut resp = String::new();
+            s.read_to_string(&mut resp).await.unwrap();
+            resp
+        };
+        let ok = get("/metrics").await;
+        assert!(ok.starts_with("HTTP/1.1 200"));
+        assert!(ok.ends_with("\r\n\r\npingpong_up 1\n"));
+        assert!(get("/").await.starts_with("HTTP/1.1 404"));
+        drop(tx);
+    }
+}
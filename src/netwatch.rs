@@ -0,0 +1,465 @@
+// ABOUTME: Notices network changes (links, addresses, default routes) as they happen.
+// ABOUTME: Listens on a Linux netlink socket and reports each burst of changes as one event.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// What about the network changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// An interface went up or down, appeared or vanished.
+    Link,
+    /// A routable address was added to or removed from an interface.
+    Address,
+    /// A default route changed: a new gateway, or a VPN taking over all traffic.
+    Route,
+}
+
+/// One settled burst of network changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkChange {
+    pub changes: Vec<ChangeKind>,
+}
+
+/// How long to keep collecting after the first change of a burst; switching Wi-Fi or
+/// bringing a VPN up takes several notifications spread over a second or so.
+const SETTLE: Duration = Duration::from_secs(1);
+
+/// Watch the network on a thread of its own and send each burst of changes to `tx`.
+/// Fails where there is nothing to watch with (another OS, a sandbox without netlink);
+/// hosts then still re-resolve after repeated failures.
+pub fn spawn(tx: mpsc::Sender<NetworkChange>) -> io::Result<()> {
+    sys::spawn(tx)
+}
+
+/// An rtnetlink listener. Addresses and default routes are tracked rather than taken
+/// at face value: IPv6 router adverts re-announce both every few minutes, and those
+/// refreshes are not changes.
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::{ChangeKind, NetworkChange, SETTLE};
+    use std::collections::{BTreeSet, HashSet};
+    use std::io;
+    use std::mem;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::time::{Duration, Instant};
+    use tokio::sync::mpsc;
+
+    /// Length of a netlink message header; messages and attributes are padded to 4 bytes.
+    const HEADER_LEN: usize = 16;
+    /// Interface flags whose change means a link really went up or down (wireless
+    /// drivers also send link messages that change nothing).
+    const LINK_FLAGS: u32 = (libc::IFF_UP | libc::IFF_RUNNING | libc::IFF_LOWER_UP) as u32;
+    /// Route attributes that tell one default route from another.
+    const ROUTE_ATTRS: [u16; 5] = [
+        libc::RTA_TABLE,
+        libc::RTA_DST,
+        libc::RTA_GATEWAY,
+        libc::RTA_OIF,
+        libc::RTA_PRIORITY,
+    ];
+    /// Sequence numbers of the two dumps that seed the tracker.
+    const ADDRESS_DUMP: u32 = 1;
+    const ROUTE_DUMP: u32 = 2;
+
+    pub fn spawn(tx: mpsc::Sender<NetworkChange>) -> io::Result<()> {
+        let socket = Socket::open()?;
+        // Learn what's there already so the first refresh of it isn't taken for a change.
+        let mut tracker = Tracker::default();
+        socket.dump(libc::RTM_GETADDR, ADDRESS_DUMP, &mut tracker)?;
+        socket.dump(libc::RTM_GETROUTE, ROUTE_DUMP, &mut tracker)?;
+        std::thread::Builder::new()
+            .name("netwatch".into())
+            .spawn(move || watch(socket, tracker, tx))?;
+        Ok(())
+    }
+
+    /// Collect changes until a burst has had `SETTLE` to play out, then send them as one
+    /// event. Returns when the app goes away or the socket fails.
+    fn watch(socket: Socket, mut tracker: Tracker, tx: mpsc::Sender<NetworkChange>) {
+        let mut pending = BTreeSet::new();
+        let mut deadline: Option<Instant> = None;
+        loop {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                deadline = None;
+                let changes = mem::take(&mut pending).into_iter().collect();
+                if tx.blocking_send(NetworkChange { changes }).is_err() {
+                    return;
+                }
+            }
+            match socket.recv(deadline.map(|d| d.saturating_duration_since(Instant::now()))) {
+                Ok(Some(buf)) => pending.extend(tracker.feed(&buf)),
+                Ok(None) => {}
+                // The kernel dropped notifications it had no room for; there's no telling
+                // which, so assume the worst.
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                    pending.extend([ChangeKind::Link, ChangeKind::Address, ChangeKind::Route]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => return,
+            }
+            if !pending.is_empty() && deadline.is_none() {
+                deadline = Some(Instant::now() + SETTLE);
+            }
+        }
+    }
+
+    /// A NETLINK_ROUTE socket subscribed to link, address and route notifications.
+    struct Socket(OwnedFd);
+
+    impl Socket {
+        fn open() -> io::Result<Self> {
+            // SAFETY: plain socket(2); on success the fd is owned by the OwnedFd from here on.
+            let fd = unsafe {
+                libc::socket(
+                    libc::AF_NETLINK,
+                    libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                    libc::NETLINK_ROUTE,
+                )
+            };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: fd was just opened and nothing else owns it.
+            let socket = Self(unsafe { OwnedFd::from_raw_fd(fd) });
+            // SAFETY: sockaddr_nl is plain data; all-zero is a valid value.
+            let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups = (libc::RTMGRP_LINK
+                | libc::RTMGRP_IPV4_IFADDR
+                | libc::RTMGRP_IPV6_IFADDR
+                | libc::RTMGRP_IPV4_ROUTE
+                | libc::RTMGRP_IPV6_ROUTE) as u32;
+            // SAFETY: binds the open fd to a live sockaddr_nl of the size given.
+            let rc = unsafe {
+                libc::bind(
+                    fd,
+                    &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                )
+            };
+            if rc < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(socket)
+        }
+
+        /// Ask the kernel for every address or route it has (`kind` is RTM_GETADDR or
+        /// RTM_GETROUTE) and feed the replies to `tracker`.
+        fn dump(&self, kind: u16, seq: u32, tracker: &mut Tracker) -> io::Result<()> {
+            // The header, then a zeroed ifaddrmsg/rtmsg: family AF_UNSPEC, i.e. all of them.
+            const LEN: usize = HEADER_LEN + 12;
+            let mut request = [0u8; LEN];
+            let flags = (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16;
+            request[0..4].copy_from_slice(&(LEN as u32).to_ne_bytes());
+            request[4..6].copy_from_slice(&kind.to_ne_bytes());
+            request[6..8].copy_from_slice(&flags.to_ne_bytes());
+            request[8..12].copy_from_slice(&seq.to_ne_bytes());
+            // SAFETY: sends from a live buffer of the length given on an open fd.
+            let rc = unsafe {
+                libc::send(
+                    self.0.as_raw_fd(),
+                    request.as_ptr() as *const libc::c_void,
+                    request.len(),
+                    0,
+                )
+            };
+            if rc < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            loop {
+                let buf = self.recv(Some(Duration::from_secs(5)))?.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::TimedOut, "no reply to netlink dump")
+                })?;
+                tracker.feed(&buf);
+                if dump_finished(&buf, seq) {
+                    return Ok(());
+                }
+            }
+        }
+
+        /// The next datagram, or None once `timeout` passes (None waits indefinitely).
+        fn recv(&self, timeout: Option<Duration>) -> io::Result<Option<Vec<u8>>> {
+            let fd = self.0.as_raw_fd();
+            let mut pfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // Round up so a sub-millisecond wait doesn't become a busy loop.
+            let ms = timeout.map_or(-1, |t| {
+                t.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+            });
+            // SAFETY: polls one live pollfd.
+            let ready = unsafe { libc::poll(&mut pfd, 1, ms) };
+            if ready < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if ready == 0 {
+                return Ok(None);
+            }
+            let mut buf = vec![0u8; 64 * 1024];
+            // SAFETY: receives into a live buffer of the length given on an open fd.
+            let n = unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            buf.truncate(n as usize);
+            Ok(Some(buf))
+        }
+    }
+
+    /// The routable addresses and default routes known to exist, as opaque keys.
+    #[derive(Debug, Default)]
+    pub struct Tracker {
+        addresses: HashSet<Vec<u8>>,
+        routes: HashSet<Vec<u8>>,
+    }
+
+    impl Tracker {
+        /// What a datagram of netlink messages changed.
+        pub fn feed(&mut self, buf: &[u8]) -> BTreeSet<ChangeKind> {
+            let mut changes = BTreeSet::new();
+            for msg in messages(buf) {
+                let changed = match msg.kind {
+                    libc::RTM_NEWLINK => link_changed(msg.body).then_some(ChangeKind::Link),
+                    libc::RTM_DELLINK => Some(ChangeKind::Link),
+                    libc::RTM_NEWADDR => address_key(msg.body)
+                        .filter(|key| self.addresses.insert(key.clone()))
+                        .map(|_| ChangeKind::Address),
+                    libc::RTM_DELADDR => address_key(msg.body)
+                        .filter(|key| self.addresses.remove(key))
+                        .map(|_| ChangeKind::Address),
+                    libc::RTM_NEWROUTE => route_key(msg.body)
+                        .filter(|key| self.routes.insert(key.clone()))
+                        .map(|_| ChangeKind::Route),
+                    libc::RTM_DELROUTE => route_key(msg.body)
+                        .filter(|key| self.routes.remove(key))
+                        .map(|_| ChangeKind::Route),
+                    _ => None,
+                };
+                changes.extend(changed);
+            }
+            changes
+        }
+    }
+
+    struct Message<'a> {
+        kind: u16,
+        seq: u32,
+        body: &'a [u8],
+    }
+
+    /// The messages in a datagram; stops at the first one that doesn't fit.
+    fn messages(mut buf: &[u8]) -> impl Iterator<Item = Message<'_>> {
+        std::iter::from_fn(move || {
+            let len = u32_at(buf, 0)? as usize;
+            if len < HEADER_LEN || len > buf.len() {
+                return None;
+            }
+            let msg = Message {
+                kind: u16_at(buf, 4)?,
+                seq: u32_at(buf, 8)?,
+                body: &buf[HEADER_LEN..len],
+            };
+            buf = &buf[align(len).min(buf.len())..];
+            Some(msg)
+        })
+    }
+
+    /// `(type, payload)` of each attribute in a message's attribute area.
+    fn attributes(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+        std::iter::from_fn(move || {
+            let len = u16_at(buf, 0)? as usize;
+            if len < 4 || len > buf.len() {
+                return None;
+            }
+            let attr = (u16_at(buf, 2)?, &buf[4..len]);
+            buf = &buf[align(len).min(buf.len())..];
+            Some(attr)
+        })
+    }
+
+    /// True once the reply to dump `seq` is complete (or the kernel refused it).
+    fn dump_finished(buf: &[u8], seq: u32) -> bool {
+        messages(buf).any(|m| {
+            m.seq == seq
+                && (m.kind == libc::NLMSG_DONE as u16 || m.kind == libc::NLMSG_ERROR as u16)
+        })
+    }
+
+    /// Whether an RTM_NEWLINK (an ifinfomsg: family, pad, type, index, flags, change)
+    /// flipped the interface up or down.
+    fn link_changed(body: &[u8]) -> bool {
+        u32_at(body, 12).is_some_and(|change| change & LINK_FLAGS != 0)
+    }
+
+    /// Family, prefix length, interface and address of an ifaddrmsg, or None for the
+    /// link-local and host-scoped addresses that come and go with every interface.
+    fn address_key(body: &[u8]) -> Option<Vec<u8>> {
+        let header = body.get(0..8)?;
+        if header[3] >= libc::RT_SCOPE_LINK {
+            return None;
+        }
+        let attrs = || attributes(&body[8..]);
+        let addr = attrs()
+            .find(|(kind, _)| *kind == libc::IFA_LOCAL)
+            .or_else(|| attrs().find(|(kind, _)| *kind == libc::IFA_ADDRESS))?
+            .1;
+        Some([&header[0..2], &header[4..8], addr].concat())
+    }
+
+    /// Family, prefix length and the `ROUTE_ATTRS` of an rtmsg, or None unless it's a
+    /// default route. A /1 counts too: VPNs take over with a 0/1 + 128/1 pair.
+    fn route_key(body: &[u8]) -> Option<Vec<u8>> {
+        let header = body.get(0..12)?;
+        let (dst_len, table, kind) = (header[1], header[4], header[7]);
+        if dst_len > 1 || kind != libc::RTN_UNICAST || table == libc::RT_TABLE_LOCAL {
+            return None;
+        }
+        let mut key = vec![header[0], dst_len, table];
+        for (kind, payload) in attributes(&body[12..]) {
+            if ROUTE_ATTRS.contains(&kind) {
+                key.extend_from_slice(&kind.to_ne_bytes());
+                key.push(payload.len() as u8);
+                key.extend_from_slice(payload);
+            }
+        }
+        Some(key)
+    }
+
+    fn align(len: usize) -> usize {
+        (len + 3) & !3
+    }
+
+    fn u16_at(buf: &[u8], at: usize) -> Option<u16> {
+        Some(u16::from_ne_bytes(buf.get(at..at + 2)?.try_into().ok()?))
+    }
+
+    fn u32_at(buf: &[u8], at: usize) -> Option<u32> {
+        Some(u32::from_ne_bytes(buf.get(at..at + 4)?.try_into().ok()?))
+    }
+
+    #[cfg(test)]
+    mod netwatch_tests {
+        use super::*;
+
+        fn message(kind: u16, seq: u32, body: &[u8]) -> Vec<u8> {
+            let len = HEADER_LEN + body.len();
+            let mut buf = Vec::new();
+            buf.extend_from_slice(&(len as u32).to_ne_bytes());
+            buf.extend_from_slice(&kind.to_ne_bytes());
+            buf.extend_from_slice(&0u16.to_ne_bytes());
+            buf.extend_from_slice(&seq.to_ne_bytes());
+            buf.extend_from_slice(&0u32.to_ne_bytes());
+            buf.extend_from_slice(body);
+            buf.resize(align(len), 0);
+            buf
+        }
+
+        fn attr(kind: u16, payload: &[u8]) -> Vec<u8> {
+            let len = 4 + payload.len();
+            let mut buf = Vec::new();
+            buf.extend_from_slice(&(len as u16).to_ne_bytes());
+            buf.extend_from_slice(&kind.to_ne_bytes());
+            buf.extend_from_slice(payload);
+            buf.resize(align(len), 0);
+            buf
+        }
+
+        fn address(kind: u16, scope: u8, ip: [u8; 4]) -> Vec<u8> {
+            let mut body = vec![libc::AF_INET as u8, 24, 0, scope];
+            body.extend_from_slice(&2u32.to_ne_bytes());
+            body.extend(attr(libc::IFA_LOCAL, &ip));
+            message(kind, 0, &body)
+        }
+
+        fn route(kind: u16, dst_len: u8, gateway: [u8; 4]) -> Vec<u8> {
+            let mut body = vec![libc::AF_INET as u8, dst_len, 0, 0, libc::RT_TABLE_MAIN];
+            body.extend([
+                libc::RTPROT_BOOT,
+                libc::RT_SCOPE_UNIVERSE,
+                libc::RTN_UNICAST,
+            ]);
+            body.extend_from_slice(&0u32.to_ne_bytes());
+            body.extend(attr(libc::RTA_GATEWAY, &gateway));
+            message(kind, 0, &body)
+        }
+
+        fn link(change: u32) -> Vec<u8> {
+            let mut body = vec![0u8; 12];
+            body.extend_from_slice(&change.to_ne_bytes());
+            message(libc::RTM_NEWLINK, 0, &body)
+        }
+
+        fn kinds(tracker: &mut Tracker, buf: &[u8]) -> Vec<ChangeKind> {
+            tracker.feed(buf).into_iter().collect()
+        }
+
+        #[test]
+        fn address_refreshes_are_not_changes() {
+            let mut t = Tracker::default();
+            let added = address(libc::RTM_NEWADDR, libc::RT_SCOPE_UNIVERSE, [192, 168, 1, 7]);
+            assert_eq!(kinds(&mut t, &added), vec![ChangeKind::Address]);
+            assert!(kinds(&mut t, &added).is_empty());
+            let removed = address(libc::RTM_DELADDR, libc::RT_SCOPE_UNIVERSE, [192, 168, 1, 7]);
+            assert_eq!(kinds(&mut t, &removed), vec![ChangeKind::Address]);
+            // Link-local addresses don't route anywhere; they're not worth re-resolving for.
+            let local = address(libc::RTM_NEWADDR, libc::RT_SCOPE_LINK, [169, 254, 0, 1]);
+            assert!(kinds(&mut t, &local).is_empty());
+        }
+
+        #[test]
+        fn only_default_routes_count() {
+            let mut t = Tracker::default();
+            assert!(kinds(&mut t, &route(libc::RTM_NEWROUTE, 24, [0; 4])).is_empty());
+            let default = route(libc::RTM_NEWROUTE, 0, [192, 168, 1, 1]);
+            assert_eq!(kinds(&mut t, &default), vec![ChangeKind::Route]);
+            assert!(kinds(&mut t, &default).is_empty());
+            // A different gateway is a different route.
+            let moved = route(libc::RTM_NEWROUTE, 0, [10, 0, 0, 1]);
+            assert_eq!(kinds(&mut t, &moved), vec![ChangeKind::Route]);
+        }
+
+        #[test]
+        fn links_count_only_when_they_go_up_or_down() {
+            let mut t = Tracker::default();
+            assert!(kinds(&mut t, &link(0)).is_empty());
+            assert_eq!(
+                kinds(&mut t, &link(libc::IFF_UP as u32)),
+                vec![ChangeKind::Link]
+            );
+        }
+
+        #[test]
+        fn one_datagram_carries_several_messages() {
+            let mut t = Tracker::default();
+            let mut buf = address(libc::RTM_NEWADDR, libc::RT_SCOPE_UNIVERSE, [10, 0, 0, 5]);
+            buf.extend(route(libc::RTM_NEWROUTE, 0, [10, 0, 0, 1]));
+            buf.extend(message(libc::NLMSG_DONE as u16, ROUTE_DUMP, &[0; 4]));
+            assert_eq!(
+                kinds(&mut t, &buf),
+                vec![ChangeKind::Address, ChangeKind::Route]
+            );
+            assert!(dump_finished(&buf, ROUTE_DUMP));
+            assert!(!dump_finished(&buf, ADDRESS_DUMP));
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use super::NetworkChange;
+    use std::io;
+    use tokio::sync::mpsc;
+
+    pub fn spawn(_tx: mpsc::Sender<NetworkChange>) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "watching for network changes is only available on Linux",
+        ))
+    }
+}
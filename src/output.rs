@@ -0,0 +1,292 @@
+// ABOUTME: Headless JSON-lines event stream: one object per line on stdout.
+// ABOUTME: Mirrors ping events, host state transitions and portal changes with wall-clock times.
+
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::io::{self, Write};
+use std::net::IpAddr;
+
+use crate::netwatch::NetworkChange;
+use crate::ping::{HostUpdate, PingEvent};
+use crate::probe::ProbeResult;
+use crate::status::{HostState, Transitions};
+
+/// Writes events as JSON lines. Every line carries `ts` (RFC 3339, UTC) and `event`;
+/// host events add `host_id` and `host`. Lines are flushed as written so a pipe
+/// into `jq` or a log shipper sees them immediately.
+pub struct JsonlWriter<W: Write> {
+    out: W,
+    states: Transitions,
+    portal: Option<ProbeResult>,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            states: Transitions::default(),
+            portal: None,
+        }
+    }
+
+    /// Write one line for an engine event.
+    pub fn ping_event(&mut self, event: &PingEvent) -> io::Result<()> {
+        let host = Some((event.host_id.as_str(), event.host_name.as_str()));
+        match &event.update {
+            HostUpdate::Resolving => self.line("resolving", host, json!({})),
+            HostUpdate::ResolveFailed(error) => {
+                self.line("resolve_failed", host, json!({ "error": error }))
+            }
+            HostUpdate::Resolved(ip) => self.line("resolved", host, json!({ "ip": ip })),
+            HostUpdate::HttpTiming(phases) => self.line("http_timing", host, phases),
+            HostUpdate::AnswersChanged { previous, current } => self.line(
+                "answers_changed",
+                host,
+                json!({ "previous": previous, "current": current }),
+            ),
+            HostUpdate::Pinged(result) => self.line("ping", host, result),
+            HostUpdate::Addresses(ips) => self.line("addresses", host, json!({ "ips": ips })),
+            // Same as a host's `ping` line, plus the address that was probed.
+            HostUpdate::AddressPinged { ip, result } => {
+                let mut body = serde_json::to_value(result)?;
+                body["ip"] = json!(ip);
+                self.line("ping", host, body)
+            }
+            HostUpdate::Paused => self.line("paused", host, json!({})),
+            HostUpdate::Resumed => self.line("resumed", host, json!({})),
+        }
+    }
+
+    /// Write a `state` line if the host's state changed kind (or its down reason)
+    /// since the last one written. RTT and loss wobble within a state are not transitions.
+    pub fn host_state(&mut self, host_id: &str, name: &str, state: &HostState) -> io::Result<()> {
+        if !self.states.changed(host_id, state) {
+            return Ok(());
+        }
+        self.line("state", Some((host_id, name)), state)
+    }
+
+    /// Write a `portal` line when the captive-portal classification changes.
+    pub fn portal(&mut self, result: &ProbeResult) -> io::Result<()> {
+        if self.portal.as_ref() == Some(result) {
+            return Ok(());
+        }
+        self.portal = Some(result.clone());
+        self.line("portal", None, result)
+    }
+
+    /// Write an `address_changed` line when a host re-resolved to a different address.
+    pub fn address_change(
+        &mut self,
+        host_id: &str,
+        name: &str,
+        from: IpAddr,
+        to: IpAddr,
+    ) -> io::Result<()> {
+        self.line(
+            "address_changed",
+            Some((host_id, name)),
+            json!({ "from": from, "to": to }),
+        )
+    }
+
+    /// Write a `network_changed` line when the machine's network changed.
+    pub fn network_change(&mut self, change: &NetworkChange) -> io::Result<()> {
+        self.line("network_changed", None, change)
+    }
+
+    /// Write a `config_error` line when a changed config file couldn't be reloaded.
+    pub fn config_error(&mut self, error: &str) -> io::Result<()> {
+        self.line("config_error", None, json!({ "error": error }))
+    }
+
+    /// Write a `config_warning` line for a config value that was ignored or replaced.
+    pub fn config_warning(&mut self, warning: &str) -> io::Result<()> {
+        self.line("config_warning", None, json!({ "warning": warning }))
+    }
+
+    fn line(
+        &mut self,
+        event: &str,
+        host: Option<(&str, &str)>,
+        body: impl Serialize,
+    ) -> io::Result<()> {
+        let mut obj = Map::new();
+        obj.insert(
+            "ts".into(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .into(),
+        );
+        obj.insert("event".into(), event.into());
+        if let Some((id, name)) = host {
+            obj.insert("host_id".into(), id.into());
+            obj.insert("host".into(), name.into());
+        }
+        if let Value::Object(fields) = serde_json::to_value(body)? {
+            obj.extend(fields);
+        }
+        serde_json::to_writer(&mut self.out, &obj)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod output_tests {
+    use super::*;
+    use crate::netwatch::ChangeKind;
+    use crate::stats::PingResult;
+    use std::time::{Duration, Instant};
+
+    fn lines(w: JsonlWriter<Vec<u8>>) -> Vec<Value> {
+        String::from_utf8(w.out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    fn event(update: HostUpdate) -> PingEvent {
+        PingEvent {
+            host_id: "abc".into(),
+            host_name: "Google DNS".into(),
+            update,
+        }
+    }
+
+    #[test]
+    fn ping_results_become_one_line_each() {
+        let mut w = JsonlWriter::new(Vec::new());
+        let now = Instant::now();
+        w.ping_event(&event(HostUpdate::Pinged(PingResult::Success {
+            rtt: Duration::from_micros(12_500),
+            sequence: 3,
+            timestamp: now,
+        })))
+        .unwrap();
+        w.ping_event(&event(HostUpdate::Pinged(PingResult::Error {
+            error: "NXDOMAIN".into(),
+            sequence: 4,
+            timestamp: now,
+        })))
+        .unwrap();
+        w.ping_event(&event(HostUpdate::AddressPinged {
+            ip: "2001:db8::1".parse().unwrap(),
+            result: PingResult::Timeout {
+                sequence: 5,
+                timestamp: now,
+            },
+        }))
+        .unwrap();
+        let out = lines(w);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0]["event"], "ping");
+        assert_eq!(out[0]["host_id"], "abc");
+        assert_eq!(out[0]["host"], "Google DNS");
+        assert_eq!(out[0]["result"], "success");
+        assert_eq!(out[0]["seq"], 3);
+        assert_eq!(out[0]["rtt_ms"], 12.5);
+        assert!(out[0]["ts"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(out[1]["result"], "error");
+        assert_eq!(out[1]["error"], "NXDOMAIN");
+        assert!(out[1].get("rtt_ms").is_none());
+        assert_eq!(out[2]["event"], "ping");
+        assert_eq!(out[2]["result"], "timeout");
+        assert_eq!(out[2]["ip"], "2001:db8::1");
+        assert!(out[0].get("ip").is_none());
+    }
+
+    #[test]
+    fn resolve_updates_are_tagged() {
+        let mut w = JsonlWriter::new(Vec::new());
+        w.ping_event(&event(HostUpdate::ResolveFailed("no such host".into())))
+            .unwrap();
+        w.ping_event(&event(HostUpdate::Resolved("8.8.8.8".parse().unwrap())))
+            .unwrap();
+        let out = lines(w);
+        assert_eq!(out[0]["event"], "resolve_failed");
+        assert_eq!(out[0]["error"], "no such host");
+        assert_eq!(out[1]["event"], "resolved");
+        assert_eq!(out[1]["ip"], "8.8.8.8");
+    }
+
+    #[test]
+    fn host_state_only_written_on_transition() {
+        let mut w = JsonlWriter::new(Vec::new());
+        w.host_state("abc", "x", &HostState::Up { rtt_ms: 10.0 })
+            .unwrap();
+        w.host_state("abc", "x", &HostState::Up { rtt_ms: 11.0 })
+            .unwrap();
+        w.host_state(
+            "abc",
+            "x",
+            &HostState::Down {
+                reason: "no replies".into(),
+            },
+        )
+        .unwrap();
+        w.host_state(
+            "abc",
+            "x",
+            &HostState::Down {
+                reason: "port closed".into(),
+            },
+        )
+        .unwrap();
+        let out = lines(w);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0]["state"], "up");
+        assert_eq!(out[0]["rtt_ms"], 10.0);
+        assert_eq!(out[1]["state"], "down");
+        assert_eq!(out[2]["reason"], "port closed");
+    }
+
+    #[test]
+    fn network_changes_list_what_changed() {
+        let mut w = JsonlWriter::new(Vec::new());
+        w.network_change(&NetworkChange {
+            changes: vec![ChangeKind::Address, ChangeKind::Route],
+        })
+        .unwrap();
+        let out = lines(w);
+        assert_eq!(out[0]["event"], "network_changed");
+        assert_eq!(out[0]["changes"], json!(["address", "route"]));
+        assert!(out[0].get("host_id").is_none());
+    }
+
+    #[test]
+    fn portal_only_written_on_change() {
+        let mut w = JsonlWriter::new(Vec::new());
+        w.portal(&ProbeResult::Online).unwrap();
+        w.portal(&ProbeResult::Online).unwrap();
+        w.portal(&ProbeResult::CaptivePortal {
+            url: "http://captive.apple.com".into(),
+        })
+        .unwrap();
+        let out = lines(w);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0]["event"], "portal");
+        assert_eq!(out[0]["portal"], "online");
+        assert_eq!(out[1]["portal"], "captive_portal");
+        assert_eq!(out[1]["url"], "http://captive.apple.com");
+    }
+
+    #[test]
+    fn address_changes_name_both_addresses() {
+        let mut w = JsonlWriter::new(Vec::new());
+        w.address_change(
+            "abc",
+            "Google",
+            "142.250.80.46".parse().unwrap(),
+            "2a00:1450::200e".parse().unwrap(),
+        )
+        .unwrap();
+        let out = lines(w);
+        assert_eq!(out[0]["event"], "address_changed");
+        assert_eq!(out[0]["host"], "Google");
+        assert_eq!(out[0]["from"], "142.250.80.46");
+        assert_eq!(out[0]["to"], "2a00:1450::200e");
+    }
+}
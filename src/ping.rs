@@ -1,33 +1,76 @@
 // ABOUTME: Core ping engine with async execution and DNS resolution
-// ABOUTME: Handles ICMP ping operations and maintains connection state per host
+// ABOUTME: Handles ICMP, TCP-connect, HTTP(S) and DNS probes and maintains connection state per host
 
 use anyhow::{Context, Result};
 use dns_lookup::lookup_host;
-use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use surge_ping::{Client, Config as SurgePingConfig, PingIdentifier, PingSequence};
-use tokio::sync::mpsc;
+use surge_ping::{Client, Config as SurgePingConfig, PingIdentifier, PingSequence, Pinger};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::task::JoinHandle;
 
-use crate::config::Host;
-use crate::stats::PingResult;
+use crate::config::{Family, Host, ProbeKind};
+use crate::dns::{DnsFailure, DnsOutcome, DnsProbe};
+use crate::http::{HttpFailure, HttpPhases, HttpProbe};
+use crate::stats::{PingResult, REFUSED};
 
 /// Represents a state change or measurement event emitted by the ping loop for one host.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum HostUpdate {
     Resolving,
     ResolveFailed(String),
-    // The app records that resolution succeeded; the resolved IpAddr itself is not read.
-    #[allow(dead_code)]
     Resolved(IpAddr),
+    /// Phase breakdown of the latest successful HTTP probe; precedes its `Pinged`.
+    HttpTiming(HttpPhases),
+    /// A DNS probe got a different answer set than its previous successful query.
+    AnswersChanged {
+        previous: Vec<String>,
+        current: Vec<String>,
+    },
     Pinged(PingResult),
+    /// Every address an `all_addresses` host resolved to; each round's results then
+    /// arrive as one `AddressPinged` per address, followed by the round's `Pinged`.
+    Addresses(Vec<IpAddr>),
+    /// The result of probing one of a host's addresses.
+    AddressPinged {
+        ip: IpAddr,
+        result: PingResult,
+    },
+    /// The host's loop stopped probing at the app's request.
+    Paused,
+    /// The host's loop picked up again after a pause.
+    Resumed,
+}
+
+/// Instruction from the app to a running engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineCommand {
+    /// Stop or restart probing one host, or every host when `host_id` is None.
+    SetPaused {
+        host_id: Option<String>,
+        paused: bool,
+    },
+    /// Start probing a host, restarting it if its id is already running (e.g. renamed
+    /// or given a new interval). A restarted host keeps its paused state; a new one
+    /// starts paused while everything is.
+    AddHost(Host),
+    /// Stop probing a host.
+    RemoveHost(String),
+    /// Use these global settings for hosts (re)started from now on.
+    SetPingConfig(crate::config::PingConfig),
+    /// The network changed: have every host resolve its address again right away.
+    Reresolve,
 }
 
 /// Event sent from the ping engine to the app for a single host update.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PingEvent {
     pub host_id: String,
-    // Carried with each event but not read; host rows are labeled from host_info instead.
-    #[allow(dead_code)]
     pub host_name: String,
     pub update: HostUpdate,
 }
@@ -60,12 +103,152 @@ impl Backoff {
     }
 }
 
+/// One measurement strategy for a resolved address. Built once per connection
+/// attempt and reused for every probe until the loop breaks out to re-resolve.
+enum Prober {
+    Icmp {
+        pinger: Pinger,
+        payload: Vec<u8>,
+    },
+    Tcp {
+        addr: SocketAddr,
+        timeout: Duration,
+    },
+    Http(HttpProbe),
+    /// `baseline` is the last answer set, compared against each new reply.
+    Dns {
+        probe: DnsProbe,
+        baseline: Option<Vec<String>>,
+    },
+}
+
+impl Prober {
+    /// Run one probe. Some probe types also report a detail update (sent before the result).
+    async fn probe(&mut self, sequence: u16) -> (PingResult, Option<HostUpdate>) {
+        let start_time = Instant::now();
+        match self {
+            Prober::Icmp { pinger, payload } => {
+                let result = match pinger.ping(PingSequence(sequence), payload).await {
+                    Ok((_, rtt)) => PingResult::Success {
+                        rtt,
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    Err(surge_ping::SurgeError::Timeout { .. }) => PingResult::Timeout {
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    Err(e) => PingResult::Error {
+                        error: e.to_string(),
+                        sequence,
+                        timestamp: start_time,
+                    },
+                };
+                (result, None)
+            }
+            Prober::Tcp { addr, timeout } => {
+                // The handshake time is the RTT; the stream is dropped (closed) right away.
+                let result = match tokio::time::timeout(*timeout, TcpStream::connect(*addr)).await {
+                    Ok(Ok(_stream)) => PingResult::Success {
+                        rtt: start_time.elapsed(),
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    Ok(Err(e)) => PingResult::Error {
+                        // A RST means the host answered but nothing listens: keep that
+                        // distinct from an unreachable host so it reads as "port closed".
+                        error: if e.kind() == std::io::ErrorKind::ConnectionRefused {
+                            REFUSED.to_string()
+                        } else {
+                            e.to_string()
+                        },
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    Err(_) => PingResult::Timeout {
+                        sequence,
+                        timestamp: start_time,
+                    },
+                };
+                (result, None)
+            }
+            Prober::Http(http) => match http.run().await {
+                Ok(phases) => (
+                    PingResult::Success {
+                        rtt: phases.total,
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    Some(HostUpdate::HttpTiming(phases)),
+                ),
+                Err(HttpFailure::Timeout) => (
+                    PingResult::Timeout {
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    None,
+                ),
+                Err(HttpFailure::Failed(error)) => (
+                    PingResult::Error {
+                        error,
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    None,
+                ),
+            },
+            Prober::Dns { probe, baseline } => match probe.run().await {
+                Ok((rtt, DnsOutcome::Answers(current))) => {
+                    let changed = match baseline.replace(current.clone()) {
+                        Some(previous) if previous != current => {
+                            Some(HostUpdate::AnswersChanged { previous, current })
+                        }
+                        _ => None,
+                    };
+                    (
+                        PingResult::Success {
+                            rtt,
+                            sequence,
+                            timestamp: start_time,
+                        },
+                        changed,
+                    )
+                }
+                // NXDOMAIN, SERVFAIL etc. arrive as errors labeled with the rcode.
+                Ok((_, DnsOutcome::Rcode(error))) | Err(DnsFailure::Failed(error)) => (
+                    PingResult::Error {
+                        error,
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    None,
+                ),
+                Err(DnsFailure::Timeout) => (
+                    PingResult::Timeout {
+                        sequence,
+                        timestamp: start_time,
+                    },
+                    None,
+                ),
+            },
+        }
+    }
+}
+
 pub struct PingEngine {
     hosts: Vec<Host>,
     event_tx: mpsc::Sender<PingEvent>,
     ping_config: crate::config::PingConfig,
 }
 
+/// A running host loop, the switch that pauses it, and the nudge that sends it back
+/// to resolving.
+struct HostTask {
+    handle: JoinHandle<()>,
+    pause_tx: watch::Sender<bool>,
+    reresolve: Arc<Notify>,
+}
+
 impl PingEngine {
     pub fn new(
         hosts: Vec<Host>,
@@ -79,31 +262,97 @@ impl PingEngine {
         }
     }
 
-    pub async fn start(&self) -> Result<()> {
-        let mut handles = Vec::new();
-        for host in &self.hosts {
-            if !host.enabled {
-                continue;
-            }
-            let host_clone = host.clone();
-            let event_tx = self.event_tx.clone();
-            let ping_config = self.ping_config.clone();
-            handles.push(tokio::spawn(async move {
-                Self::ping_host_loop(host_clone, event_tx, ping_config).await
-            }));
+    /// Spawn a loop per enabled host, then serve `commands` until the app drops its
+    /// sender. After that only the host loops hold the event channel, so with no hosts
+    /// the app sees it close.
+    pub async fn start(mut self, mut commands: mpsc::Receiver<EngineCommand>) -> Result<()> {
+        let mut tasks: HashMap<String, HostTask> = HashMap::new();
+        for host in self.hosts.iter().filter(|h| h.enabled) {
+            tasks.insert(Self::host_id(host), self.spawn_host(host.clone(), false));
         }
-        for handle in handles {
-            let _ = handle.await; // task panics already restore the terminal via panic hook
+        // Whether everything was last paused (or resumed) at once, so hosts added
+        // meanwhile start out paused too. Resuming one host doesn't change it.
+        let mut all_paused = false;
+
+        while let Some(command) = commands.recv().await {
+            match command {
+                EngineCommand::SetPaused { host_id, paused } => {
+                    if host_id.is_none() {
+                        all_paused = paused;
+                    }
+                    for (id, task) in &tasks {
+                        if host_id.as_ref().is_none_or(|h| h == id) {
+                            task.pause_tx.send_replace(paused);
+                        }
+                    }
+                }
+                EngineCommand::AddHost(host) => {
+                    let id = Self::host_id(&host);
+                    let paused = match tasks.remove(&id) {
+                        Some(old) => {
+                            old.handle.abort();
+                            *old.pause_tx.borrow()
+                        }
+                        None => all_paused,
+                    };
+                    tasks.insert(id, self.spawn_host(host, paused));
+                }
+                EngineCommand::RemoveHost(id) => {
+                    if let Some(old) = tasks.remove(&id) {
+                        old.handle.abort();
+                    }
+                }
+                EngineCommand::SetPingConfig(ping_config) => self.ping_config = ping_config,
+                EngineCommand::Reresolve => {
+                    for task in tasks.values() {
+                        task.reresolve.notify_one();
+                    }
+                }
+            }
         }
         Ok(())
     }
 
+    fn spawn_host(&self, host: Host, paused: bool) -> HostTask {
+        let (pause_tx, pause_rx) = watch::channel(paused);
+        let reresolve = Arc::new(Notify::new());
+        let event_tx = self.event_tx.clone();
+        let ping_config = self.ping_config.clone();
+        let nudge = reresolve.clone();
+        // Task panics already restore the terminal via the panic hook.
+        let handle = tokio::spawn(async move {
+            Self::ping_host_loop(host, event_tx, ping_config, pause_rx, nudge).await
+        });
+        HostTask {
+            handle,
+            pause_tx,
+            reresolve,
+        }
+    }
+
+    /// If the host is paused, report it and wait for the resume. Returns true if it waited.
+    async fn hold_while_paused(
+        paused: &mut watch::Receiver<bool>,
+        send: &impl Fn(HostUpdate),
+    ) -> bool {
+        if !*paused.borrow_and_update() {
+            return false;
+        }
+        send(HostUpdate::Paused);
+        // A dropped sender means the engine is gone; carry on rather than hang.
+        let _ = paused.wait_for(|p| !p).await;
+        send(HostUpdate::Resumed);
+        true
+    }
+
     async fn ping_host_loop(
         host: Host,
         event_tx: mpsc::Sender<PingEvent>,
         ping_config: crate::config::PingConfig,
+        mut paused: watch::Receiver<bool>,
+        reresolve: Arc<Notify>,
     ) {
-        let host_id = Self::generate_host_id(&host.address);
+        let host_id = Self::host_id(&host);
         let interval = Duration::from_secs_f64(host.interval.unwrap_or(ping_config.interval));
         let timeout = Duration::from_secs_f64(ping_config.timeout);
         let payload = vec![0u8; ping_config.packet_size as usize];
@@ -118,88 +367,116 @@ impl PingEngine {
 
         let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
         let mut sequence = 0u16;
+        // A DNS host's last answer set (per resolver address) survives re-resolution so a
+        // change across an outage is still reported.
+        let mut dns_baselines: HashMap<IpAddr, Vec<String>> = HashMap::new();
 
         loop {
             // (Re)resolve with backoff until success.
             send(HostUpdate::Resolving);
-            let ip_addr = loop {
-                match Self::resolve_hostname(&host.address).await {
-                    Ok(ip) => {
+            let addrs = loop {
+                Self::hold_while_paused(&mut paused, &send).await;
+                match Self::resolve_addresses(&Self::resolve_name(&host), host.family).await {
+                    Ok(mut addrs) => {
+                        if !host.all_addresses {
+                            addrs.truncate(1);
+                        }
                         backoff.reset();
-                        break ip;
+                        break addrs;
                     }
                     Err(e) => {
                         send(HostUpdate::ResolveFailed(e.to_string()));
-                        tokio::time::sleep(backoff.next()).await;
+                        // A network change is the likeliest fix; try again as soon as one lands.
+                        // A pause holds straight away rather than after the backoff.
+                        tokio::select! {
+                            _ = tokio::time::sleep(backoff.next()) => {}
+                            _ = reresolve.notified() => backoff.reset(),
+                            Ok(()) = paused.changed() => {}
+                        }
                     }
                 }
             };
-            send(HostUpdate::Resolved(ip_addr));
-
-            // Build a client; if sockets are denied even after surge-ping's
-            // DGRAM->RAW fallback, report it and back off (don't spin).
-            let client = match Client::new(&SurgePingConfig::default()) {
-                Ok(c) => c,
-                Err(e) => {
-                    send(HostUpdate::ResolveFailed(format!(
-                        "icmp socket denied ({e}); on Linux set net.ipv4.ping_group_range or run elevated"
-                    )));
-                    tokio::time::sleep(backoff.next()).await;
-                    continue;
+            send(HostUpdate::Resolved(addrs[0]));
+            if host.all_addresses {
+                send(HostUpdate::Addresses(addrs.clone()));
+            }
+
+            let mut probers = Vec::with_capacity(addrs.len());
+            for &ip in &addrs {
+                let baseline = dns_baselines.remove(&ip);
+                match Self::prober(&host, ip, timeout, &payload, baseline).await {
+                    Ok(prober) => probers.push((ip, prober)),
+                    Err(e) => {
+                        // Denied sockets and bad URLs or patterns won't fix themselves;
+                        // report and back off (don't spin).
+                        send(HostUpdate::ResolveFailed(e));
+                        break;
+                    }
                 }
-            };
+            }
+            if probers.len() < addrs.len() {
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff.next()) => {}
+                    Ok(()) = paused.changed() => {}
+                }
+                continue;
+            }
 
-            // Ping at the configured interval. After several consecutive failures,
-            // break out to re-resolve (handles IP changes / reconnects).
-            // Create the pinger ONCE and reuse it — this is surge-ping's intended use
-            // and avoids a redundant double-timeout. It enforces `pinger.timeout` itself
-            // and returns Err(SurgeError::Timeout) when a reply does not arrive in time.
-            let mut pinger = client.pinger(ip_addr, PingIdentifier(0)).await;
-            pinger.timeout(timeout);
+            // Probe at the configured interval, every address at once. After several
+            // rounds in which nothing answered, or straight away when the network
+            // changes, break out to re-resolve (handles IP changes / reconnects).
             let mut interval_timer = tokio::time::interval(interval);
             let mut consecutive_failures = 0u32;
             loop {
-                interval_timer.tick().await;
-                let start_time = Instant::now();
+                tokio::select! {
+                    _ = interval_timer.tick() => {}
+                    _ = reresolve.notified() => break,
+                }
+                // Nothing is sent while paused, so the pause never shows up as loss.
+                // On resume, probe straight away rather than waiting out an interval.
+                if Self::hold_while_paused(&mut paused, &send).await {
+                    interval_timer.reset_immediately();
+                    continue;
+                }
 
-                let result = match pinger.ping(PingSequence(sequence), &payload).await {
-                    Ok((_, rtt)) => {
-                        consecutive_failures = 0;
-                        PingResult::Success {
-                            rtt,
-                            sequence,
-                            timestamp: start_time,
-                        }
+                let results = futures::future::join_all(
+                    probers.iter_mut().map(|(_, prober)| prober.probe(sequence)),
+                )
+                .await;
+                let mut updates = Vec::with_capacity(results.len() + 1);
+                let mut round = Vec::with_capacity(results.len());
+                for ((ip, _), (result, detail)) in probers.iter().zip(results) {
+                    updates.extend(detail);
+                    if host.all_addresses {
+                        updates.push(HostUpdate::AddressPinged {
+                            ip: *ip,
+                            result: result.clone(),
+                        });
                     }
-                    Err(surge_ping::SurgeError::Timeout { .. }) => {
-                        consecutive_failures += 1;
-                        PingResult::Timeout {
-                            sequence,
-                            timestamp: start_time,
-                        }
-                    }
-                    Err(e) => {
-                        consecutive_failures += 1;
-                        PingResult::Error {
-                            error: e.to_string(),
-                            sequence,
-                            timestamp: start_time,
+                    round.push(result);
+                }
+                let result = round_result(round);
+                let answered = result.is_success();
+                updates.push(HostUpdate::Pinged(result));
+                for update in updates {
+                    if event_tx
+                        .try_send(PingEvent {
+                            host_id: host_id.clone(),
+                            host_name: host.name.clone(),
+                            update,
+                        })
+                        .is_err()
+                    {
+                        // Channel full is fine (UI drops a frame); channel closed = exit.
+                        if event_tx.is_closed() {
+                            return;
                         }
                     }
-                };
-
-                if event_tx
-                    .try_send(PingEvent {
-                        host_id: host_id.clone(),
-                        host_name: host.name.clone(),
-                        update: HostUpdate::Pinged(result),
-                    })
-                    .is_err()
-                {
-                    // Channel full is fine (UI drops a frame); channel closed = exit.
-                    if event_tx.is_closed() {
-                        return;
-                    }
+                }
+                if answered {
+                    consecutive_failures = 0;
+                } else {
+                    consecutive_failures += 1;
                 }
 
                 sequence = sequence.wrapping_add(1);
@@ -207,13 +484,108 @@ impl PingEngine {
                     break; // re-resolve
                 }
             }
+            for (ip, prober) in probers {
+                if let Prober::Dns {
+                    baseline: Some(baseline),
+                    ..
+                } = prober
+                {
+                    dns_baselines.insert(ip, baseline);
+                }
+            }
+        }
+    }
+
+    /// Build the prober for one resolved address of a host. Fails with the reason
+    /// when it can't be probed at all.
+    async fn prober(
+        host: &Host,
+        ip: IpAddr,
+        timeout: Duration,
+        payload: &[u8],
+        baseline: Option<Vec<String>>,
+    ) -> Result<Prober, String> {
+        Ok(match host.probe {
+            ProbeKind::Icmp => {
+                // Build a client; sockets may be denied even after surge-ping's
+                // DGRAM->RAW fallback.
+                let kind = if ip.is_ipv6() {
+                    surge_ping::ICMP::V6
+                } else {
+                    surge_ping::ICMP::V4
+                };
+                let client = Client::new(&SurgePingConfig::builder().kind(kind).build()).map_err(|e| {
+                    format!("icmp socket denied ({e}); on Linux set net.ipv4.ping_group_range or run elevated")
+                })?;
+                // Create the pinger ONCE and reuse it — this is surge-ping's intended use
+                // and avoids a redundant double-timeout. It enforces `pinger.timeout` itself
+                // and returns Err(SurgeError::Timeout) when a reply does not arrive in time.
+                let mut pinger = client.pinger(ip, PingIdentifier(0)).await;
+                pinger.timeout(timeout);
+                Prober::Icmp {
+                    pinger,
+                    payload: payload.to_vec(),
+                }
+            }
+            ProbeKind::Tcp => Prober::Tcp {
+                // validate() guarantees TCP hosts carry a port.
+                addr: SocketAddr::new(ip, host.port.unwrap_or(80)),
+                timeout,
+            },
+            ProbeKind::Http => {
+                let probe =
+                    HttpProbe::new(&host.address, host.expect.as_deref(), host.family, timeout)
+                        .map_err(|e| e.to_string())?;
+                // With one address per row there's no lookup to time; otherwise DNS is
+                // one of the phases, looked up on every request.
+                Prober::Http(if host.all_addresses {
+                    probe.pinned(ip)
+                } else {
+                    probe
+                })
+            }
+            ProbeKind::Dns => Prober::Dns {
+                // validate() guarantees DNS hosts carry a port and a query.
+                probe: DnsProbe::new(
+                    SocketAddr::new(ip, host.port.unwrap_or(53)),
+                    host.query.as_deref().unwrap_or("example.com"),
+                    host.record_type,
+                    timeout,
+                ),
+                baseline,
+            },
+        })
+    }
+
+    /// The name to resolve for a host: the URL's host for HTTP probes, else the address.
+    fn resolve_name(host: &Host) -> String {
+        match host.probe {
+            ProbeKind::Http => crate::http::parse_url(&host.address)
+                .map(|t| t.host)
+                .unwrap_or_else(|| host.address.clone()),
+            _ => host.address.clone(),
         }
     }
 
-    async fn resolve_hostname(hostname: &str) -> Result<IpAddr> {
+    /// Look up a host's address, the first of `family` when one is given.
+    pub async fn resolve_hostname(hostname: &str, family: Option<Family>) -> Result<IpAddr> {
+        Ok(Self::resolve_addresses(hostname, family).await?[0])
+    }
+
+    /// Every address a host resolves to (of `family` when one is given), in the
+    /// resolver's order without repeats. Never empty.
+    pub async fn resolve_addresses(hostname: &str, family: Option<Family>) -> Result<Vec<IpAddr>> {
+        let fits = |ip: &IpAddr| family.is_none_or(|f| f.fits(ip));
+        let missing = || match family {
+            Some(f) => format!("No {} addresses found for {hostname}", f.label()),
+            None => format!("No IP addresses found for {hostname}"),
+        };
         // IP literals need no DNS; resolve them synchronously.
         if let Ok(ip) = hostname.parse::<IpAddr>() {
-            return Ok(ip);
+            return Some(ip)
+                .filter(fits)
+                .map(|ip| vec![ip])
+                .with_context(missing);
         }
 
         // dns_lookup::lookup_host is blocking; run it on the blocking thread pool
@@ -224,9 +596,39 @@ impl PingEngine {
             .context("DNS resolver task panicked")?
             .with_context(|| format!("DNS lookup failed for {hostname}"))?;
 
-        ips.into_iter()
-            .next()
-            .with_context(|| format!("No IP addresses found for {hostname}"))
+        let mut found: Vec<IpAddr> = Vec::new();
+        for ip in ips.into_iter().filter(fits) {
+            if !found.contains(&ip) {
+                found.push(ip);
+            }
+        }
+        if found.is_empty() {
+            anyhow::bail!(missing());
+        }
+        Ok(found)
+    }
+
+    /// Stable id for a host. ICMP hosts key on the address alone; other probe types
+    /// also key on the probe and port so the same address can be monitored both ways.
+    /// A single-family probe is a series of its own, apart from the unpinned one.
+    pub fn host_id(host: &Host) -> String {
+        let key = match host.probe {
+            // HTTP addresses are full URLs, already distinct from any bare host.
+            ProbeKind::Icmp | ProbeKind::Http => host.address.clone(),
+            ProbeKind::Tcp => format!("tcp://{}:{}", host.address, host.port.unwrap_or(80)),
+            ProbeKind::Dns => format!(
+                "dns://{}:{}/{}/{:?}",
+                host.address,
+                host.port.unwrap_or(53),
+                host.query.as_deref().unwrap_or(""),
+                host.record_type
+            ),
+        };
+        match host.family {
+            Some(Family::V4) => Self::generate_host_id(&format!("{key}#v4")),
+            Some(Family::V6) => Self::generate_host_id(&format!("{key}#v6")),
+            _ => Self::generate_host_id(&key),
+        }
     }
 
     fn generate_host_id(address: &str) -> String {
@@ -236,14 +638,21 @@ impl PingEngine {
             uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_DNS, address.as_bytes())
         )
     }
+}
 
-    pub fn get_host_info(&self) -> Vec<(String, String)> {
-        self.hosts
-            .iter()
-            .filter(|h| h.enabled)
-            .map(|h| (Self::generate_host_id(&h.address), h.name.clone()))
-            .collect()
-    }
+/// What a round of probes says about the host: the fastest answer from any of its
+/// addresses, or the first failure when none answered.
+fn round_result(round: Vec<PingResult>) -> PingResult {
+    let fastest = round
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| r.rtt().map(|rtt| (rtt, i)))
+        .min()
+        .map_or(0, |(_, i)| i);
+    round
+        .into_iter()
+        .nth(fastest)
+        .expect("a round probes at least one address")
 }
 
 #[cfg(test)]
@@ -257,8 +666,7 @@ mod tests {
         let hosts = vec![Host {
             name: "localhost".into(),
             address: "127.0.0.1".into(),
-            enabled: true,
-            interval: None,
+            ..Host::default()
         }];
         let ping_config = PingConfig {
             interval: 1.0,
@@ -266,6 +674,8 @@ mod tests {
             history_size: 100,
             packet_size: 64,
             portal_check_url: "http://captive.apple.com".to_string(),
+            reset_on_network_change: false,
+            min_mos: None,
         };
         let (tx, _rx) = mpsc::channel(64);
         let _engine = PingEngine::new(hosts, ping_config, tx);
@@ -273,13 +683,24 @@ mod tests {
 
     #[tokio::test]
     async fn test_ip_parse_fast_path() {
-        assert!(PingEngine::resolve_hostname("127.0.0.1").await.is_ok());
+        assert!(PingEngine::resolve_hostname("127.0.0.1", None)
+            .await
+            .is_ok());
+        let v6 = PingEngine::resolve_hostname("::1", Some(Family::V6)).await;
+        assert_eq!(v6.unwrap(), "::1".parse::<IpAddr>().unwrap());
+        let err = PingEngine::resolve_hostname("127.0.0.1", Some(Family::V6)).await;
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "No IPv6 addresses found for 127.0.0.1"
+        );
     }
 
     #[tokio::test]
     #[ignore = "requires live DNS; run with --ignored"]
     async fn test_hostname_resolution_live() {
-        assert!(PingEngine::resolve_hostname("localhost").await.is_ok());
+        assert!(PingEngine::resolve_hostname("localhost", None)
+            .await
+            .is_ok());
     }
 
     #[test]
@@ -295,6 +716,162 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn tcp_probe_times_handshake_and_reports_refused() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        let mut prober = Prober::Tcp {
+            addr: open,
+            timeout: Duration::from_secs(2),
+        };
+        assert!(prober.probe(0).await.0.is_success());
+
+        // Free the port so nothing listens there: the connect must come back refused.
+        drop(listener);
+        let mut prober = Prober::Tcp {
+            addr: open,
+            timeout: Duration::from_secs(2),
+        };
+        assert!(prober.probe(1).await.0.is_refused());
+    }
+
+    #[tokio::test]
+    async fn engine_adds_and_removes_hosts_on_command() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = Host {
+            name: "local".into(),
+            address: "127.0.0.1".into(),
+            probe: ProbeKind::Tcp,
+            port: Some(listener.local_addr().unwrap().port()),
+            interval: Some(0.1),
+            ..Host::default()
+        };
+        let id = PingEngine::host_id(&host);
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (cmd_tx, cmd_rx) = mpsc::channel(4);
+        let engine = PingEngine::new(vec![], crate::config::Config::default().ping, event_tx);
+        tokio::spawn(engine.start(cmd_rx));
+
+        cmd_tx.send(EngineCommand::AddHost(host)).await.unwrap();
+        let pinged = async {
+            while let Some(e) = event_rx.recv().await {
+                if matches!(e.update, HostUpdate::Pinged(_)) {
+                    return e.host_id;
+                }
+            }
+            String::new()
+        };
+        let got = tokio::time::timeout(Duration::from_secs(5), pinged).await;
+        assert_eq!(got.unwrap(), id);
+
+        // With the host stopped and the engine told to finish, the event stream ends.
+        cmd_tx.send(EngineCommand::RemoveHost(id)).await.unwrap();
+        drop(cmd_tx);
+        let drained = async { while event_rx.recv().await.is_some() {} };
+        assert!(tokio::time::timeout(Duration::from_secs(5), drained)
+            .await
+            .is_ok());
+    }
+
+    /// The first update `host` sends other than `Resolving`.
+    async fn first_update(
+        event_rx: &mut mpsc::Receiver<PingEvent>,
+        host: &Host,
+    ) -> Option<HostUpdate> {
+        let id = PingEngine::host_id(host);
+        let first = async {
+            loop {
+                let event = event_rx.recv().await?;
+                match event.update {
+                    _ if event.host_id != id => continue,
+                    HostUpdate::Resolving => continue,
+                    other => return Some(other),
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), first)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    fn local_tcp(address: &str) -> Host {
+        Host {
+            name: address.into(),
+            address: address.into(),
+            probe: ProbeKind::Tcp,
+            port: Some(9),
+            interval: Some(0.1),
+            ..Host::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn hosts_added_while_paused_start_paused() {
+        let host = local_tcp("127.0.0.1");
+        let (event_tx, mut event_rx) = mpsc::channel(64);
+        let (cmd_tx, cmd_rx) = mpsc::channel(4);
+        let engine = PingEngine::new(vec![], crate::config::Config::default().ping, event_tx);
+        tokio::spawn(engine.start(cmd_rx));
+
+        let pause_all = EngineCommand::SetPaused {
+            host_id: None,
+            paused: true,
+        };
+        cmd_tx.send(pause_all).await.unwrap();
+        cmd_tx
+            .send(EngineCommand::AddHost(host.clone()))
+            .await
+            .unwrap();
+        let got = first_update(&mut event_rx, &host).await;
+        assert!(matches!(got, Some(HostUpdate::Paused)));
+    }
+
+    #[tokio::test]
+    async fn resuming_one_host_leaves_new_hosts_paused() {
+        let (first, added) = (local_tcp("127.0.0.1"), local_tcp("127.0.0.2"));
+        let (event_tx, mut event_rx) = mpsc::channel(256);
+        let (cmd_tx, cmd_rx) = mpsc::channel(4);
+        let engine = PingEngine::new(
+            vec![first.clone()],
+            crate::config::Config::default().ping,
+            event_tx,
+        );
+        tokio::spawn(engine.start(cmd_rx));
+
+        for (host_id, paused) in [(None, true), (Some(PingEngine::host_id(&first)), false)] {
+            cmd_tx
+                .send(EngineCommand::SetPaused { host_id, paused })
+                .await
+                .unwrap();
+        }
+        cmd_tx
+            .send(EngineCommand::AddHost(added.clone()))
+            .await
+            .unwrap();
+        let got = first_update(&mut event_rx, &added).await;
+        assert!(matches!(got, Some(HostUpdate::Paused)));
+    }
+
+    #[test]
+    fn tcp_hosts_get_their_own_id() {
+        let icmp = Host {
+            address: "example.com".into(),
+            ..Host::default()
+        };
+        let tcp = Host {
+            address: "example.com".into(),
+            probe: ProbeKind::Tcp,
+            port: Some(443),
+            ..Host::default()
+        };
+        assert_eq!(
+            PingEngine::host_id(&icmp),
+            PingEngine::generate_host_id("example.com")
+        );
+        assert_ne!(PingEngine::host_id(&icmp), PingEngine::host_id(&tcp));
+    }
+
     #[test]
     fn backoff_doubles_and_caps_and_resets() {
         let mut b = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
@@ -308,4 +885,20 @@ mod tests {
         b.reset();
         assert_eq!(b.next(), Duration::from_secs(1));
     }
+
+    #[test]
+    fn a_round_counts_once_for_its_host() {
+        let ok = |ms| PingResult::Success {
+            rtt: Duration::from_millis(ms),
+            sequence: 7,
+            timestamp: Instant::now(),
+        };
+        let lost = || PingResult::Timeout {
+            sequence: 7,
+            timestamp: Instant::now(),
+        };
+        let best = round_result(vec![lost(), ok(80), ok(10)]);
+        assert_eq!(best.rtt(), Some(Duration::from_millis(10)));
+        assert!(!round_result(vec![lost(), lost()]).is_success());
+    }
 }
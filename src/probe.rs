@@ -1,11 +1,13 @@
 // ABOUTME: Captive-portal connectivity probe over plain HTTP.
 // ABOUTME: Classifies the network as Online, CaptivePortal, or Offline.
 
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "portal", rename_all = "snake_case")]
 pub enum ProbeResult {
     Online,
     CaptivePortal { url: String },
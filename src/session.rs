@@ -0,0 +1,606 @@
+// ABOUTME: Session recordings (.pplog): the engine and portal event stream with timestamps.
+// ABOUTME: Records a live run to JSON lines and plays one back through the app's channels.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::config::{Family, ProbeKind};
+use crate::netwatch::NetworkChange;
+use crate::ping::PingEvent;
+use crate::probe::ProbeResult;
+
+/// Bumped whenever the line format changes incompatibly.
+const FORMAT_VERSION: u32 = 1;
+
+/// First line of a recording: what was being monitored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub started: DateTime<Utc>,
+    pub hosts: Vec<SessionHost>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionHost {
+    pub id: String,
+    pub name: String,
+    pub probe: ProbeKind,
+    /// Host list section; recordings from before groups existed have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Ids of the recorded hosts this one depends on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// For one half of a dual-stack host: that host's name, and which half this is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<(String, Family)>,
+}
+
+/// Every later line: one event and its offset from the start of the recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEvent {
+    Ping(PingEvent),
+    Portal(ProbeResult),
+    NetworkChange(NetworkChange),
+    /// The host list changed (a host added, removed or renamed): all of it, as now.
+    Hosts(Vec<SessionHost>),
+}
+
+/// A recording read back from disk.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub header: Header,
+    pub entries: Vec<Entry>,
+}
+
+/// Appends events to a recording as they arrive. Lines are flushed as written so a
+/// crash or kill still leaves everything up to that point playable.
+pub struct Recorder<W: Write> {
+    out: W,
+    started: Instant,
+    // Host list as last recorded, header or event.
+    hosts: Vec<SessionHost>,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create(path: &Path, hosts: Vec<SessionHost>) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording: {}", path.display()))?;
+        Self::new(BufWriter::new(file), hosts)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(out: W, hosts: Vec<SessionHost>) -> Result<Self> {
+        let mut recorder = Self {
+            out,
+            started: Instant::now(),
+            hosts: hosts.clone(),
+        };
+        let header = Header {
+            version: FORMAT_VERSION,
+            started: Utc::now(),
+            hosts,
+        };
+        recorder.line(&header)?;
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, event: SessionEvent) -> Result<()> {
+        let entry = Entry {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+        self.line(&entry)
+    }
+
+    /// Record the host list, if it's changed since it was last recorded.
+    pub fn set_hosts(&mut self, hosts: Vec<SessionHost>) -> Result<()> {
+        if hosts == self.hosts {
+            return Ok(());
+        }
+        self.hosts = hosts.clone();
+        self.record(SessionEvent::Hosts(hosts))
+    }
+
+    fn line(&mut self, value: &impl Serialize) -> Result<()> {
+        serde_json::to_writer(&mut self.out, value)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open recording: {}", path.display()))?;
+        Self::read(BufReader::new(file))
+            .with_context(|| format!("Failed to read recording: {}", path.display()))
+    }
+
+    /// Parse a recording. A truncated last line (the recorder was killed mid-write)
+    /// is dropped; anything else malformed is an error naming the line.
+    pub fn read(input: impl BufRead) -> Result<Self> {
+        let mut lines = input.lines();
+        let header: Header = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).context("line 1: not a recording header")?,
+            None => bail!("empty recording"),
+        };
+        if header.version != FORMAT_VERSION {
+            bail!("unsupported recording version {}", header.version);
+        }
+        let lines: Vec<String> = lines.collect::<std::io::Result<_>>()?;
+        let mut entries = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<Entry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) if i + 1 == lines.len() => break,
+                Err(e) => bail!("line {}: {e}", i + 2),
+            }
+        }
+        Ok(Self { header, entries })
+    }
+}
+
+/// Steering for a replay in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayControl {
+    /// Stop the playback clock, or start it again.
+    TogglePause,
+    /// Pause, and play just the next event.
+    Step,
+    /// Move the playback clock by this many milliseconds of recording, back when negative.
+    Seek(i64),
+}
+
+/// What a replay feeds the app, in the order it must be applied.
+#[derive(Debug, Clone)]
+pub enum Playback {
+    /// The next recorded event.
+    Event(Entry),
+    /// Playback went back before events already played: forget everything they did.
+    /// The recording is played again from its first host list up to where it was sent.
+    Restart,
+    /// The playback clock was paused, resumed, stepped or moved; it now reads `at_ms`.
+    At { at_ms: u64, paused: bool },
+    /// Every recorded event has been played.
+    Ended,
+}
+
+/// Where in the recording playback is. While running it advances at `speed` times
+/// real time from `at_ms`, counted from `origin`; while paused it stays at `at_ms`.
+struct Clock {
+    origin: tokio::time::Instant,
+    at_ms: u64,
+    speed: f64,
+    paused: bool,
+}
+
+impl Clock {
+    fn now_ms(&self) -> u64 {
+        if self.paused {
+            self.at_ms
+        } else {
+            self.at_ms + (self.origin.elapsed().as_secs_f64() * 1000.0 * self.speed) as u64
+        }
+    }
+
+    fn set(&mut self, at_ms: u64, paused: bool) {
+        self.origin = tokio::time::Instant::now();
+        self.at_ms = at_ms;
+        self.paused = paused;
+    }
+
+    /// When an event recorded at `at_ms` is due, at the current speed.
+    fn due(&self, at_ms: u64) -> tokio::time::Instant {
+        let ms = at_ms.saturating_sub(self.at_ms) as f64;
+        self.origin + Duration::from_secs_f64(ms / 1000.0 / self.speed)
+    }
+}
+
+/// Play a recording into `out`, keeping the events' original spacing divided by
+/// `speed`, and follow `control` to pause, step and seek. Once everything has played
+/// it waits for a seek back; it returns when `control` closes at the end of the
+/// recording, or when the app goes away. Dropping `out` is how the app learns that
+/// playback is over.
+pub async fn replay(
+    session: Session,
+    speed: f64,
+    out: mpsc::Sender<Playback>,
+    mut control: mpsc::Receiver<ReplayControl>,
+) {
+    let Session { header, entries } = session;
+    let total_ms = entries.last().map_or(0, |e| e.at_ms);
+    let mut clock = Clock {
+        origin: tokio::time::Instant::now(),
+        at_ms: 0,
+        speed,
+        paused: false,
+    };
+    // Index of the next entry to play, and whether the end has been announced.
+    let mut next = 0;
+    let mut ended = false;
+    let mut steered = true;
+    loop {
+        if next == entries.len() {
+            if !ended {
+                ended = true;
+                if out.send(Playback::Ended).await.is_err() {
+                    return;
+                }
+            }
+            if !steered {
+                return;
+            }
+        }
+        let playing = !clock.paused && next < entries.len();
+        let due = entries
+            .get(next)
+            .map_or(clock.origin, |e| clock.due(e.at_ms));
+        tokio::select! {
+            _ = tokio::time::sleep_until(due), if playing => {
+                if out.send(Playback::Event(entries[next].clone())).await.is_err() {
+                    return;
+                }
+                next += 1;
+            }
+            command = control.recv(), if steered => {
+                // The clock can run past the next event by a moment before it's sent.
+                let limit = entries.get(next).map_or(total_ms, |e| e.at_ms);
+                let now = clock.now_ms().min(limit);
+                match command {
+                    // Nobody is left to resume a pause, so play on to the end.
+                    None => {
+                        steered = false;
+                        clock.set(now, false);
+                        continue;
+                    }
+                    Some(ReplayControl::TogglePause) => clock.set(now, !clock.paused),
+                    Some(ReplayControl::Step) => {
+                        clock.set(now, true);
+                        if let Some(entry) = entries.get(next) {
+                            if out.send(Playback::Event(entry.clone())).await.is_err() {
+                                return;
+                            }
+                            clock.set(entry.at_ms, true);
+                            next += 1;
+                        }
+                    }
+                    Some(ReplayControl::Seek(offset_ms)) => {
+                        let target = (now as i64 + offset_ms).clamp(0, total_ms as i64) as u64;
+                        if next > 0 && entries[next - 1].at_ms > target {
+                            let first = Entry {
+                                at_ms: 0,
+                                event: SessionEvent::Hosts(header.hosts.clone()),
+                            };
+                            for playback in [Playback::Restart, Playback::Event(first)] {
+                                if out.send(playback).await.is_err() {
+                                    return;
+                                }
+                            }
+                            next = 0;
+                        }
+                        while let Some(entry) = entries.get(next).filter(|e| e.at_ms <= target) {
+                            if out.send(Playback::Event(entry.clone())).await.is_err() {
+                                return;
+                            }
+                            next += 1;
+                        }
+                        ended &= next == entries.len();
+                        clock.set(target, clock.paused);
+                    }
+                }
+                let at = Playback::At { at_ms: clock.at_ms, paused: clock.paused };
+                if out.send(at).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Parse a playback speed such as `10x`, `0.5x` or `2`.
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    let n: f64 = s
+        .trim()
+        .trim_end_matches(['x', 'X'])
+        .parse()
+        .map_err(|_| format!("invalid speed '{s}' (expected e.g. 10x)"))?;
+    if !n.is_finite() || n <= 0.0 {
+        return Err(format!("speed must be positive, got '{s}'"));
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod session_tests {
+    use super::*;
+    use crate::ping::HostUpdate;
+    use crate::stats::{PingResult, PingStats};
+    use crate::status::{self, HostState};
+
+    fn host() -> SessionHost {
+        SessionHost {
+            id: "h1".into(),
+            name: "VPN".into(),
+            probe: ProbeKind::Icmp,
+            group: Some("VPN".into()),
+            depends_on: Vec::new(),
+            stack: None,
+        }
+    }
+
+    fn pinged(result: PingResult) -> SessionEvent {
+        SessionEvent::Ping(PingEvent {
+            host_id: "h1".into(),
+            host_name: "VPN".into(),
+            update: HostUpdate::Pinged(result),
+        })
+    }
+
+    fn recording() -> Vec<u8> {
+        let mut rec = Recorder::new(Vec::new(), vec![host()]).unwrap();
+        rec.record(SessionEvent::Ping(PingEvent {
+            host_id: "h1".into(),
+            host_name: "VPN".into(),
+            update: HostUpdate::Resolved("10.0.0.1".parse().unwrap()),
+        }))
+        .unwrap();
+        for seq in 0..3 {
+            rec.record(pinged(PingResult::Success {
+                rtt: Duration::from_micros(12_500),
+                sequence: seq,
+                timestamp: Instant::now(),
+            }))
+            .unwrap();
+        }
+        rec.record(SessionEvent::Portal(ProbeResult::Online))
+            .unwrap();
+        rec.out
+    }
+
+    #[test]
+    fn recording_round_trips() {
+        let session = Session::read(recording().as_slice()).unwrap();
+        assert_eq!(session.header.hosts, vec![host()]);
+        assert_eq!(session.entries.len(), 5);
+        match &session.entries[1].event {
+            SessionEvent::Ping(PingEvent {
+                update: HostUpdate::Pinged(r),
+                ..
+            }) => assert_eq!(r.rtt(), Some(Duration::from_micros(12_500))),
+            other => panic!("unexpected entry {other:?}"),
+        }
+        assert!(matches!(
+            session.entries[4].event,
+            SessionEvent::Portal(ProbeResult::Online)
+        ));
+    }
+
+    #[test]
+    fn truncated_last_line_is_dropped_but_corruption_is_not() {
+        let mut bytes = recording();
+        bytes.extend_from_slice(b"{\"at_ms\":9,\"pi");
+        assert_eq!(Session::read(bytes.as_slice()).unwrap().entries.len(), 5);
+
+        let mut lines: Vec<&str> = std::str::from_utf8(&bytes).unwrap().lines().collect();
+        lines[2] = "garbage";
+        let err = Session::read(lines.join("\n").as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("line 3"));
+    }
+
+    /// What the app would show for `h1` after the playback so far.
+    struct Shown {
+        stats: PingStats,
+        resolved: bool,
+        portal: Option<ProbeResult>,
+        ended: bool,
+    }
+
+    impl Shown {
+        fn new() -> Self {
+            Self {
+                stats: PingStats::new(5),
+                resolved: false,
+                portal: None,
+                ended: false,
+            }
+        }
+
+        /// Apply one piece of playback; returns the clock when that's what it was.
+        fn apply(&mut self, playback: Playback) -> Option<u64> {
+            match playback {
+                Playback::Event(entry) => match entry.event {
+                    SessionEvent::Ping(PingEvent {
+                        update: HostUpdate::Resolved(_),
+                        ..
+                    }) => self.resolved = true,
+                    SessionEvent::Ping(PingEvent {
+                        update: HostUpdate::Pinged(r),
+                        ..
+                    }) => self.stats.add_result(&r),
+                    SessionEvent::Portal(p) => self.portal = Some(p),
+                    _ => {}
+                },
+                Playback::Restart => *self = Self::new(),
+                Playback::At { at_ms, .. } => return Some(at_ms),
+                Playback::Ended => self.ended = true,
+            }
+            None
+        }
+
+        /// Apply playback until the replay reports its clock, and return that.
+        async fn until_clock(&mut self, rx: &mut mpsc::Receiver<Playback>) -> u64 {
+            loop {
+                let playback = rx.recv().await.expect("replay stopped early");
+                if let Some(at_ms) = self.apply(playback) {
+                    return at_ms;
+                }
+            }
+        }
+
+        fn state(&self) -> HostState {
+            status::host_state(Some(&self.stats), self.resolved, None)
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_reproduces_host_state() {
+        let session = Session::read(recording().as_slice()).unwrap();
+        let (out, mut rx) = mpsc::channel(16);
+        // Nobody steering: it plays straight through and stops.
+        let (_, control) = mpsc::channel(1);
+        replay(session, 1000.0, out, control).await;
+
+        let mut shown = Shown::new();
+        while let Some(playback) = rx.recv().await {
+            shown.apply(playback);
+        }
+        assert!(shown.ended);
+        assert_eq!(shown.portal, Some(ProbeResult::Online));
+        assert_eq!(shown.state(), HostState::Up { rtt_ms: 12.5 });
+    }
+
+    #[tokio::test]
+    async fn seeking_shows_the_state_at_that_point() {
+        // Answers every 10 s for the first 50 s, then nothing for the next minute.
+        let mut entries = vec![Entry {
+            at_ms: 0,
+            event: SessionEvent::Ping(PingEvent {
+                host_id: "h1".into(),
+                host_name: "VPN".into(),
+                update: HostUpdate::Resolved("10.0.0.1".parse().unwrap()),
+            }),
+        }];
+        for sequence in 0..12 {
+            let timestamp = Instant::now();
+            let result = match sequence {
+                0..=4 => PingResult::Success {
+                    rtt: Duration::from_micros(12_500),
+                    sequence,
+                    timestamp,
+                },
+                _ => PingResult::Timeout {
+                    sequence,
+                    timestamp,
+                },
+            };
+            entries.push(Entry {
+                at_ms: u64::from(sequence) * 10_000,
+                event: pinged(result),
+            });
+        }
+        let session = Session {
+            header: Session::read(recording().as_slice()).unwrap().header,
+            entries,
+        };
+        let (out, mut rx) = mpsc::channel(64);
+        let (control, control_rx) = mpsc::channel(4);
+        tokio::spawn(replay(session, 1.0, out, control_rx));
+        let mut shown = Shown::new();
+
+        control.send(ReplayControl::TogglePause).await.unwrap();
+        let start = shown.until_clock(&mut rx).await;
+        control.send(ReplayControl::Seek(95_000)).await.unwrap();
+        assert_eq!(shown.until_clock(&mut rx).await, start + 95_000);
+        assert_eq!(shown.stats.total_pings(), 10);
+        assert!(matches!(shown.state(), HostState::Down { .. }));
+
+        // Back to before the outage: everything after it is forgotten.
+        control.send(ReplayControl::Seek(-70_000)).await.unwrap();
+        assert_eq!(shown.until_clock(&mut rx).await, start + 25_000);
+        assert_eq!(shown.stats.total_pings(), 3);
+        assert_eq!(shown.state(), HostState::Up { rtt_ms: 12.5 });
+
+        // Stepping plays the next event and no more.
+        control.send(ReplayControl::Step).await.unwrap();
+        assert_eq!(shown.until_clock(&mut rx).await, 30_000);
+        assert_eq!(shown.stats.total_pings(), 4);
+        assert!(!shown.ended);
+    }
+
+    #[tokio::test]
+    async fn hosts_added_mid_recording_round_trip() {
+        let added = SessionHost {
+            id: "h2".into(),
+            name: "DNS".into(),
+            group: None,
+            ..host()
+        };
+        let mut rec = Recorder::new(Vec::new(), vec![host()]).unwrap();
+        rec.set_hosts(vec![host(), added.clone()]).unwrap();
+        // Unchanged, so not recorded again.
+        rec.set_hosts(vec![host(), added.clone()]).unwrap();
+        rec.record(SessionEvent::Ping(PingEvent {
+            host_id: "h2".into(),
+            host_name: "DNS".into(),
+            update: HostUpdate::Resolved("10.0.0.53".parse().unwrap()),
+        }))
+        .unwrap();
+
+        let mut session = Session::read(rec.out.as_slice()).unwrap();
+        assert_eq!(session.header.hosts, vec![host()]);
+        assert_eq!(session.entries.len(), 2);
+        assert!(matches!(
+            &session.entries[0].event,
+            SessionEvent::Hosts(hosts) if *hosts == vec![host(), added.clone()]
+        ));
+
+        // Played back, the host is listed before its first event; going back to the
+        // start lists only the recording's first hosts again.
+        session.entries[0].at_ms = 1_000;
+        session.entries[1].at_ms = 2_000;
+        let (out, mut rx) = mpsc::channel(16);
+        let (control, control_rx) = mpsc::channel(4);
+        tokio::spawn(replay(session, 1000.0, out, control_rx));
+        let mut hosts = vec![host()];
+        loop {
+            match rx.recv().await.unwrap() {
+                Playback::Event(Entry {
+                    event: SessionEvent::Hosts(list),
+                    ..
+                }) => hosts = list,
+                Playback::Event(Entry {
+                    event: SessionEvent::Ping(event),
+                    ..
+                }) => assert!(hosts.iter().any(|h| h.id == event.host_id)),
+                Playback::Ended => break,
+                other => panic!("unexpected playback {other:?}"),
+            }
+        }
+        assert_eq!(hosts, vec![host(), added]);
+
+        control.send(ReplayControl::Seek(-60_000)).await.unwrap();
+        assert!(matches!(rx.recv().await, Some(Playback::Restart)));
+        match rx.recv().await {
+            Some(Playback::Event(Entry {
+                event: SessionEvent::Hosts(list),
+                ..
+            })) => assert_eq!(list, vec![host()]),
+            other => panic!("unexpected playback {other:?}"),
+        }
+    }
+
+    #[test]
+    fn speed_accepts_multiplier_suffix() {
+        assert_eq!(parse_speed("10x"), Ok(10.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0x").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+}
@@ -1,34 +1,94 @@
 // ABOUTME: Statistics collection and analysis for ping results
 // ABOUTME: Maintains circular buffers of ping data and computes real-time metrics
 
-use std::collections::VecDeque;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, VecDeque};
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+/// Error text for a TCP probe answered with a RST: the host is up but the port is closed.
+pub const REFUSED: &str = "connection refused";
+
+/// Upper bounds of the lifetime RTT histogram buckets, in milliseconds.
+pub const RTT_BUCKETS_MS: [f64; 12] = [
+    1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+];
+
+/// Index into RTT_BUCKETS_MS of the bucket `rtt` falls in; one past the end when
+/// it's above the largest bound.
+fn rtt_bucket(rtt: Duration) -> usize {
+    let ms = rtt.as_secs_f64() * 1000.0;
+    RTT_BUCKETS_MS
+        .iter()
+        .position(|&le| ms <= le)
+        .unwrap_or(RTT_BUCKETS_MS.len())
+}
+
+/// One probe outcome. Serializes as `{"result": "success", "seq": 3, "rtt_ms": 12.4}`;
+/// the monotonic timestamp is process-local, so it is left out and restamped on load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
 pub enum PingResult {
     Success {
+        #[serde(
+            rename = "rtt_ms",
+            serialize_with = "serialize_ms",
+            deserialize_with = "deserialize_ms"
+        )]
         rtt: Duration,
-        #[allow(dead_code)]
+        #[serde(rename = "seq")]
         sequence: u16,
         #[allow(dead_code)]
+        #[serde(skip, default = "Instant::now")]
         timestamp: Instant,
     },
     Timeout {
-        #[allow(dead_code)]
+        #[serde(rename = "seq")]
         sequence: u16,
         #[allow(dead_code)]
+        #[serde(skip, default = "Instant::now")]
         timestamp: Instant,
     },
     Error {
-        #[allow(dead_code)]
         error: String,
-        #[allow(dead_code)]
+        #[serde(rename = "seq")]
         sequence: u16,
         #[allow(dead_code)]
+        #[serde(skip, default = "Instant::now")]
         timestamp: Instant,
     },
 }
 
+/// Serialize a duration as fractional milliseconds, the unit every view reports in.
+pub fn serialize_ms<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64() * 1000.0)
+}
+
+/// Read a duration written by [`serialize_ms`], rejecting negative or non-finite values.
+pub fn deserialize_ms<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+    let ms = f64::deserialize(d)?;
+    if !ms.is_finite() || ms < 0.0 {
+        return Err(serde::de::Error::custom(format!(
+            "invalid duration: {ms} ms"
+        )));
+    }
+    Ok(Duration::from_secs_f64(ms / 1000.0))
+}
+
+/// [`deserialize_ms`] for optional durations.
+pub fn deserialize_opt_ms<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    struct Ms(#[serde(deserialize_with = "deserialize_ms")] Duration);
+    Ok(Option::<Ms>::deserialize(d)?.map(|Ms(d)| d))
+}
+
+/// [`serialize_ms`] for optional durations; `None` becomes `null`.
+pub fn serialize_opt_ms<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+    match d {
+        Some(d) => serialize_ms(d, s),
+        None => s.serialize_none(),
+    }
+}
+
 impl PingResult {
     #[allow(dead_code)]
     pub fn timestamp(&self) -> Instant {
@@ -39,7 +99,6 @@ impl PingResult {
         }
     }
 
-    #[allow(dead_code)]
     pub fn sequence(&self) -> u16 {
         match self {
             PingResult::Success { sequence, .. } => *sequence,
@@ -58,6 +117,11 @@ impl PingResult {
             _ => None,
         }
     }
+
+    /// True for a TCP probe that reached the host but found the port closed.
+    pub fn is_refused(&self) -> bool {
+        matches!(self, PingResult::Error { error, .. } if error == REFUSED)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -68,6 +132,48 @@ pub struct PingStats {
     successful_pings: u64,
     timeouts: u64,
     errors: u64,
+    // Lifetime error count per error text (e.g. "NXDOMAIN", "connection refused").
+    error_counts: BTreeMap<String, u64>,
+    // Times a DNS probe's answer set differed from the previous reply.
+    answer_changes: u64,
+    // Lifetime successful RTTs per RTT_BUCKETS_MS bucket (not cumulative); the
+    // extra last slot counts RTTs above the largest bound.
+    rtt_buckets: [u64; RTT_BUCKETS_MS.len() + 1],
+    rtt_sum: Duration,
+    // RTTs of the successes in the retained history, kept sorted as results come and
+    // go, plus their per-bucket counts and their sum and sum of squares (in ns, exact),
+    // so neither percentiles nor the mean and spread need a pass over the history.
+    window_rtts: Vec<Duration>,
+    window_buckets: [u64; RTT_BUCKETS_MS.len() + 1],
+    window_sum: u128,
+    window_sum_sq: u128,
+    // RFC 3550 interarrival jitter in seconds, smoothed over every pair of consecutive
+    // successful RTTs, and the latest RTT to difference the next one against (none
+    // just after a marker).
+    jitter: f64,
+    last_rtt: Option<Duration>,
+    // Markers keyed by the number of results recorded before them, oldest first.
+    markers: VecDeque<(u64, Marker)>,
+}
+
+/// Something that happened between two results, drawn as a vertical line in the sparkline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// Probing was paused here; results on either side are not contiguous.
+    Pause,
+    /// The machine's network changed here (interface, address or default route).
+    NetworkChange,
+    /// The host resolved to a different address from here on.
+    AddressChange,
+}
+
+/// Cumulative lifetime RTT histogram: `(upper bound ms, count ≤ bound)` per bucket,
+/// plus the sum and count of every successful RTT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RttHistogram {
+    pub buckets: Vec<(f64, u64)>,
+    pub sum: Duration,
+    pub count: u64,
 }
 
 impl PingStats {
@@ -79,27 +185,169 @@ impl PingStats {
             successful_pings: 0,
             timeouts: 0,
             errors: 0,
+            error_counts: BTreeMap::new(),
+            answer_changes: 0,
+            rtt_buckets: [0; RTT_BUCKETS_MS.len() + 1],
+            rtt_sum: Duration::ZERO,
+            window_rtts: Vec::with_capacity(max_history),
+            window_buckets: [0; RTT_BUCKETS_MS.len() + 1],
+            window_sum: 0,
+            window_sum_sq: 0,
+            jitter: 0.0,
+            last_rtt: None,
+            markers: VecDeque::new(),
         }
     }
 
     pub fn add_result(&mut self, result: &PingResult) {
         // Add to history
         if self.history.len() >= self.max_history {
-            self.history.pop_front();
+            if let Some(rtt) = self.history.pop_front().and_then(|r| r.rtt()) {
+                if let Ok(i) = self.window_rtts.binary_search(&rtt) {
+                    self.window_rtts.remove(i);
+                }
+                self.window_buckets[rtt_bucket(rtt)] -= 1;
+                self.window_sum -= rtt.as_nanos();
+                self.window_sum_sq -= rtt.as_nanos() * rtt.as_nanos();
+            }
         }
         self.history.push_back(result.clone());
 
         // Update counters
         self.total_pings += 1;
+        // Drop markers that now sit before the oldest retained result.
+        let oldest = self.total_pings - self.history.len() as u64;
+        while self.markers.front().is_some_and(|(at, _)| *at < oldest) {
+            self.markers.pop_front();
+        }
         match result {
-            PingResult::Success { .. } => self.successful_pings += 1,
+            PingResult::Success { rtt, .. } => {
+                self.successful_pings += 1;
+                let bucket = rtt_bucket(*rtt);
+                self.rtt_buckets[bucket] += 1;
+                self.rtt_sum += *rtt;
+                let i = self.window_rtts.partition_point(|r| r <= rtt);
+                self.window_rtts.insert(i, *rtt);
+                self.window_buckets[bucket] += 1;
+                self.window_sum += rtt.as_nanos();
+                self.window_sum_sq += rtt.as_nanos() * rtt.as_nanos();
+                // J += (|D| - J) / 16, with D the change from the previous RTT.
+                if let Some(prev) = self.last_rtt {
+                    let d = rtt.abs_diff(prev).as_secs_f64();
+                    self.jitter += (d - self.jitter) / 16.0;
+                }
+                self.last_rtt = Some(*rtt);
+            }
             PingResult::Timeout { .. } => self.timeouts += 1,
-            PingResult::Error { .. } => self.errors += 1,
+            PingResult::Error { error, .. } => {
+                self.errors += 1;
+                *self.error_counts.entry(error.clone()).or_insert(0) += 1;
+            }
         }
     }
 
-    // Total-history loss; used in tests and available for callers that want lifetime stats.
+    /// Mark the point after the latest result, e.g. where probing was paused. Results
+    /// either side of a marker aren't consecutive, so jitter doesn't pair them.
+    pub fn add_marker(&mut self, marker: Marker) {
+        if self.markers.len() >= self.max_history {
+            self.markers.pop_front();
+        }
+        self.markers.push_back((self.total_pings, marker));
+        self.last_rtt = None;
+    }
+
+    /// Use the mean jitter of `paths`, the stats of each address this host's results
+    /// came from, instead of differencing RTTs that may have taken different paths.
+    pub fn take_jitter_from<'a>(&mut self, paths: impl IntoIterator<Item = &'a PingStats>) {
+        let measured: Vec<f64> = paths
+            .into_iter()
+            .filter(|s| s.successful_pings > 1)
+            .map(|s| s.jitter)
+            .collect();
+        self.jitter = match measured.len() {
+            0 => 0.0,
+            n => measured.iter().sum::<f64>() / n as f64,
+        };
+    }
+
+    /// Forget everything measured so far and start over from `marker`, so loss and
+    /// averages describe only what came after it.
+    pub fn start_epoch(&mut self, marker: Marker) {
+        *self = Self::new(self.max_history);
+        self.add_marker(marker);
+    }
+
+    /// Markers placed just before the result with this lifetime index (0-based);
+    /// `total_pings()` gives the ones after the latest result.
+    pub fn markers_at(&self, index: u64) -> impl Iterator<Item = Marker> + '_ {
+        self.markers
+            .iter()
+            .filter(move |(at, _)| *at == index)
+            .map(|(_, m)| *m)
+    }
+
+    /// Note that a DNS probe returned a different answer set than before.
+    pub fn record_answer_change(&mut self) {
+        self.answer_changes += 1;
+    }
+
+    // Raw counter behind outcome_summary; available for callers that want the number.
     #[allow(dead_code)]
+    pub fn answer_changes(&self) -> u64 {
+        self.answer_changes
+    }
+
+    /// Lifetime count of each distinct error, keyed by its text.
+    // Raw tally behind outcome_summary; available for callers that want the numbers.
+    #[allow(dead_code)]
+    pub fn error_counts(&self) -> &BTreeMap<String, u64> {
+        &self.error_counts
+    }
+
+    /// One-line tally of errors and answer changes, e.g. "NXDOMAIN 2 · answers changed 1×".
+    /// Empty when there is nothing to report.
+    pub fn outcome_summary(&self) -> String {
+        let mut parts: Vec<String> = self
+            .error_counts
+            .iter()
+            .map(|(error, n)| format!("{error} {n}"))
+            .collect();
+        if self.answer_changes > 0 {
+            parts.push(format!("answers changed {}\u{d7}", self.answer_changes));
+        }
+        parts.join(" \u{b7} ")
+    }
+
+    /// Lifetime RTT distribution over every successful result, not just the retained history.
+    pub fn rtt_histogram(&self) -> RttHistogram {
+        let mut cumulative = 0;
+        let buckets = RTT_BUCKETS_MS
+            .iter()
+            .zip(&self.rtt_buckets)
+            .map(|(&le, &n)| {
+                cumulative += n;
+                (le, cumulative)
+            })
+            .collect();
+        RttHistogram {
+            buckets,
+            sum: self.rtt_sum,
+            count: self.successful_pings,
+        }
+    }
+
+    /// RTT distribution over the retained history: `(upper bound ms, count)` per
+    /// RTT_BUCKETS_MS bucket (not cumulative), with an infinite bound for the rest.
+    pub fn recent_histogram(&self) -> Vec<(f64, u64)> {
+        RTT_BUCKETS_MS
+            .iter()
+            .copied()
+            .chain([f64::INFINITY])
+            .zip(self.window_buckets)
+            .collect()
+    }
+
+    /// Lifetime loss over every result recorded, not just the retained history.
     pub fn packet_loss_percent(&self) -> f64 {
         if self.total_pings == 0 {
             0.0
@@ -120,21 +368,20 @@ impl PingStats {
         ((total - successful) as f64 / total as f64) * 100.0
     }
 
+    /// Summary of the RTTs in the retained history.
     pub fn rtt_stats(&self) -> RttStats {
-        let rtts: Vec<Duration> = self.history.iter().filter_map(|r| r.rtt()).collect();
+        // Already sorted: add_result keeps it that way.
+        let sorted_rtts = &self.window_rtts;
 
-        if rtts.is_empty() {
+        if sorted_rtts.is_empty() {
             return RttStats::default();
         }
 
-        let mut sorted_rtts = rtts.clone();
-        sorted_rtts.sort();
-
         let min = *sorted_rtts.first().unwrap();
         let max = *sorted_rtts.last().unwrap();
 
-        let sum: Duration = rtts.iter().sum();
-        let avg = sum / rtts.len() as u32;
+        let n = sorted_rtts.len() as u128;
+        let avg = Duration::from_nanos((self.window_sum / n) as u64);
 
         let median = if sorted_rtts.len().is_multiple_of(2) {
             let mid = sorted_rtts.len() / 2;
@@ -143,44 +390,62 @@ impl PingStats {
             sorted_rtts[sorted_rtts.len() / 2]
         };
 
-        // Calculate jitter (standard deviation of RTT)
-        let variance: f64 = rtts
-            .iter()
-            .map(|rtt| {
-                let diff = rtt.as_secs_f64() - avg.as_secs_f64();
-                diff * diff
-            })
-            .sum::<f64>()
-            / rtts.len() as f64;
+        // Var = (n·Σx² − (Σx)²) / n², exact in integers up to the final division.
+        let spread = n * self.window_sum_sq - self.window_sum * self.window_sum;
+        let variance = spread as f64 / (n * n) as f64;
+        let stddev = Duration::from_nanos(variance.sqrt() as u64);
 
-        let jitter = Duration::from_secs_f64(variance.sqrt());
+        // Nearest rank: the smallest RTT that at least p% of them are at or below.
+        let percentile = |p: usize| sorted_rtts[(sorted_rtts.len() * p).div_ceil(100) - 1];
 
         RttStats {
             min,
             max,
             avg,
             median,
-            jitter,
+            p90: percentile(90),
+            p95: percentile(95),
+            p99: percentile(99),
+            stddev,
+            jitter: Duration::from_secs_f64(self.jitter),
         }
     }
 
-    // Derived quality category; available for callers that want a label beyond HostState.
-    #[allow(dead_code)]
+    /// Estimated voice-call quality from the ITU-T G.107 E-model: loss and mean RTT
+    /// over the last 20 results (the host state's window), with the smoothed jitter,
+    /// which is weighted to about the last 16 answers anyway. None before any result.
+    pub fn call_quality(&self) -> Option<CallQuality> {
+        if self.history.is_empty() {
+            return None;
+        }
+        let loss = self.packet_loss_percent_recent(20);
+        let rtts: Vec<Duration> = self
+            .history
+            .iter()
+            .rev()
+            .take(20)
+            .filter_map(PingResult::rtt)
+            .collect();
+        let avg_ms = match rtts.len() {
+            0 => 0.0,
+            n => rtts.iter().sum::<Duration>().as_secs_f64() * 1000.0 / n as f64,
+        };
+        Some(CallQuality::estimate(avg_ms, self.jitter * 1000.0, loss))
+    }
+
+    /// Good/Fair/Poor by the call-quality estimate: G.107's "satisfied" (R ≥ 80) and
+    /// "some users dissatisfied" (R ≥ 70) bands, and below.
     pub fn connection_quality(&self) -> ConnectionQuality {
-        let loss_percent = self.packet_loss_percent_recent(20); // Last 20 pings
-        let rtt_stats = self.rtt_stats();
-
-        // Quality based on packet loss and RTT
-        if loss_percent > 10.0 || rtt_stats.avg > Duration::from_millis(500) {
-            ConnectionQuality::Poor
-        } else if loss_percent > 2.0 || rtt_stats.avg > Duration::from_millis(100) {
-            ConnectionQuality::Fair
-        } else {
-            ConnectionQuality::Good
+        match self.call_quality() {
+            Some(q) if q.r_factor >= 80.0 => ConnectionQuality::Good,
+            Some(q) if q.r_factor >= 70.0 => ConnectionQuality::Fair,
+            Some(_) => ConnectionQuality::Poor,
+            // Nothing measured yet is nothing wrong yet.
+            None => ConnectionQuality::Good,
         }
     }
 
-    #[allow(dead_code)]
+    /// Most recent results first, at most `count`.
     pub fn recent_results(&self, count: usize) -> Vec<&PingResult> {
         self.history.iter().rev().take(count).collect()
     }
@@ -190,21 +455,23 @@ impl PingStats {
         &self.history
     }
 
+    /// The most recent result, if any.
+    pub fn last_result(&self) -> Option<&PingResult> {
+        self.history.back()
+    }
+
     pub fn total_pings(&self) -> u64 {
         self.total_pings
     }
 
-    #[allow(dead_code)]
     pub fn successful_pings(&self) -> u64 {
         self.successful_pings
     }
 
-    #[allow(dead_code)]
     pub fn timeouts(&self) -> u64 {
         self.timeouts
     }
 
-    #[allow(dead_code)]
     pub fn errors(&self) -> u64 {
         self.errors
     }
@@ -229,19 +496,54 @@ impl PingStats {
 
 #[derive(Debug, Clone, Default)]
 pub struct RttStats {
-    #[allow(dead_code)]
     pub min: Duration,
-    #[allow(dead_code)]
     pub max: Duration,
     pub avg: Duration,
-    #[allow(dead_code)]
     pub median: Duration,
-    #[allow(dead_code)]
+    pub p90: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    /// Standard deviation of the retained RTTs.
+    pub stddev: Duration,
+    /// RFC 3550 interarrival jitter: the smoothed mean difference between consecutive
+    /// RTTs, what VoIP tooling calls jitter. Runs over every result, not just the history.
     pub jitter: Duration,
 }
 
-// Quality classification; variants are available for external callers and tests.
-#[allow(dead_code)]
+/// An E-model (ITU-T G.107) transmission rating and the mean opinion score it maps
+/// to: roughly, how a voice call over this path would be rated from 1 (bad) to 4.5.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallQuality {
+    pub r_factor: f64,
+    pub mos: f64,
+}
+
+impl CallQuality {
+    /// Rate a path from its mean RTT and jitter (ms) and loss (%), assuming G.711
+    /// with packet-loss concealment, a jitter buffer of twice the jitter, and
+    /// G.107's defaults for everything not measured.
+    pub fn estimate(avg_rtt_ms: f64, jitter_ms: f64, loss_pct: f64) -> Self {
+        // One-way mouth-to-ear delay: half the round trip, the jitter buffer, and
+        // 10 ms of codec framing.
+        let delay = avg_rtt_ms / 2.0 + 2.0 * jitter_ms + 10.0;
+        let delay_impairment = 0.024 * delay + 0.11 * (delay - 177.3).max(0.0);
+        // Ie-eff for random loss; Ie = 0 and Bpl = 25.1 for G.711 with PLC.
+        let loss = loss_pct.clamp(0.0, 100.0);
+        let loss_impairment = 95.0 * loss / (loss + 25.1);
+        let r_factor = (93.2 - delay_impairment - loss_impairment).clamp(0.0, 100.0);
+        let mos = if r_factor <= 0.0 {
+            1.0
+        } else {
+            1.0 + 0.035 * r_factor + 7e-6 * r_factor * (r_factor - 60.0) * (100.0 - r_factor)
+        };
+        Self {
+            r_factor,
+            mos: mos.clamp(1.0, 4.5),
+        }
+    }
+}
+
+/// Three-level call-quality label; see `PingStats::connection_quality`.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ConnectionQuality {
     Good,
@@ -391,6 +693,106 @@ mod stats_tests {
         assert_eq!(s.rtt_stats().median, Duration::from_millis(25));
     }
 
+    #[test]
+    fn percentiles_are_nearest_rank_over_the_window() {
+        let mut s = PingStats::new(100);
+        // Shuffled 1..=100 ms: order of arrival mustn't matter.
+        for ms in (0..100u64).map(|i| i * 37 % 100 + 1) {
+            s.add_result(&success(ms));
+        }
+        let r = s.rtt_stats();
+        assert_eq!(r.p90, Duration::from_millis(90));
+        assert_eq!(r.p95, Duration::from_millis(95));
+        assert_eq!(r.p99, Duration::from_millis(99));
+        // Pushing the 100 oldest out leaves only what came after.
+        for _ in 0..100 {
+            s.add_result(&success(5));
+        }
+        assert_eq!(s.rtt_stats().p99, Duration::from_millis(5));
+    }
+
+    #[test]
+    fn recent_histogram_follows_the_window() {
+        let mut s = PingStats::new(3);
+        for ms in [1u64, 3, 40, 9000] {
+            s.add_result(&success(ms));
+        }
+        s.add_result(&timeout());
+        let h = s.recent_histogram();
+        assert_eq!(h.len(), RTT_BUCKETS_MS.len() + 1);
+        // 1 and 3 ms aged out; 40 ms and the 9 s outlier remain.
+        assert_eq!(h.iter().map(|(_, n)| n).sum::<u64>(), 2);
+        assert_eq!(h[5], (50.0, 1));
+        assert_eq!(h.last(), Some(&(f64::INFINITY, 1)));
+    }
+
+    #[test]
+    fn stddev_is_the_spread_and_jitter_the_smoothed_step() {
+        let mut s = PingStats::new(100);
+        for ms in [10u64, 20, 30] {
+            s.add_result(&success(ms));
+        }
+        let r = s.rtt_stats();
+        // Population stddev of {10, 20, 30} is sqrt(200/3) ≈ 8.165 ms.
+        assert!((r.stddev.as_secs_f64() * 1000.0 - 8.165).abs() < 0.01);
+        // Two 10 ms steps: J = 10/16, then J += (10 - J)/16.
+        let j1 = 10.0 / 16.0;
+        let j2 = j1 + (10.0 - j1) / 16.0;
+        assert!((r.jitter.as_secs_f64() * 1000.0 - j2).abs() < 1e-6);
+        // A timeout doesn't reset it: the next RTT pairs with the last answered one
+        // (30 → 30), so jitter decays.
+        s.add_result(&timeout());
+        s.add_result(&success(30));
+        assert!(s.rtt_stats().jitter < r.jitter);
+    }
+
+    #[test]
+    fn window_mean_and_spread_follow_evictions() {
+        let mut s = PingStats::new(3);
+        for ms in [500u64, 10, 20, 30] {
+            s.add_result(&success(ms));
+        }
+        let r = s.rtt_stats();
+        assert_eq!(r.avg, Duration::from_millis(20));
+        assert!((r.stddev.as_secs_f64() * 1000.0 - 8.165).abs() < 0.01);
+        for _ in 0..3 {
+            s.add_result(&timeout());
+        }
+        assert_eq!(s.rtt_stats().stddev, Duration::ZERO);
+    }
+
+    #[test]
+    fn jitter_does_not_pair_results_across_a_marker() {
+        let mut s = PingStats::new(100);
+        s.add_result(&success(10));
+        for marker in [Marker::Pause, Marker::NetworkChange, Marker::AddressChange] {
+            s.add_marker(marker);
+            s.add_result(&success(80));
+            s.add_marker(marker);
+            s.add_result(&success(10));
+        }
+        assert_eq!(s.rtt_stats().jitter, Duration::ZERO);
+    }
+
+    #[test]
+    fn host_jitter_comes_from_its_addresses() {
+        // The host's rounds are answered by a steady 10 ms v4 path and a steady 80 ms
+        // v6 one in turn; neither path jitters.
+        let mut host = PingStats::new(100);
+        let (mut v4, mut v6) = (PingStats::new(100), PingStats::new(100));
+        for round in 0..10 {
+            v4.add_result(&success(10));
+            v6.add_result(&success(80));
+            host.add_result(&success(if round % 2 == 0 { 10 } else { 80 }));
+            host.take_jitter_from([&v4, &v6]);
+        }
+        assert_eq!(host.rtt_stats().jitter, Duration::ZERO);
+
+        v6.add_result(&success(96));
+        host.take_jitter_from([&v4, &v6]);
+        assert_eq!(host.rtt_stats().jitter, Duration::from_micros(500));
+    }
+
     #[test]
     fn jitter_zero_for_constant_rtt() {
         let mut s = PingStats::new(100);
@@ -415,6 +817,71 @@ mod stats_tests {
         assert_eq!(poor.connection_quality(), ConnectionQuality::Poor);
     }
 
+    #[test]
+    fn call_quality_follows_the_e_model() {
+        // A clean 20 ms path rates near G.711's ceiling (R 93.2, MOS 4.41).
+        let clean = CallQuality::estimate(20.0, 1.0, 0.0);
+        assert!((clean.r_factor - 92.67).abs() < 0.01);
+        assert!((clean.mos - 4.40).abs() < 0.01);
+        // Past 177 ms one way, delay starts to cost much more.
+        let far = CallQuality::estimate(600.0, 1.0, 0.0);
+        assert!(far.r_factor < 80.0 && far.r_factor > 70.0);
+        // 5% loss alone drops it out of "satisfied".
+        assert!(CallQuality::estimate(20.0, 1.0, 5.0).r_factor < 80.0);
+        assert_eq!(CallQuality::estimate(0.0, 0.0, 100.0).mos.round(), 1.0);
+        assert_eq!(PingStats::new(10).call_quality(), None);
+
+        // Slow answers that have since aged out of the 20-result window don't count.
+        let mut s = PingStats::new(100);
+        for ms in [900, 20] {
+            for _ in 0..20 {
+                s.add_result(&success(ms));
+            }
+        }
+        let recent = s.call_quality().unwrap();
+        assert!(recent.mos > 4.3, "MOS {}", recent.mos);
+    }
+
+    #[test]
+    fn refused_is_distinct_from_other_errors() {
+        let refused = PingResult::Error {
+            error: REFUSED.to_string(),
+            sequence: 0,
+            timestamp: Instant::now(),
+        };
+        assert!(refused.is_refused());
+        assert!(!error_result().is_refused());
+        assert!(!timeout().is_refused());
+
+        let mut s = PingStats::new(10);
+        s.add_result(&timeout());
+        s.add_result(&refused);
+        assert!(s.last_result().is_some_and(|r| r.is_refused()));
+    }
+
+    #[test]
+    fn distinct_errors_and_answer_changes_are_tallied() {
+        let mut s = PingStats::new(10);
+        assert_eq!(s.outcome_summary(), "");
+        for label in ["NXDOMAIN", "SERVFAIL", "NXDOMAIN"] {
+            s.add_result(&PingResult::Error {
+                error: label.to_string(),
+                sequence: 0,
+                timestamp: Instant::now(),
+            });
+        }
+        s.add_result(&timeout());
+        s.record_answer_change();
+        assert_eq!(s.error_counts().get("NXDOMAIN"), Some(&2));
+        assert_eq!(s.error_counts().get("SERVFAIL"), Some(&1));
+        assert_eq!(s.timeouts(), 1);
+        assert_eq!(s.answer_changes(), 1);
+        assert_eq!(
+            s.outcome_summary(),
+            "NXDOMAIN 2 \u{b7} SERVFAIL 1 \u{b7} answers changed 1\u{d7}"
+        );
+    }
+
     #[test]
     fn history_is_bounded() {
         let mut s = PingStats::new(3);
@@ -425,4 +892,55 @@ mod stats_tests {
         assert_eq!(s.rtt_stats().min, Duration::from_millis(3));
         assert_eq!(s.total_pings(), 5); // cumulative counter is unaffected by the cap
     }
+
+    #[test]
+    fn rtt_histogram_is_cumulative_and_outlives_history() {
+        let mut s = PingStats::new(2);
+        for ms in [1u64, 3, 40, 9000] {
+            s.add_result(&success(ms));
+        }
+        s.add_result(&timeout());
+        let h = s.rtt_histogram();
+        assert_eq!(h.count, 4);
+        assert_eq!(h.sum, Duration::from_millis(9044));
+        assert_eq!(h.buckets[0], (1.0, 1));
+        assert_eq!(h.buckets[2], (5.0, 2));
+        assert_eq!(h.buckets[5], (50.0, 3));
+        // 9000 ms is above every bound; only the count (+Inf) includes it.
+        assert_eq!(h.buckets.last(), Some(&(5000.0, 3)));
+    }
+
+    #[test]
+    fn markers_sit_between_results_and_age_out_with_history() {
+        let mut s = PingStats::new(3);
+        s.add_result(&success(1));
+        s.add_marker(Marker::Pause);
+        assert_eq!(s.markers_at(1).collect::<Vec<_>>(), vec![Marker::Pause]);
+        s.add_result(&success(2));
+        assert_eq!(s.markers_at(1).count(), 1);
+        assert_eq!(s.markers_at(2).count(), 0);
+        // Once result #1 (and everything before it) leaves the history, so does the marker.
+        for ms in [3, 4, 5] {
+            s.add_result(&success(ms));
+        }
+        assert_eq!(s.markers_at(1).count(), 0);
+        // Pausing doesn't add results, so loss is unaffected.
+        assert_eq!(s.packet_loss_percent_recent(20), 0.0);
+    }
+
+    #[test]
+    fn new_epoch_starts_stats_over_behind_a_marker() {
+        let mut s = PingStats::new(10);
+        s.add_result(&success(5));
+        s.add_result(&timeout());
+        s.start_epoch(Marker::NetworkChange);
+        assert_eq!(s.total_pings(), 0);
+        assert_eq!(
+            s.markers_at(0).collect::<Vec<_>>(),
+            vec![Marker::NetworkChange]
+        );
+        s.add_result(&success(7));
+        assert_eq!(s.packet_loss_percent_recent(20), 0.0);
+        assert_eq!(s.markers_at(0).count(), 1);
+    }
 }
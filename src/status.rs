@@ -1,16 +1,77 @@
 // ABOUTME: Pure derivation of per-host and global connectivity state and the
 // ABOUTME: terminal-title summary string. No I/O — fully unit-testable.
 
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+use crate::config::{Family, ProbeKind};
 use crate::probe::ProbeResult;
-use crate::stats::PingStats;
+use crate::stats::{PingResult, PingStats};
 
 /// Per-host display state, derived from stats + last resolution status.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
 pub enum HostState {
     Resolving,
-    Up { rtt_ms: f64 },
-    Degraded { loss_pct: f64 },
-    Down { reason: String },
+    Up {
+        rtt_ms: f64,
+    },
+    /// Losing replies, or, with `mos` set, answering with a call-quality score below
+    /// the configured `min_mos`.
+    Degraded {
+        loss_pct: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mos: Option<f64>,
+    },
+    Down {
+        reason: String,
+    },
+}
+
+impl HostState {
+    /// Short lowercase name of the state, matching its serialized tag.
+    pub fn label(&self) -> &'static str {
+        match self {
+            HostState::Resolving => "resolving",
+            HostState::Up { .. } => "up",
+            HostState::Degraded { .. } => "degraded",
+            HostState::Down { .. } => "down",
+        }
+    }
+
+    /// Why a degraded host is degraded: "12% loss", or "MOS 3.3" when it's its call
+    /// quality that fell short. None in any other state.
+    pub fn degraded_reason(&self) -> Option<String> {
+        match self {
+            HostState::Degraded { mos: Some(mos), .. } => Some(format!("MOS {mos:.1}")),
+            HostState::Degraded { loss_pct, .. } => Some(format!("{loss_pct:.0}% loss")),
+            _ => None,
+        }
+    }
+}
+
+/// Remembers the last state reported per host so only real transitions get recorded:
+/// a change of kind, or of the down reason. RTT and loss wobble within a state is not one.
+#[derive(Debug, Default)]
+pub struct Transitions {
+    last: HashMap<String, HostState>,
+}
+
+impl Transitions {
+    /// True (and remembered) when `state` differs from the host's previous one.
+    pub fn changed(&mut self, host_id: &str, state: &HostState) -> bool {
+        let same = match (self.last.get(host_id), state) {
+            (Some(HostState::Down { reason: a }), HostState::Down { reason: b }) => a == b,
+            (Some(prev), _) => mem::discriminant(prev) == mem::discriminant(state),
+            (None, _) => false,
+        };
+        if !same {
+            self.last.insert(host_id.to_string(), state.clone());
+        }
+        !same
+    }
 }
 
 /// Global connectivity, derived from all host states + the portal probe.
@@ -52,11 +113,20 @@ pub fn host_state(
         Some(s) => {
             let loss = s.packet_loss_percent_recent(20);
             if loss >= 100.0 {
-                HostState::Down {
-                    reason: "no replies".to_string(),
-                }
+                // Name the failure when the probe reported one: a refused TCP connect
+                // proves the host is reachable, and DNS rcodes (NXDOMAIN, SERVFAIL)
+                // say more than silence would.
+                let reason = match s.last_result() {
+                    Some(r) if r.is_refused() => "port closed".to_string(),
+                    Some(PingResult::Error { error, .. }) => error.clone(),
+                    _ => "no replies".to_string(),
+                };
+                HostState::Down { reason }
             } else if loss > 2.0 {
-                HostState::Degraded { loss_pct: loss }
+                HostState::Degraded {
+                    loss_pct: loss,
+                    mos: None,
+                }
             } else {
                 HostState::Up {
                     rtt_ms: s.rtt_stats().avg.as_secs_f64() * 1000.0,
@@ -66,6 +136,82 @@ pub fn host_state(
     }
 }
 
+/// Count an up ICMP host whose estimated call quality is below `min_mos` (when one
+/// is configured) as degraded: it answers, but a call over it would suffer. Connect,
+/// request and query times aren't a voice path, so other probes are left alone.
+pub fn with_min_mos(
+    state: HostState,
+    probe: ProbeKind,
+    stats: Option<&PingStats>,
+    min_mos: Option<f64>,
+) -> HostState {
+    let (HostState::Up { .. }, ProbeKind::Icmp, Some(stats), Some(min)) =
+        (&state, probe, stats, min_mos)
+    else {
+        return state;
+    };
+    match stats.call_quality() {
+        Some(q) if q.mos < min => HostState::Degraded {
+            loss_pct: stats.packet_loss_percent_recent(20),
+            mos: Some(q.mos),
+        },
+        _ => state,
+    }
+}
+
+/// Roll the states of a host's addresses up into one for the host: up when every
+/// address is, down when none passes traffic, and degraded in between, by the
+/// addresses' average loss. Addresses with no results yet don't count either way.
+pub fn roll_up(addresses: &[HostState]) -> HostState {
+    let settled: Vec<&HostState> = addresses
+        .iter()
+        .filter(|s| !matches!(s, HostState::Resolving))
+        .collect();
+    if settled.is_empty() {
+        return HostState::Resolving;
+    }
+    let up_rtts: Vec<f64> = settled
+        .iter()
+        .filter_map(|s| match s {
+            HostState::Up { rtt_ms } => Some(*rtt_ms),
+            _ => None,
+        })
+        .collect();
+    if up_rtts.len() == settled.len() {
+        return HostState::Up {
+            rtt_ms: up_rtts.iter().sum::<f64>() / up_rtts.len() as f64,
+        };
+    }
+    let reasons: Vec<&str> = settled
+        .iter()
+        .filter_map(|s| match s {
+            HostState::Down { reason } => Some(reason.as_str()),
+            _ => None,
+        })
+        .collect();
+    if reasons.len() == settled.len() {
+        // One shared reason says more than a count would.
+        let reason = if reasons.iter().all(|r| *r == reasons[0]) {
+            reasons[0].to_string()
+        } else {
+            format!("all {} addresses down", reasons.len())
+        };
+        return HostState::Down { reason };
+    }
+    let loss: f64 = settled
+        .iter()
+        .map(|s| match s {
+            HostState::Degraded { loss_pct, .. } => *loss_pct,
+            HostState::Down { .. } => 100.0,
+            _ => 0.0,
+        })
+        .sum();
+    HostState::Degraded {
+        loss_pct: loss / settled.len() as f64,
+        mos: None,
+    }
+}
+
 /// Derive global connectivity from host states and the latest probe result.
 pub fn connectivity(states: &[HostState], probe: &ProbeResult) -> ConnectivityState {
     if let ProbeResult::CaptivePortal { url } = probe {
@@ -111,7 +257,7 @@ pub fn aggregate(states: &[HostState]) -> Aggregate {
     let worst_loss_pct = states
         .iter()
         .filter_map(|s| match s {
-            HostState::Degraded { loss_pct } => Some(*loss_pct),
+            HostState::Degraded { loss_pct, .. } => Some(*loss_pct),
             HostState::Down { .. } => Some(100.0),
             _ => None,
         })
@@ -124,6 +270,225 @@ pub fn aggregate(states: &[HostState]) -> Aggregate {
     }
 }
 
+/// Connectivity and aggregate numbers for one group of hosts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupStatus {
+    pub name: String,
+    pub conn: ConnectivityState,
+    pub agg: Aggregate,
+}
+
+impl GroupStatus {
+    /// One-line summary for a group header, e.g. "LAN 2/2 up · 3ms".
+    pub fn summary(&self) -> String {
+        let ratio = format!("{}/{} up", self.agg.hosts_up, self.agg.hosts_total);
+        let metric = match self.conn {
+            ConnectivityState::Online => format!("{:.0}ms", self.agg.avg_rtt_ms),
+            ConnectivityState::Degraded => format!("{:.0}% loss", self.agg.worst_loss_pct),
+            ConnectivityState::CaptivePortal { .. } => "captive portal".to_string(),
+            ConnectivityState::Offline => "down".to_string(),
+        };
+        format!("{} {ratio} \u{b7} {metric}", self.name)
+    }
+}
+
+/// Connectivity per host group, in the order each group's first host appears.
+/// `groups` maps host id to group name; hosts without a group are left out.
+pub fn by_group(
+    states: &[(String, HostState)],
+    groups: &HashMap<String, String>,
+    probe: &ProbeResult,
+) -> Vec<GroupStatus> {
+    let mut members: Vec<(&str, Vec<HostState>)> = Vec::new();
+    for (id, state) in states {
+        let Some(group) = groups.get(id) else {
+            continue;
+        };
+        match members.iter_mut().find(|(name, _)| name == group) {
+            Some((_, list)) => list.push(state.clone()),
+            None => members.push((group, vec![state.clone()])),
+        }
+    }
+    members
+        .into_iter()
+        .map(|(name, states)| GroupStatus {
+            name: name.to_string(),
+            conn: connectivity(&states, probe),
+            agg: aggregate(&states),
+        })
+        .collect()
+}
+
+/// Why hosts are down, as far as the dependency graph can tell, in words for
+/// someone who doesn't run the network.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RootCause {
+    /// A host others depend on, with nothing up in front of it, is down.
+    Gateway { name: String },
+    /// A host others depend on is down though the one it depends on is fine.
+    Upstream { name: String, via: String },
+    /// Dual-stack hosts answer over one IP version but not the other, which `family`
+    /// is. Clients that try it first stall before falling back.
+    Family { family: Family, hosts: Vec<String> },
+    /// Name lookups are failing while hosts that do have an address answer.
+    Dns,
+    /// Particular hosts are down and nothing else is.
+    Targets(Vec<String>),
+}
+
+impl fmt::Display for RootCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gateway { name } => write!(f, "default gateway down ({name})"),
+            Self::Upstream { name, via } => write!(f, "upstream {name} unreachable ({via} ok)"),
+            Self::Family { family, hosts } => {
+                let ok = if *family == Family::V6 {
+                    Family::V4
+                } else {
+                    Family::V6
+                };
+                write!(
+                    f,
+                    "{} broken on {} ({} ok)",
+                    family.label(),
+                    name_list(hosts),
+                    ok.label()
+                )
+            }
+            Self::Dns => write!(f, "DNS failing but IPs reachable"),
+            Self::Targets(names) => write!(f, "only {} down", name_list(names)),
+        }
+    }
+}
+
+/// "A", "A and B", or "A, B and 3 more".
+fn name_list(names: &[String]) -> String {
+    match names {
+        [one] => one.clone(),
+        [a, b] => format!("{a} and {b}"),
+        [a, b, rest @ ..] => format!("{a}, {b} and {} more", rest.len()),
+        [] => String::new(),
+    }
+}
+
+/// Down hosts that are down because something they depend on is, mapped to the
+/// deepest such dependency. `deps` maps a host id to the ids it depends on.
+pub fn blocked(
+    states: &[(String, HostState)],
+    deps: &HashMap<String, Vec<String>>,
+) -> HashMap<String, String> {
+    let down: Vec<&str> = states
+        .iter()
+        .filter(|(_, s)| matches!(s, HostState::Down { .. }))
+        .map(|(id, _)| id.as_str())
+        .collect();
+    down.iter()
+        .filter_map(|id| {
+            let root = blocker(id, &down, deps, &mut vec![id])?;
+            Some((id.to_string(), root))
+        })
+        .collect()
+}
+
+/// The first down dependency of `id`, followed down to the one it in turn is behind.
+/// `seen` guards against dependency cycles.
+fn blocker<'a>(
+    id: &str,
+    down: &[&str],
+    deps: &'a HashMap<String, Vec<String>>,
+    seen: &mut Vec<&'a str>,
+) -> Option<String> {
+    for dep in deps.get(id).into_iter().flatten() {
+        if seen.contains(&dep.as_str()) || !down.contains(&dep.as_str()) {
+            continue;
+        }
+        seen.push(dep);
+        return Some(blocker(dep, down, deps, seen).unwrap_or_else(|| dep.clone()));
+    }
+    None
+}
+
+/// Explain the down hosts that aren't just behind another down host. Infrastructure
+/// that other hosts depend on is blamed first, then a broken IP version, then DNS,
+/// then the hosts themselves. None when nothing is down, or everything is and the
+/// graph can't say why. `stacks` maps the id of each half of a dual-stack host to
+/// that host's name and the half's family.
+pub fn root_cause(
+    states: &[(String, HostState)],
+    deps: &HashMap<String, Vec<String>>,
+    names: &[(String, String)],
+    stacks: &HashMap<String, (String, Family)>,
+) -> Option<RootCause> {
+    let name = |id: &str| {
+        names
+            .iter()
+            .find(|(h, _)| h == id)
+            .map_or(id.to_string(), |(_, n)| n.clone())
+    };
+    let state = |id: &str| states.iter().find(|(h, _)| h == id).map(|(_, s)| s);
+    let passing = |s: &HostState| matches!(s, HostState::Up { .. } | HostState::Degraded { .. });
+    let behind = blocked(states, deps);
+    let roots: Vec<(&str, &str)> = states
+        .iter()
+        .filter(|(id, _)| !behind.contains_key(id))
+        .filter_map(|(id, s)| match s {
+            HostState::Down { reason } => Some((id.as_str(), reason.as_str())),
+            _ => None,
+        })
+        .collect();
+    if roots.is_empty() {
+        return None;
+    }
+
+    let depended_on = |id: &str| deps.values().any(|d| d.iter().any(|x| x == id));
+    if let Some((id, _)) = roots.iter().find(|(id, _)| depended_on(id)) {
+        let via = deps
+            .get(*id)
+            .into_iter()
+            .flatten()
+            .find(|d| state(d).is_some_and(passing));
+        return Some(match via {
+            Some(via) => RootCause::Upstream {
+                name: name(id),
+                via: name(via),
+            },
+            None => RootCause::Gateway { name: name(id) },
+        });
+    }
+
+    // With every host down there's no telling a target from the network.
+    if !states.iter().any(|(_, s)| passing(s)) {
+        return None;
+    }
+    // Every down host is one half of a dual-stack host whose other half answers.
+    let half_broken = |id: &str| {
+        let (stack, family) = stacks.get(id)?;
+        states
+            .iter()
+            .any(|(other, s)| {
+                other != id && stacks.get(other).is_some_and(|(o, _)| o == stack) && passing(s)
+            })
+            .then_some((stack.clone(), *family))
+    };
+    let halves: Option<Vec<(String, Family)>> =
+        roots.iter().map(|(id, _)| half_broken(id)).collect();
+    if let Some(halves) = halves {
+        let family = halves[0].1;
+        if halves.iter().all(|(_, f)| *f == family) {
+            return Some(RootCause::Family {
+                family,
+                hosts: halves.into_iter().map(|(stack, _)| stack).collect(),
+            });
+        }
+    }
+    if roots.iter().all(|(_, reason)| reason.starts_with("dns: ")) {
+        return Some(RootCause::Dns);
+    }
+    Some(RootCause::Targets(
+        roots.iter().map(|(id, _)| name(id)).collect(),
+    ))
+}
+
 /// Build the terminal-title string: symbol + ratio + most-relevant metric.
 pub fn title(conn: &ConnectivityState, agg: &Aggregate) -> String {
     match conn {
@@ -149,7 +514,6 @@ pub fn title(conn: &ConnectivityState, agg: &Aggregate) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::stats::PingResult;
     use std::time::{Duration, Instant};
 
     fn stats_with(successes: usize, timeouts: usize, ms: u64) -> PingStats {
@@ -203,6 +567,81 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn low_call_quality_degrades_only_when_asked() {
+        // 20 replies at 700 ms: answering, but no good for a call (MOS ≈ 3.3).
+        let mut s = PingStats::new(100);
+        for _ in 0..20 {
+            s.add_result(&PingResult::Success {
+                rtt: Duration::from_millis(700),
+                sequence: 0,
+                timestamp: Instant::now(),
+            });
+        }
+        let up = host_state(Some(&s), true, None);
+        let icmp = ProbeKind::Icmp;
+        assert!(matches!(up, HostState::Up { .. }));
+        assert_eq!(with_min_mos(up.clone(), icmp, Some(&s), None), up);
+        assert_eq!(with_min_mos(up.clone(), icmp, Some(&s), Some(3.0)), up);
+        let low = with_min_mos(up, icmp, Some(&s), Some(3.6));
+        assert!(matches!(
+            low,
+            HostState::Degraded { loss_pct, mos: Some(mos) } if loss_pct == 0.0 && mos < 3.6
+        ));
+        // The reason is the score, not a loss there isn't.
+        assert_eq!(low.degraded_reason().as_deref(), Some("MOS 3.3"));
+        // Down stays down, whatever the score.
+        let down = HostState::Down {
+            reason: "no replies".into(),
+        };
+        assert_eq!(with_min_mos(down.clone(), icmp, Some(&s), Some(4.5)), down);
+    }
+
+    #[test]
+    fn call_quality_applies_to_ping_hosts_only() {
+        // A 700 ms HTTP request is slow, but it isn't a call.
+        let mut s = PingStats::new(100);
+        for _ in 0..20 {
+            s.add_result(&PingResult::Success {
+                rtt: Duration::from_millis(700),
+                sequence: 0,
+                timestamp: Instant::now(),
+            });
+        }
+        let up = host_state(Some(&s), true, None);
+        for probe in [ProbeKind::Tcp, ProbeKind::Http, ProbeKind::Dns] {
+            assert_eq!(with_min_mos(up.clone(), probe, Some(&s), Some(3.6)), up);
+        }
+    }
+
+    #[test]
+    fn addresses_roll_up_to_their_host() {
+        let up = |rtt_ms| HostState::Up { rtt_ms };
+        let down = |reason: &str| HostState::Down {
+            reason: reason.into(),
+        };
+        assert_eq!(roll_up(&[]), HostState::Resolving);
+        assert_eq!(
+            roll_up(&[up(10.0), up(20.0), HostState::Resolving]),
+            up(15.0)
+        );
+        assert_eq!(
+            roll_up(&[up(10.0), down("no replies")]),
+            HostState::Degraded {
+                loss_pct: 50.0,
+                mos: None,
+            }
+        );
+        assert_eq!(
+            roll_up(&[down("no replies"), down("no replies")]),
+            down("no replies")
+        );
+        assert_eq!(
+            roll_up(&[down("no replies"), down("port closed")]),
+            down("all 2 addresses down")
+        );
+    }
+
     #[test]
     fn portal_probe_wins() {
         let states = vec![HostState::Up { rtt_ms: 10.0 }];
@@ -269,6 +708,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn down_port_closed_when_tcp_refused() {
+        let mut s = PingStats::new(100);
+        for _ in 0..5 {
+            s.add_result(&PingResult::Error {
+                error: crate::stats::REFUSED.to_string(),
+                sequence: 0,
+                timestamp: Instant::now(),
+            });
+        }
+        assert_eq!(
+            host_state(Some(&s), true, None),
+            HostState::Down {
+                reason: "port closed".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn down_names_dns_rcode() {
+        let mut s = PingStats::new(100);
+        for _ in 0..5 {
+            s.add_result(&PingResult::Error {
+                error: "SERVFAIL".to_string(),
+                sequence: 0,
+                timestamp: Instant::now(),
+            });
+        }
+        assert_eq!(
+            host_state(Some(&s), true, None),
+            HostState::Down {
+                reason: "SERVFAIL".to_string()
+            }
+        );
+    }
+
     #[test]
     fn offline_when_no_hosts() {
         // An empty host list must never read as Online, even if the probe is Online.
@@ -282,7 +757,10 @@ mod tests {
     fn aggregate_counts_and_worst_loss() {
         let states = vec![
             HostState::Up { rtt_ms: 10.0 },
-            HostState::Degraded { loss_pct: 15.0 },
+            HostState::Degraded {
+                loss_pct: 15.0,
+                mos: None,
+            },
             HostState::Down { reason: "x".into() },
         ];
         let agg = aggregate(&states);
@@ -292,6 +770,136 @@ mod tests {
         assert_eq!(agg.worst_loss_pct, 100.0); // a Down host counts as 100% loss
     }
 
+    #[test]
+    fn groups_get_their_own_connectivity_and_summary() {
+        let states = vec![
+            ("a".to_string(), HostState::Up { rtt_ms: 2.0 }),
+            ("vpn".to_string(), HostState::Down { reason: "x".into() }),
+            ("b".to_string(), HostState::Up { rtt_ms: 4.0 }),
+            ("loose".to_string(), HostState::Up { rtt_ms: 90.0 }),
+        ];
+        let groups = HashMap::from([
+            ("a".to_string(), "LAN".to_string()),
+            ("b".to_string(), "LAN".to_string()),
+            ("vpn".to_string(), "VPN".to_string()),
+        ]);
+        let by = by_group(&states, &groups, &ProbeResult::Online);
+        assert_eq!(by.len(), 2);
+        assert_eq!(by[0].conn, ConnectivityState::Online);
+        assert_eq!(by[0].summary(), "LAN 2/2 up \u{b7} 3ms");
+        assert_eq!(by[1].conn, ConnectivityState::Offline);
+        assert_eq!(by[1].summary(), "VPN 0/1 up \u{b7} down");
+    }
+
+    fn dependency_fixture(states: [HostState; 4]) -> Option<RootCause> {
+        let ids = ["gw", "isp", "saas", "dns"];
+        let states: Vec<(String, HostState)> = ids
+            .iter()
+            .zip(states)
+            .map(|(id, s)| (id.to_string(), s))
+            .collect();
+        let names: Vec<(String, String)> = [("gw", "gateway"), ("isp", "ISP"), ("saas", "SaaS")]
+            .iter()
+            .map(|(id, n)| (id.to_string(), n.to_string()))
+            .collect();
+        let deps = HashMap::from([
+            ("isp".to_string(), vec!["gw".to_string()]),
+            ("saas".to_string(), vec!["isp".to_string()]),
+            ("dns".to_string(), vec!["isp".to_string()]),
+        ]);
+        root_cause(&states, &deps, &names, &HashMap::new())
+    }
+
+    #[test]
+    fn root_cause_walks_the_dependency_chain() {
+        let up = || HostState::Up { rtt_ms: 5.0 };
+        let down = || HostState::Down {
+            reason: "no replies".into(),
+        };
+        let dns = || HostState::Down {
+            reason: "dns: no address".into(),
+        };
+        assert_eq!(dependency_fixture([up(), up(), up(), up()]), None);
+        assert_eq!(
+            dependency_fixture([down(), down(), down(), down()])
+                .unwrap()
+                .to_string(),
+            "default gateway down (gateway)"
+        );
+        assert_eq!(
+            dependency_fixture([up(), down(), down(), down()])
+                .unwrap()
+                .to_string(),
+            "upstream ISP unreachable (gateway ok)"
+        );
+        assert_eq!(
+            dependency_fixture([up(), up(), dns(), dns()]),
+            Some(RootCause::Dns)
+        );
+        assert_eq!(
+            dependency_fixture([up(), up(), down(), up()])
+                .unwrap()
+                .to_string(),
+            "only SaaS down"
+        );
+    }
+
+    #[test]
+    fn one_half_of_a_dual_stack_host_down_blames_that_family() {
+        let up = || HostState::Up { rtt_ms: 5.0 };
+        let down = || HostState::Down {
+            reason: "no replies".into(),
+        };
+        let stacks = HashMap::from([
+            ("g4".to_string(), ("Google".to_string(), Family::V4)),
+            ("g6".to_string(), ("Google".to_string(), Family::V6)),
+            ("c4".to_string(), ("Cloudflare".to_string(), Family::V4)),
+            ("c6".to_string(), ("Cloudflare".to_string(), Family::V6)),
+        ]);
+        let cause = |states: [HostState; 4]| {
+            let states: Vec<(String, HostState)> = ["g4", "g6", "c4", "c6"]
+                .iter()
+                .zip(states)
+                .map(|(id, s)| (id.to_string(), s))
+                .collect();
+            root_cause(&states, &HashMap::new(), &[], &stacks).map(|c| c.to_string())
+        };
+        assert_eq!(
+            cause([up(), down(), up(), down()]).as_deref(),
+            Some("IPv6 broken on Google and Cloudflare (IPv4 ok)")
+        );
+        assert_eq!(
+            cause([down(), up(), up(), up()]).as_deref(),
+            Some("IPv4 broken on Google (IPv6 ok)")
+        );
+        // A host that's down over both isn't a family problem.
+        assert_eq!(
+            cause([down(), down(), up(), down()]).as_deref(),
+            Some("only g4, g6 and 1 more down")
+        );
+    }
+
+    #[test]
+    fn hosts_behind_a_down_dependency_are_blocked_by_the_deepest_one() {
+        let down = || HostState::Down { reason: "x".into() };
+        let states = vec![
+            ("gw".to_string(), down()),
+            ("isp".to_string(), down()),
+            ("saas".to_string(), down()),
+            ("loop".to_string(), down()),
+        ];
+        let deps = HashMap::from([
+            ("isp".to_string(), vec!["gw".to_string()]),
+            ("saas".to_string(), vec!["isp".to_string()]),
+            ("loop".to_string(), vec!["loop".to_string()]), // cycles don't hang
+        ]);
+        let b = blocked(&states, &deps);
+        assert_eq!(b.get("saas").map(String::as_str), Some("gw"));
+        assert_eq!(b.get("isp").map(String::as_str), Some("gw"));
+        assert!(!b.contains_key("gw"));
+        assert!(!b.contains_key("loop"));
+    }
+
     #[test]
     fn title_degraded_shows_loss() {
         let agg = Aggregate {
@@ -0,0 +1,416 @@
+// ABOUTME: SQLite history of every probe result and host state transition.
+// ABOUTME: Old results are rolled up into coarse buckets, then expired; recent ones reload on startup.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rusqlite::{params, Connection};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::config::StorageConfig;
+use crate::stats::{PingResult, PingStats};
+use crate::status::HostState;
+
+/// How often the writer rolls up and expires old rows.
+const COMPACT_EVERY: Duration = Duration::from_secs(3600);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS pings (
+    ts_ms   INTEGER NOT NULL,
+    host_id TEXT NOT NULL,
+    host    TEXT NOT NULL,
+    seq     INTEGER NOT NULL,
+    result  TEXT NOT NULL,
+    rtt_ms  REAL,
+    error   TEXT
+);
+CREATE INDEX IF NOT EXISTS pings_host_ts ON pings (host_id, ts_ms);
+CREATE TABLE IF NOT EXISTS rollups (
+    bucket_ms  INTEGER NOT NULL,
+    host_id    TEXT NOT NULL,
+    host       TEXT NOT NULL,
+    sent       INTEGER NOT NULL,
+    received   INTEGER NOT NULL,
+    rtt_min_ms REAL,
+    rtt_sum_ms REAL,
+    rtt_max_ms REAL,
+    PRIMARY KEY (host_id, bucket_ms)
+);
+CREATE TABLE IF NOT EXISTS transitions (
+    ts_ms   INTEGER NOT NULL,
+    host_id TEXT NOT NULL,
+    host    TEXT NOT NULL,
+    state   TEXT NOT NULL,
+    detail  TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transitions_host_ts ON transitions (host_id, ts_ms);
+";
+
+/// One row to persist, stamped with wall-clock time when the app received it.
+#[derive(Debug, Clone)]
+pub enum Record {
+    Ping {
+        at: DateTime<Utc>,
+        host_id: String,
+        host: String,
+        result: PingResult,
+    },
+    Transition {
+        at: DateTime<Utc>,
+        host_id: String,
+        host: String,
+        state: HostState,
+    },
+}
+
+pub struct Store {
+    conn: Connection,
+    config: StorageConfig,
+}
+
+impl Store {
+    /// Open (creating if needed) the database at `config.path`.
+    pub fn open(config: &StorageConfig) -> Result<Self> {
+        let conn = Connection::open(&config.path)
+            .with_context(|| format!("Failed to open history database: {}", config.path))?;
+        Self::with_connection(conn, config)
+    }
+
+    fn with_connection(conn: Connection, config: &StorageConfig) -> Result<Self> {
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create history tables")?;
+        Ok(Self {
+            conn,
+            config: config.clone(),
+        })
+    }
+
+    /// The host's most recent full-resolution results, oldest first, at most `limit`.
+    /// Each result's monotonic timestamp is rebuilt from its age relative to now.
+    pub fn recent(&self, host_id: &str, limit: usize) -> Result<Vec<PingResult>> {
+        let now_ms = Utc::now().timestamp_millis();
+        let now = Instant::now();
+        let mut stmt = self.conn.prepare(
+            "SELECT ts_ms, seq, result, rtt_ms, error FROM pings
+             WHERE host_id = ?1 ORDER BY ts_ms DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![host_id, limit as i64], |row| {
+            let age = Duration::from_millis((now_ms - row.get::<_, i64>(0)?).max(0) as u64);
+            let timestamp = now.checked_sub(age).unwrap_or(now);
+            let sequence = row.get::<_, i64>(1)? as u16;
+            let result = match row.get::<_, String>(2)?.as_str() {
+                "success" => PingResult::Success {
+                    rtt: Duration::from_secs_f64(row.get::<_, f64>(3)?.max(0.0) / 1000.0),
+                    sequence,
+                    timestamp,
+                },
+                "timeout" => PingResult::Timeout {
+                    sequence,
+                    timestamp,
+                },
+                _ => PingResult::Error {
+                    error: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                    sequence,
+                    timestamp,
+                },
+            };
+            Ok(result)
+        })?;
+        let mut results = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        results.reverse();
+        Ok(results)
+    }
+
+    /// Stats for a host that's starting, filled with its recent results so its graphs
+    /// start full.
+    pub fn recent_stats(&self, host_id: &str, history_size: usize) -> Result<PingStats> {
+        let mut stats = PingStats::new(history_size);
+        for result in self.recent(host_id, history_size)? {
+            stats.add_result(&result);
+        }
+        Ok(stats)
+    }
+
+    /// Write a batch of records in one transaction.
+    pub fn write(&mut self, records: &[Record]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut ping = tx.prepare_cached(
+                "INSERT INTO pings (ts_ms, host_id, host, seq, result, rtt_ms, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            let mut transition = tx.prepare_cached(
+                "INSERT INTO transitions (ts_ms, host_id, host, state, detail)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for record in records {
+                match record {
+                    Record::Ping {
+                        at,
+                        host_id,
+                        host,
+                        result,
+                    } => {
+                        let (kind, error) = match result {
+                            PingResult::Success { .. } => ("success", None),
+                            PingResult::Timeout { .. } => ("timeout", None),
+                            PingResult::Error { error, .. } => ("error", Some(error.as_str())),
+                        };
+                        let rtt_ms = result.rtt().map(|d| d.as_secs_f64() * 1000.0);
+                        ping.execute(params![
+                            at.timestamp_millis(),
+                            host_id,
+                            host,
+                            result.sequence(),
+                            kind,
+                            rtt_ms,
+                            error
+                        ])?;
+                    }
+                    Record::Transition {
+                        at,
+                        host_id,
+                        host,
+                        state,
+                    } => {
+                        transition.execute(params![
+                            at.timestamp_millis(),
+                            host_id,
+                            host,
+                            state.label(),
+                            serde_json::to_string(state)?
+                        ])?;
+                    }
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Roll results older than `raw_hours` up into `rollup_secs` buckets, then delete
+    /// everything older than `retention_days`. The rollup cutoff is aligned to a bucket
+    /// boundary so a bucket is never split across two compactions.
+    pub fn compact(&mut self, now: DateTime<Utc>) -> Result<()> {
+        let bucket_ms = i64::from(self.config.rollup_secs.max(1)) * 1000;
+        let raw_cutoff = (now - ChronoDuration::hours(i64::from(self.config.raw_hours)))
+            .timestamp_millis()
+            / bucket_ms
+            * bucket_ms;
+        let keep_cutoff =
+            (now - ChronoDuration::days(i64::from(self.config.retention_days))).timestamp_millis();
+
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO rollups (bucket_ms, host_id, host, sent, received, rtt_min_ms, rtt_sum_ms, rtt_max_ms)
+             SELECT ts_ms / ?1 * ?1, host_id, MAX(host), COUNT(*), SUM(result = 'success'),
+                    MIN(rtt_ms), SUM(rtt_ms), MAX(rtt_ms)
+             FROM pings WHERE ts_ms < ?2 GROUP BY ts_ms / ?1, host_id
+             ON CONFLICT (host_id, bucket_ms) DO UPDATE SET
+                sent = sent + excluded.sent,
+                received = received + excluded.received,
+                rtt_min_ms = MIN(COALESCE(rtt_min_ms, excluded.rtt_min_ms), COALESCE(excluded.rtt_min_ms, rtt_min_ms)),
+                rtt_sum_ms = COALESCE(rtt_sum_ms, 0) + COALESCE(excluded.rtt_sum_ms, 0),
+                rtt_max_ms = MAX(COALESCE(rtt_max_ms, excluded.rtt_max_ms), COALESCE(excluded.rtt_max_ms, rtt_max_ms))",
+            params![bucket_ms, raw_cutoff],
+        )?;
+        tx.execute("DELETE FROM pings WHERE ts_ms < ?1", params![raw_cutoff])?;
+        tx.execute("DELETE FROM pings WHERE ts_ms < ?1", params![keep_cutoff])?;
+        tx.execute(
+            "DELETE FROM rollups WHERE bucket_ms < ?1",
+            params![keep_cutoff],
+        )?;
+        tx.execute(
+            "DELETE FROM transitions WHERE ts_ms < ?1",
+            params![keep_cutoff],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move the store onto a writer thread and return the channel feeding it. Records
+    /// are written in batches of whatever has queued up. The channel is unbounded, so a
+    /// slow disk makes the queue longer rather than losing results or transitions; a
+    /// failed write (disk full, locked file) drops that batch rather than stalling the
+    /// monitor.
+    pub fn spawn_writer(mut self) -> mpsc::UnboundedSender<Record> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Record>();
+        std::thread::spawn(move || {
+            let mut last_compact = Instant::now();
+            while let Some(first) = rx.blocking_recv() {
+                let mut batch = vec![first];
+                while let Ok(record) = rx.try_recv() {
+                    batch.push(record);
+                }
+                let _ = self.write(&batch);
+                if last_compact.elapsed() >= COMPACT_EVERY {
+                    let _ = self.compact(Utc::now());
+                    last_compact = Instant::now();
+                }
+            }
+        });
+        tx
+    }
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use super::*;
+
+    fn store() -> Store {
+        let config = StorageConfig {
+            enabled: true,
+            raw_hours: 1,
+            retention_days: 2,
+            ..StorageConfig::default()
+        };
+        Store::with_connection(Connection::open_in_memory().unwrap(), &config).unwrap()
+    }
+
+    fn ping(at: DateTime<Utc>, host_id: &str, result: PingResult) -> Record {
+        Record::Ping {
+            at,
+            host_id: host_id.to_string(),
+            host: "Host".to_string(),
+            result,
+        }
+    }
+
+    fn success(ms: u64, sequence: u16) -> PingResult {
+        PingResult::Success {
+            rtt: Duration::from_millis(ms),
+            sequence,
+            timestamp: Instant::now(),
+        }
+    }
+
+    fn count(store: &Store, table: &str) -> i64 {
+        store
+            .conn
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn recent_reloads_results_oldest_first() {
+        let mut s = store();
+        let now = Utc::now();
+        s.write(&[
+            ping(now - ChronoDuration::seconds(3), "a", success(10, 1)),
+            ping(
+                now - ChronoDuration::seconds(2),
+                "a",
+                PingResult::Error {
+                    error: "NXDOMAIN".into(),
+                    sequence: 2,
+                    timestamp: Instant::now(),
+                },
+            ),
+            ping(now - ChronoDuration::seconds(1), "a", success(30, 3)),
+            ping(now, "b", success(99, 1)),
+        ])
+        .unwrap();
+
+        let r = s.recent("a", 2).unwrap();
+        assert_eq!(r.len(), 2);
+        assert!(matches!(&r[0], PingResult::Error { error, .. } if error == "NXDOMAIN"));
+        assert_eq!(r[1].rtt(), Some(Duration::from_millis(30)));
+        assert_eq!(r[1].sequence(), 3);
+        assert!(r[0].timestamp() <= r[1].timestamp());
+    }
+
+    #[test]
+    fn a_reader_sees_what_the_writer_wrote() {
+        let dir = std::env::temp_dir().join(format!("pingpong-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = StorageConfig {
+            enabled: true,
+            path: dir.join("history.db").to_string_lossy().into_owned(),
+            ..StorageConfig::default()
+        };
+        let tx = Store::open(&config).unwrap().spawn_writer();
+        let reader = Store::open(&config).unwrap();
+        for sequence in 0..3 {
+            tx.send(ping(Utc::now(), "a", success(10, sequence)))
+                .unwrap();
+        }
+        let mut stats = reader.recent_stats("a", 10).unwrap();
+        for _ in 0..100 {
+            if stats.total_pings() == 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+            stats = reader.recent_stats("a", 10).unwrap();
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(stats.total_pings(), 3);
+        assert_eq!(stats.successful_pings(), 3);
+    }
+
+    #[test]
+    fn transitions_are_stored_with_their_detail() {
+        let mut s = store();
+        s.write(&[Record::Transition {
+            at: Utc::now(),
+            host_id: "a".into(),
+            host: "VPN".into(),
+            state: HostState::Down {
+                reason: "no replies".into(),
+            },
+        }])
+        .unwrap();
+        let (state, detail): (String, String) = s
+            .conn
+            .query_row("SELECT state, detail FROM transitions", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(state, "down");
+        assert!(detail.contains("no replies"));
+    }
+
+    #[test]
+    fn compact_rolls_up_old_results_and_expires_ancient_ones() {
+        let mut s = store();
+        let now = Utc::now();
+        let old = now - ChronoDuration::hours(3);
+        let old =
+            DateTime::from_timestamp_millis(old.timestamp_millis() / 60_000 * 60_000).unwrap();
+        s.write(&[
+            ping(old, "a", success(10, 1)),
+            ping(old + ChronoDuration::seconds(10), "a", success(30, 2)),
+            ping(
+                old + ChronoDuration::seconds(20),
+                "a",
+                PingResult::Timeout {
+                    sequence: 3,
+                    timestamp: Instant::now(),
+                },
+            ),
+            ping(now - ChronoDuration::days(3), "a", success(5, 0)),
+            ping(now, "a", success(20, 4)),
+        ])
+        .unwrap();
+
+        s.compact(now).unwrap();
+
+        // Only the fresh result stays at full resolution.
+        assert_eq!(count(&s, "pings"), 1);
+        let (sent, received, min, sum, max): (i64, i64, f64, f64, f64) = s
+            .conn
+            .query_row(
+                "SELECT sent, received, rtt_min_ms, rtt_sum_ms, rtt_max_ms FROM rollups",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!((sent, received), (3, 2));
+        assert_eq!((min, sum, max), (10.0, 40.0, 30.0));
+        // The three-day-old result was rolled up and then expired.
+        assert_eq!(count(&s, "rollups"), 1);
+
+        // A second compaction changes nothing.
+        s.compact(now).unwrap();
+        assert_eq!(count(&s, "rollups"), 1);
+    }
+}
@@ -0,0 +1,524 @@
+// ABOUTME: Hop-by-hop path tracing (MTR-style) toward one host's resolved address.
+// ABOUTME: Sends TTL-limited UDP probes each round and keeps per-hop loss/RTT in PingStats.
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::stats::{PingResult, PingStats};
+
+/// Deepest TTL probed while the destination hasn't answered yet.
+pub const MAX_HOPS: u8 = 30;
+
+/// One hop's answer (or silence) in a single round.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HopReply {
+    pub ttl: u8,
+    /// Router (or the destination) that answered; None when the probe went unanswered.
+    pub from: Option<IpAddr>,
+    pub rtt: Option<Duration>,
+    /// True when the destination itself answered (port unreachable), ending the path.
+    pub reached: bool,
+}
+
+/// A finished round of probes, or why the round could not be sent.
+pub type TraceRound = Result<Vec<HopReply>, String>;
+
+/// Accumulated state for one hop.
+#[derive(Debug, Clone)]
+pub struct Hop {
+    pub ttl: u8,
+    /// Every address seen answering at this TTL (several with ECMP load balancing).
+    pub addrs: Vec<IpAddr>,
+    pub stats: PingStats,
+}
+
+/// One rendered row of the path table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathRow {
+    pub ttl: u8,
+    pub host: String,
+    pub loss_pct: f64,
+    pub sent: u64,
+    pub last_ms: Option<f64>,
+    pub avg_ms: f64,
+    pub best_ms: f64,
+    pub worst_ms: f64,
+    pub jitter_ms: f64,
+}
+
+/// Everything the TUI needs to draw the path view for one frame.
+#[derive(Debug, Clone)]
+pub struct PathPanel {
+    pub title: String,
+    pub rows: Vec<PathRow>,
+    pub error: Option<String>,
+}
+
+/// The drill-down path view for one host: per-hop stats built up round by round.
+pub struct PathView {
+    host_name: String,
+    target: IpAddr,
+    hops: Vec<Hop>,
+    // TTL at which the destination answered; hops past it are noise and dropped.
+    path_len: Option<u8>,
+    error: Option<String>,
+    history_size: usize,
+    sequence: u16,
+}
+
+impl PathView {
+    pub fn new(host_name: String, target: IpAddr, history_size: usize) -> Self {
+        Self {
+            host_name,
+            target,
+            hops: Vec::new(),
+            path_len: None,
+            error: None,
+            history_size,
+            sequence: 0,
+        }
+    }
+
+    /// Fold one round of replies into the per-hop stats.
+    pub fn apply(&mut self, round: TraceRound) {
+        let replies = match round {
+            Ok(r) => r,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        self.error = None;
+        // The shortest TTL at which the destination answered is the path length.
+        if let Some(len) = replies.iter().filter(|r| r.reached).map(|r| r.ttl).min() {
+            self.path_len = Some(len);
+        }
+        let now = Instant::now();
+        for reply in replies {
+            if reply.ttl == 0 || self.path_len.is_some_and(|len| reply.ttl > len) {
+                continue;
+            }
+            let idx = reply.ttl as usize - 1;
+            while self.hops.len() <= idx {
+                self.hops.push(Hop {
+                    ttl: self.hops.len() as u8 + 1,
+                    addrs: Vec::new(),
+                    stats: PingStats::new(self.history_size),
+                });
+            }
+            let hop = &mut self.hops[idx];
+            if let Some(addr) = reply.from {
+                if !hop.addrs.contains(&addr) {
+                    hop.addrs.push(addr);
+                }
+            }
+            let result = match reply.rtt {
+                Some(rtt) => PingResult::Success {
+                    rtt,
+                    sequence: self.sequence,
+                    timestamp: now,
+                },
+                None => PingResult::Timeout {
+                    sequence: self.sequence,
+                    timestamp: now,
+                },
+            };
+            hop.stats.add_result(&result);
+        }
+        if let Some(len) = self.path_len {
+            self.hops.truncate(len as usize);
+        }
+        self.sequence = self.sequence.wrapping_add(1);
+    }
+
+    /// Snapshot for rendering.
+    pub fn panel(&self) -> PathPanel {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let rows = self
+            .hops
+            .iter()
+            .map(|hop| {
+                let rtt = hop.stats.rtt_stats();
+                let host = match hop.addrs.as_slice() {
+                    [] => "???".to_string(),
+                    [one] => one.to_string(),
+                    [first, rest @ ..] => format!("{first} (+{})", rest.len()),
+                };
+                PathRow {
+                    ttl: hop.ttl,
+                    host,
+                    loss_pct: hop.stats.packet_loss_percent(),
+                    sent: hop.stats.total_pings(),
+                    last_ms: hop.stats.last_result().and_then(|r| r.rtt()).map(ms),
+                    avg_ms: ms(rtt.avg),
+                    best_ms: ms(rtt.min),
+                    worst_ms: ms(rtt.max),
+                    jitter_ms: ms(rtt.jitter),
+                }
+            })
+            .collect();
+        PathPanel {
+            title: format!(" Path to {} ({}) ", self.host_name, self.target),
+            rows,
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// Trace `target` every `interval` until the receiver is dropped. Each round probes
+/// TTLs 1..=depth, where depth shrinks to the known path length once it is found.
+pub async fn run(
+    target: IpAddr,
+    interval: Duration,
+    timeout: Duration,
+    tx: mpsc::Sender<TraceRound>,
+) {
+    let mut depth = MAX_HOPS;
+    let mut tick = tokio::time::interval(interval);
+    loop {
+        tick.tick().await;
+        // The round blocks in poll(2); keep it off the async workers.
+        let round = tokio::task::spawn_blocking(move || sys::probe_round(target, depth, timeout))
+            .await
+            .map_err(|e| format!("trace task failed: {e}"))
+            .and_then(|r| r.map_err(|e| format!("trace: {e}")));
+        if let Ok(replies) = &round {
+            depth = replies
+                .iter()
+                .filter(|r| r.reached)
+                .map(|r| r.ttl)
+                .min()
+                .unwrap_or(MAX_HOPS);
+        }
+        if tx.send(round).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Unprivileged tracepath-style probing: a UDP datagram per TTL with IP_RECVERR set,
+/// so the kernel queues the ICMP time-exceeded / port-unreachable replies on the socket.
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::HopReply;
+    use std::io;
+    use std::mem;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+    use std::os::fd::AsRawFd;
+    use std::time::{Duration, Instant};
+
+    /// Classic traceroute base port; each TTL uses its own port above it.
+    const BASE_PORT: u16 = 33434;
+
+    fn set_int_opt(
+        sock: &UdpSocket,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: libc::c_int,
+    ) -> io::Result<()> {
+        // SAFETY: the fd is open for the life of `sock`; we pass a live c_int and its size.
+        let rc = unsafe {
+            libc::setsockopt(
+                sock.as_raw_fd(),
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Convert a kernel sockaddr (the ICMP offender) into an IpAddr.
+    ///
+    /// # Safety
+    /// `sa` must point to a sockaddr_in or sockaddr_in6 as indicated by its family.
+    unsafe fn sockaddr_ip(sa: *const libc::sockaddr) -> Option<IpAddr> {
+        match (*sa).sa_family as libc::c_int {
+            libc::AF_INET => {
+                let sin = std::ptr::read_unaligned(sa as *const libc::sockaddr_in);
+                Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                    sin.sin_addr.s_addr,
+                ))))
+            }
+            libc::AF_INET6 => {
+                let sin6 = std::ptr::read_unaligned(sa as *const libc::sockaddr_in6);
+                Some(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Pop one queued ICMP error. Returns the responder and whether it was the destination.
+    fn read_error(sock: &UdpSocket) -> io::Result<Option<(IpAddr, bool)>> {
+        let mut data = [0u8; 512];
+        // u64 storage keeps the control buffer aligned for cmsghdr.
+        let mut control = [0u64; 64];
+        // SAFETY: all-zero is a valid sockaddr_storage and msghdr.
+        let mut name: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        };
+        msg.msg_name = &mut name as *mut libc::sockaddr_storage as *mut libc::c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+
+        // SAFETY: msg points at buffers that outlive the call.
+        let n = unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, libc::MSG_ERRQUEUE) };
+        if n < 0 {
+            let e = io::Error::last_os_error();
+            return if e.kind() == io::ErrorKind::WouldBlock {
+                Ok(None)
+            } else {
+                Err(e)
+            };
+        }
+
+        // SAFETY: the kernel filled msg_control with well-formed cmsgs of msg_controllen bytes;
+        // the CMSG_* helpers walk within those bounds.
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let hdr = &*cmsg;
+                let is_err = (hdr.cmsg_level == libc::SOL_IP && hdr.cmsg_type == libc::IP_RECVERR)
+                    || (hdr.cmsg_level == libc::SOL_IPV6 && hdr.cmsg_type == libc::IPV6_RECVERR);
+                if is_err {
+                    let ee_ptr = libc::CMSG_DATA(cmsg) as *const libc::sock_extended_err;
+                    let ee = std::ptr::read_unaligned(ee_ptr);
+                    let reached = match ee.ee_origin {
+                        // Port unreachable (v4 3/3, v6 1/4) comes from the destination itself.
+                        libc::SO_EE_ORIGIN_ICMP => ee.ee_type == 3 && ee.ee_code == 3,
+                        libc::SO_EE_ORIGIN_ICMP6 => ee.ee_type == 1 && ee.ee_code == 4,
+                        _ => return Ok(None), // locally generated, no responder
+                    };
+                    let from = sockaddr_ip(libc::SO_EE_OFFENDER(ee_ptr));
+                    return Ok(from.map(|ip| (ip, reached)));
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Send one probe per TTL in 1..=depth and collect replies until `timeout`.
+    pub fn probe_round(target: IpAddr, depth: u8, timeout: Duration) -> io::Result<Vec<HopReply>> {
+        struct Probe {
+            ttl: u8,
+            sock: UdpSocket,
+            sent: Instant,
+            reply: Option<HopReply>,
+        }
+
+        let mut probes = Vec::with_capacity(depth as usize);
+        for ttl in 1..=depth {
+            let sock = match target {
+                IpAddr::V4(_) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+                IpAddr::V6(_) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+            };
+            sock.set_nonblocking(true)?;
+            match target {
+                IpAddr::V4(_) => {
+                    sock.set_ttl(ttl as u32)?;
+                    set_int_opt(&sock, libc::IPPROTO_IP, libc::IP_RECVERR, 1)?;
+                }
+                IpAddr::V6(_) => {
+                    set_int_opt(
+                        &sock,
+                        libc::IPPROTO_IPV6,
+                        libc::IPV6_UNICAST_HOPS,
+                        ttl as libc::c_int,
+                    )?;
+                    set_int_opt(&sock, libc::IPPROTO_IPV6, libc::IPV6_RECVERR, 1)?;
+                }
+            }
+            let sent = Instant::now();
+            // A failed send (e.g. no route) just leaves this hop unanswered.
+            let _ = sock.send_to(b"pingpong", SocketAddr::new(target, BASE_PORT + ttl as u16));
+            probes.push(Probe {
+                ttl,
+                sock,
+                sent,
+                reply: None,
+            });
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let pending: Vec<usize> = (0..probes.len())
+                .filter(|&i| probes[i].reply.is_none())
+                .collect();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if pending.is_empty() || remaining.is_zero() {
+                break;
+            }
+            // POLLERR is always reported, so no events need to be requested.
+            let mut fds: Vec<libc::pollfd> = pending
+                .iter()
+                .map(|&i| libc::pollfd {
+                    fd: probes[i].sock.as_raw_fd(),
+                    events: 0,
+                    revents: 0,
+                })
+                .collect();
+            let wait_ms = remaining.as_millis().clamp(1, i32::MAX as u128) as libc::c_int;
+            // SAFETY: fds is a live, correctly sized pollfd array.
+            let rc = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, wait_ms) };
+            if rc < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            if rc == 0 {
+                break;
+            }
+            let now = Instant::now();
+            for (pfd, &i) in fds.iter().zip(&pending) {
+                if pfd.revents & libc::POLLERR == 0 {
+                    continue;
+                }
+                let probe = &mut probes[i];
+                // An error with no ICMP responder still settles the probe, so a
+                // drained queue can't keep poll spinning on the same socket.
+                let (from, rtt, reached) = match read_error(&probe.sock)? {
+                    Some((ip, reached)) => (Some(ip), Some(now - probe.sent), reached),
+                    None => (None, None, false),
+                };
+                probe.reply = Some(HopReply {
+                    ttl: probe.ttl,
+                    from,
+                    rtt,
+                    reached,
+                });
+            }
+        }
+
+        Ok(probes
+            .into_iter()
+            .map(|p| {
+                p.reply.unwrap_or(HopReply {
+                    ttl: p.ttl,
+                    from: None,
+                    rtt: None,
+                    reached: false,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use super::HopReply;
+    use std::io;
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    pub fn probe_round(
+        _target: IpAddr,
+        _depth: u8,
+        _timeout: Duration,
+    ) -> io::Result<Vec<HopReply>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the path view is only available on Linux",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(ttl: u8, from: Option<[u8; 4]>, ms: Option<u64>, reached: bool) -> HopReply {
+        HopReply {
+            ttl,
+            from: from.map(IpAddr::from),
+            rtt: ms.map(Duration::from_millis),
+            reached,
+        }
+    }
+
+    fn view() -> PathView {
+        PathView::new("host".into(), IpAddr::from([9, 9, 9, 9]), 100)
+    }
+
+    #[test]
+    fn hops_accumulate_loss_and_rtt_per_ttl() {
+        let mut v = view();
+        v.apply(Ok(vec![
+            reply(1, Some([10, 0, 0, 1]), Some(1), false),
+            reply(2, None, None, false),
+            reply(3, Some([9, 9, 9, 9]), Some(20), true),
+        ]));
+        v.apply(Ok(vec![
+            reply(1, Some([10, 0, 0, 1]), Some(3), false),
+            reply(2, Some([100, 64, 0, 1]), Some(10), false),
+            reply(3, Some([9, 9, 9, 9]), Some(22), true),
+        ]));
+        let rows = v.panel().rows;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].host, "10.0.0.1");
+        assert_eq!(rows[0].sent, 2);
+        assert_eq!(rows[0].loss_pct, 0.0);
+        assert_eq!(rows[0].best_ms, 1.0);
+        assert_eq!(rows[0].worst_ms, 3.0);
+        assert_eq!(
+            rows[1].loss_pct, 50.0,
+            "one of two probes at hop 2 was lost"
+        );
+        assert_eq!(rows[2].last_ms, Some(22.0));
+    }
+
+    #[test]
+    fn hops_beyond_the_destination_are_dropped() {
+        let mut v = view();
+        // First round didn't reach the target, so deeper TTLs were probed.
+        v.apply(Ok((1..=5).map(|t| reply(t, None, None, false)).collect()));
+        assert_eq!(v.panel().rows.len(), 5);
+        v.apply(Ok(vec![
+            reply(1, Some([10, 0, 0, 1]), Some(1), false),
+            reply(2, Some([9, 9, 9, 9]), Some(5), true),
+            reply(3, Some([9, 9, 9, 9]), Some(5), true),
+        ]));
+        assert_eq!(v.panel().rows.len(), 2);
+    }
+
+    #[test]
+    fn multiple_responders_are_listed_and_errors_surface() {
+        let mut v = view();
+        v.apply(Ok(vec![reply(1, Some([10, 0, 0, 1]), Some(1), false)]));
+        v.apply(Ok(vec![reply(1, Some([10, 0, 0, 2]), Some(1), false)]));
+        assert_eq!(v.panel().rows[0].host, "10.0.0.1 (+1)");
+        v.apply(Err("trace: permission denied".into()));
+        assert_eq!(v.panel().error.as_deref(), Some("trace: permission denied"));
+        assert_eq!(
+            v.panel().rows.len(),
+            1,
+            "an error keeps the stats gathered so far"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "sends real probes; run with --ignored"]
+    async fn loopback_is_reached_in_one_hop() {
+        let replies = tokio::task::spawn_blocking(|| {
+            sys::probe_round(IpAddr::from([127, 0, 0, 1]), 3, Duration::from_secs(1))
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(replies[0].reached);
+    }
+}
@@ -14,16 +14,20 @@ use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Paragraph, Sparkline},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Paragraph, Sparkline, SparklineBar},
     Frame, Terminal,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::io::Write as _;
+use std::net::IpAddr;
 use std::time::{Duration, Instant};
 
-use crate::stats::PingStats;
-use crate::status::HostState;
+use crate::config::{Host, HostEdit};
+use crate::stats::{ConnectionQuality, Marker, PingStats, RTT_BUCKETS_MS};
+use crate::status::{ConnectivityState, GroupStatus, HostState};
+use crate::trace::PathPanel;
 
 /// Put the terminal into TUI mode: raw mode, alternate screen, save title.
 pub fn terminal_enter() -> anyhow::Result<()> {
@@ -157,11 +161,203 @@ pub struct RenderOpts {
     pub graph_height: u16,
     pub banner: Option<String>, // connectivity banner text (portal/offline)
     pub host_states: Vec<(String, HostState)>, // (host_id, state)
+    pub address_states: HashMap<String, Vec<HostState>>, // host_id -> each probed address's state
+    pub host_ips: HashMap<String, String>, // host_id -> resolved address, and the one before a change
+    pub host_notes: HashMap<String, String>, // host_id -> extra detail (HTTP phases, DNS tally)
+    pub groups: Vec<GroupStatus>,          // per-group status for the host list's section headers
+    pub blocked: HashMap<String, String>,  // host_id -> id of the down host it's behind
+    pub path: Option<PathPanel>,           // open path (traceroute) view, replacing the host list
+    pub paused: HashSet<String>,           // host ids whose probing is paused
+    pub profile: Option<String>, // monitoring profile in use, shown in the host list title
+    pub icmp_hosts: HashSet<String>, // host ids probed by ping, the ones a call-quality score applies to
+}
+
+/// One row of the host list: a group's section header, a host (index into host_info),
+/// or one of an expanded host's addresses (index into its address list).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListRow {
+    Group { name: String, collapsed: bool },
+    Host(usize),
+    Address { host: usize, addr: usize },
+}
+
+/// The host list's rows: ungrouped hosts first, then each group (in the order its first
+/// host appears) as a header followed by its hosts, unless the group is collapsed. An
+/// expanded host is followed by a row per address it's probing.
+fn list_rows(
+    host_info: &[(String, String)],
+    groups: &HashMap<String, String>,
+    collapsed: &HashSet<String>,
+    addresses: &HashMap<String, usize>,
+    expanded: &HashSet<String>,
+) -> Vec<ListRow> {
+    let host_rows = |i: usize| {
+        let id = &host_info[i].0;
+        let shown = if expanded.contains(id) {
+            addresses.get(id).copied().unwrap_or(0)
+        } else {
+            0
+        };
+        std::iter::once(ListRow::Host(i))
+            .chain((0..shown).map(move |addr| ListRow::Address { host: i, addr }))
+    };
+    let mut rows: Vec<ListRow> = host_info
+        .iter()
+        .enumerate()
+        .filter(|(_, (id, _))| !groups.contains_key(id))
+        .flat_map(|(i, _)| host_rows(i))
+        .collect();
+    let mut order: Vec<&String> = Vec::new();
+    for group in host_info.iter().filter_map(|(id, _)| groups.get(id)) {
+        if !order.contains(&group) {
+            order.push(group);
+        }
+    }
+    for group in order {
+        let folded = collapsed.contains(group);
+        rows.push(ListRow::Group {
+            name: group.clone(),
+            collapsed: folded,
+        });
+        if !folded {
+            rows.extend(
+                host_info
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (id, _))| groups.get(id) == Some(group))
+                    .flat_map(|(i, _)| host_rows(i)),
+            );
+        }
+    }
+    rows
+}
+
+/// What the app should do in response to a key press.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiAction {
+    None,
+    Quit,
+    /// Open the path view for this host id.
+    OpenPath(String),
+    ClosePath,
+    /// Pause or resume probing of this host id, or of every host when None (or a
+    /// replay's playback).
+    TogglePause(Option<String>),
+    /// Pause a replay and play just its next event.
+    Step,
+    /// Move a replay's clock by this many milliseconds, back when negative.
+    Seek(i64),
+    /// Change the host list from the host editor.
+    EditHost(HostEdit),
+    /// Write the edited host list back to the config file.
+    SaveHosts,
+    /// Switch to the next monitoring profile (after the last, back to none).
+    CycleProfile,
+}
+
+/// The host editor overlay: a cursor over the config's hosts (enabled or not) and
+/// the text prompt being typed into, if any.
+#[derive(Debug, Default)]
+pub struct HostEditor {
+    pub selected: usize,
+    pub prompt: Option<Prompt>,
+}
+
+#[derive(Debug)]
+pub struct Prompt {
+    pub field: PromptField,
+    pub input: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptField {
+    Address,
+    Name,
+    Interval,
+}
+
+impl HostEditor {
+    /// Handle a key while the editor is open. Returns None when the editor should close.
+    pub fn handle_key(&mut self, code: KeyCode, hosts: &[Host]) -> Option<UiAction> {
+        if let Some(prompt) = self.prompt.as_mut() {
+            match code {
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                }
+                KeyCode::Char(c) => prompt.input.push(c),
+                KeyCode::Enter => return Some(self.submit(hosts.len())),
+                _ => {}
+            }
+            return Some(UiAction::None);
+        }
+        let i = self.selected;
+        let action = match code {
+            KeyCode::Esc | KeyCode::Char('e') => return None,
+            KeyCode::Up => {
+                self.selected = i.saturating_sub(1);
+                UiAction::None
+            }
+            KeyCode::Down => {
+                self.selected = (i + 1).min(hosts.len().saturating_sub(1));
+                UiAction::None
+            }
+            KeyCode::Char('a') => self.open(PromptField::Address, String::new()),
+            KeyCode::Char('r') => match hosts.get(i) {
+                Some(h) => self.open(PromptField::Name, h.name.clone()),
+                None => UiAction::None,
+            },
+            KeyCode::Char('i') => match hosts.get(i) {
+                Some(h) => {
+                    let current = h.interval.map(|v| v.to_string()).unwrap_or_default();
+                    self.open(PromptField::Interval, current)
+                }
+                None => UiAction::None,
+            },
+            KeyCode::Char(' ') if i < hosts.len() => UiAction::EditHost(HostEdit::ToggleEnabled(i)),
+            KeyCode::Char('x') | KeyCode::Delete if i < hosts.len() => {
+                UiAction::EditHost(HostEdit::Delete(i))
+            }
+            KeyCode::Char('w') => UiAction::SaveHosts,
+            _ => UiAction::None,
+        };
+        Some(action)
+    }
+
+    fn open(&mut self, field: PromptField, input: String) -> UiAction {
+        self.prompt = Some(Prompt { field, input });
+        UiAction::None
+    }
+
+    /// Turn the typed text into an edit. Input that doesn't parse keeps the prompt open.
+    fn submit(&mut self, host_count: usize) -> UiAction {
+        let Some(prompt) = self.prompt.as_ref() else {
+            return UiAction::None;
+        };
+        let text = prompt.input.trim();
+        let edit = match prompt.field {
+            PromptField::Address if text.is_empty() => return UiAction::None,
+            PromptField::Address => {
+                // The new host lands at the end of the list; follow it there.
+                self.selected = host_count;
+                HostEdit::Add(text.to_string())
+            }
+            PromptField::Name if text.is_empty() => return UiAction::None,
+            PromptField::Name => HostEdit::Rename(self.selected, text.to_string()),
+            // Blank means "use the global interval".
+            PromptField::Interval if text.is_empty() => HostEdit::SetInterval(self.selected, None),
+            PromptField::Interval => match text.parse::<f64>() {
+                Ok(v) if v.is_finite() && v > 0.0 => HostEdit::SetInterval(self.selected, Some(v)),
+                _ => return UiAction::None,
+            },
+        };
+        self.prompt = None;
+        UiAction::EditHost(edit)
+    }
 }
 
 pub struct TuiState {
     pub show_help: bool,
-    pub paused: bool,
     pub animation_frame: usize,
     pub last_frame_time: Instant,
     pub start_time: Instant,
@@ -176,6 +372,16 @@ pub struct TuiState {
     pub show_lore: bool,
     pub theme_name: String,
     pub show_details: bool,
+    // Index into the host list's rows (group headers and hosts) of the highlighted row.
+    pub selected: usize,
+    // Groups folded down to their header line.
+    pub collapsed: HashSet<String>,
+    // Hosts whose addresses are listed under them, by host id.
+    pub expanded: HashSet<String>,
+    // Whether the last frame showed the path view; decides what Enter/Esc do.
+    pub path_open: bool,
+    // Open host editor overlay, if any.
+    pub editor: Option<HostEditor>,
     // Stored from config; the renderer reads graph height from RenderOpts, not this field.
     #[allow(dead_code)]
     pub graph_height: u16,
@@ -194,7 +400,6 @@ impl TuiState {
 
         Self {
             show_help: false,
-            paused: false,
             animation_frame: 0,
             last_frame_time: now,
             start_time: now,
@@ -209,6 +414,11 @@ impl TuiState {
             show_lore: true,
             theme_name: "auto".into(),
             show_details: true,
+            selected: 0,
+            collapsed: HashSet::new(),
+            expanded: HashSet::new(),
+            path_open: false,
+            editor: None,
             graph_height: 10,
         }
     }
@@ -249,7 +459,12 @@ impl Default for TuiState {
 pub struct TuiApp {
     terminal: Terminal<CrosstermBackend<io::Stdout>>,
     state: TuiState,
-    host_info: Vec<(String, String)>, // (id, name)
+    host_info: Vec<(String, String)>,       // (id, name)
+    host_groups: HashMap<String, String>,   // host id -> group, for hosts that have one
+    address_counts: HashMap<String, usize>, // host id -> addresses probed, as of the last frame
+    hosts: Option<Vec<Host>>, // every configured host, for the editor; None when not editable
+    editor_note: Option<String>, // result of the last save, shown in the editor
+    replaying: bool,          // playing a recording back; the replay keys apply
 }
 
 impl TuiApp {
@@ -268,6 +483,11 @@ impl TuiApp {
             terminal,
             state,
             host_info: Vec::new(),
+            host_groups: HashMap::new(),
+            address_counts: HashMap::new(),
+            hosts: None,
+            editor_note: None,
+            replaying: false,
         })
     }
 
@@ -275,6 +495,92 @@ impl TuiApp {
         self.host_info = host_info;
     }
 
+    pub fn set_host_groups(&mut self, host_groups: HashMap<String, String>) {
+        self.host_groups = host_groups;
+    }
+
+    /// The host list as currently shown, with collapsed groups folded away.
+    fn rows(&self) -> Vec<ListRow> {
+        list_rows(
+            &self.host_info,
+            &self.host_groups,
+            &self.state.collapsed,
+            &self.address_counts,
+            &self.state.expanded,
+        )
+    }
+
+    /// The host id on the selected row (an address row's host), if it isn't a group header.
+    fn selected_host(&self) -> Option<String> {
+        match self.rows().get(self.state.selected)? {
+            ListRow::Host(i) | ListRow::Address { host: i, .. } => {
+                self.host_info.get(*i).map(|(id, _)| id.clone())
+            }
+            ListRow::Group { .. } => None,
+        }
+    }
+
+    /// List or hide the selected host's addresses, keeping the host selected.
+    fn toggle_addresses(&mut self) {
+        let Some(id) = self.selected_host() else {
+            return;
+        };
+        if !self.address_counts.contains_key(&id) {
+            return;
+        }
+        if !self.state.expanded.remove(&id) {
+            self.state.expanded.insert(id.clone());
+        }
+        let host = self.host_info.iter().position(|(h, _)| *h == id);
+        if let Some(i) = self
+            .rows()
+            .iter()
+            .position(|r| matches!(r, ListRow::Host(h) if Some(*h) == host))
+        {
+            self.state.selected = i;
+        }
+    }
+
+    /// Fold or unfold the selected row's group, keeping its header selected.
+    fn toggle_group(&mut self) {
+        let group = match self.rows().get(self.state.selected) {
+            Some(ListRow::Group { name, .. }) => name.clone(),
+            Some(ListRow::Host(i) | ListRow::Address { host: i, .. }) => match self
+                .host_info
+                .get(*i)
+                .and_then(|(id, _)| self.host_groups.get(id))
+            {
+                Some(group) => group.clone(),
+                None => return,
+            },
+            None => return,
+        };
+        if !self.state.collapsed.remove(&group) {
+            self.state.collapsed.insert(group.clone());
+        }
+        if let Some(i) = self
+            .rows()
+            .iter()
+            .position(|r| matches!(r, ListRow::Group { name, .. } if *name == group))
+        {
+            self.state.selected = i;
+        }
+    }
+
+    /// Make the host list editable; the editor shows these (disabled ones dimmed).
+    pub fn set_hosts(&mut self, hosts: Vec<Host>) {
+        self.hosts = Some(hosts);
+    }
+
+    /// Playing a recording back: take the keys that step and seek through it.
+    pub fn set_replaying(&mut self) {
+        self.replaying = true;
+    }
+
+    pub fn set_editor_note(&mut self, note: String) {
+        self.editor_note = Some(note);
+    }
+
     // Pushes theme/detail/graph-height settings from app config into render state.
     pub fn set_ui_config(&mut self, theme_name: String, show_details: bool, graph_height: u16) {
         self.state.theme_name = theme_name;
@@ -298,10 +604,20 @@ impl TuiApp {
     pub async fn draw(
         &mut self,
         stats: &HashMap<String, PingStats>,
+        addresses: &HashMap<String, Vec<(IpAddr, PingStats)>>,
         opts: &RenderOpts,
     ) -> anyhow::Result<()> {
+        self.address_counts = addresses
+            .iter()
+            .map(|(id, a)| (id.clone(), a.len()))
+            .collect();
         let host_info = self.host_info.clone();
+        let rows = self.rows();
         let show_help = self.state.show_help;
+        let replaying = self.replaying;
+        self.state.path_open = opts.path.is_some();
+        self.state.selected = self.state.selected.min(rows.len().saturating_sub(1));
+        let selected = self.state.selected;
 
         // Update animation frame based on ping performance
         let avg_rtt = calculate_average_rtt(stats);
@@ -329,15 +645,24 @@ impl TuiApp {
         let chicago_time = self.state.chicago_time;
         let use_24_hour = self.state.use_24_hour_format;
         let show_lore = self.state.show_lore;
+        if let (Some(editor), Some(hosts)) = (self.state.editor.as_mut(), &self.hosts) {
+            editor.selected = editor.selected.min(hosts.len().saturating_sub(1));
+        }
+        let editor = self.state.editor.as_ref().zip(self.hosts.as_deref());
+        let editor_note = self.editor_note.as_deref();
 
         self.terminal.draw(move |f| {
             if show_help {
-                render_help(f);
+                render_help(f, replaying);
+            } else if let Some((editor, hosts)) = editor {
+                render_host_editor(f, hosts, editor, editor_note, &opts.theme);
             } else {
                 render_main(
                     f,
                     stats,
+                    addresses,
                     &host_info,
+                    &rows,
                     animation_frame,
                     animation_time,
                     avg_rtt,
@@ -347,6 +672,7 @@ impl TuiApp {
                     chicago_time,
                     use_24_hour,
                     show_lore,
+                    selected,
                     opts,
                 );
             }
@@ -354,21 +680,61 @@ impl TuiApp {
         Ok(())
     }
 
-    pub async fn handle_events(&mut self) -> anyhow::Result<bool> {
+    pub async fn handle_events(&mut self) -> anyhow::Result<UiAction> {
         if event::poll(Duration::from_millis(50))? {
             if let Event::Key(key) = event::read()? {
                 match key.code {
                     KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        return Ok(true); // Ctrl-C quits (raw mode swallows SIGINT)
+                        return Ok(UiAction::Quit); // Ctrl-C quits (raw mode swallows SIGINT)
+                    }
+                    // While the host editor is open it takes every other key.
+                    code if self.state.editor.is_some() => {
+                        self.editor_note = None;
+                        let hosts = self.hosts.as_deref().unwrap_or_default();
+                        let editor = self.state.editor.as_mut().expect("checked above");
+                        return Ok(match editor.handle_key(code, hosts) {
+                            Some(action) => action,
+                            None => {
+                                self.state.editor = None;
+                                UiAction::None
+                            }
+                        });
+                    }
+                    // Esc backs out of the path view first, then quits.
+                    KeyCode::Esc if self.state.path_open => return Ok(UiAction::ClosePath),
+                    KeyCode::Esc => return Ok(UiAction::Quit),
+                    KeyCode::Char('q') => return Ok(UiAction::Quit), // Quit
+                    KeyCode::Up => {
+                        self.state.selected = self.state.selected.saturating_sub(1);
                     }
-                    KeyCode::Esc => return Ok(true),
-                    KeyCode::Char('q') => return Ok(true), // Quit
+                    KeyCode::Down if self.state.selected + 1 < self.rows().len() => {
+                        self.state.selected += 1;
+                    }
+                    KeyCode::Enter if self.state.path_open => return Ok(UiAction::ClosePath),
+                    KeyCode::Enter => match self.selected_host() {
+                        Some(id) => return Ok(UiAction::OpenPath(id)),
+                        None => self.toggle_group(),
+                    },
+                    KeyCode::Char('g') => self.toggle_group(),
+                    KeyCode::Char('a') => self.toggle_addresses(),
                     KeyCode::Char('h') | KeyCode::F(1) => {
                         self.state.show_help = !self.state.show_help;
                     }
-                    KeyCode::Char(' ') => {
-                        self.state.paused = !self.state.paused;
+                    KeyCode::Char(' ') => return Ok(UiAction::TogglePause(None)),
+                    KeyCode::Char('.') if self.replaying => return Ok(UiAction::Step),
+                    KeyCode::Left if self.replaying => return Ok(UiAction::Seek(-10_000)),
+                    KeyCode::Right if self.replaying => return Ok(UiAction::Seek(10_000)),
+                    KeyCode::Char('[') if self.replaying => return Ok(UiAction::Seek(-60_000)),
+                    KeyCode::Char(']') if self.replaying => return Ok(UiAction::Seek(60_000)),
+                    KeyCode::Char('P') => {
+                        if let Some(id) = self.selected_host() {
+                            return Ok(UiAction::TogglePause(Some(id)));
+                        }
+                    }
+                    KeyCode::Char('e') if self.hosts.is_some() => {
+                        self.state.editor = Some(HostEditor::default());
                     }
+                    KeyCode::Char('o') => return Ok(UiAction::CycleProfile),
                     KeyCode::Char('v') => {
                         self.state.cycle_animation();
                     }
@@ -389,7 +755,7 @@ impl TuiApp {
                 }
             }
         }
-        Ok(false)
+        Ok(UiAction::None)
     }
 
     fn update_bounce_position(&mut self) {
@@ -419,7 +785,9 @@ impl TuiApp {
 fn render_main(
     f: &mut Frame,
     stats: &HashMap<String, PingStats>,
+    addresses: &HashMap<String, Vec<(IpAddr, PingStats)>>,
     host_info: &[(String, String)],
+    rows: &[ListRow],
     animation_frame: usize,
     animation_time: f64,
     avg_rtt: f64,
@@ -429,6 +797,7 @@ fn render_main(
     chicago_time: DateTime<chrono_tz::Tz>,
     use_24_hour: bool,
     show_lore: bool,
+    selected: usize,
     opts: &RenderOpts,
 ) {
     let size = f.area();
@@ -484,13 +853,31 @@ fn render_main(
             .split(main_chunks[0]);
 
         // Render pings window (top left)
-        render_pings_window(f, left_chunks[0], stats, host_info, opts);
+        render_hosts_or_path(
+            f,
+            left_chunks[0],
+            stats,
+            addresses,
+            host_info,
+            rows,
+            selected,
+            opts,
+        );
 
         // Render lore window (bottom left)
         render_lore_window(f, left_chunks[1], animation_type);
     } else {
         // Render pings window taking full left side
-        render_pings_window(f, main_chunks[0], stats, host_info, opts);
+        render_hosts_or_path(
+            f,
+            main_chunks[0],
+            stats,
+            addresses,
+            host_info,
+            rows,
+            selected,
+            opts,
+        );
     }
 
     // Render animation (right side)
@@ -508,79 +895,455 @@ fn render_main(
     render_status_bar(f, outer_chunks[1], local_time, chicago_time, use_24_hour);
 }
 
+/// The host list, or the path view for the selected host when one is open.
+#[allow(clippy::too_many_arguments)]
+fn render_hosts_or_path(
+    f: &mut Frame,
+    area: Rect,
+    stats: &HashMap<String, PingStats>,
+    addresses: &HashMap<String, Vec<(IpAddr, PingStats)>>,
+    host_info: &[(String, String)],
+    rows: &[ListRow],
+    selected: usize,
+    opts: &RenderOpts,
+) {
+    match &opts.path {
+        Some(panel) => render_path_window(f, area, panel, &opts.theme),
+        None => render_pings_window(f, area, stats, addresses, host_info, rows, selected, opts),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn render_pings_window(
     f: &mut Frame,
     area: Rect,
     stats: &HashMap<String, PingStats>,
+    addresses: &HashMap<String, Vec<(IpAddr, PingStats)>>,
     host_info: &[(String, String)],
+    rows: &[ListRow],
+    selected: usize,
     opts: &RenderOpts,
 ) {
-    let outer = Block::default()
-        .borders(Borders::ALL)
-        .title(" Network Status ");
+    let mut title = " Network Status ".to_string();
+    if let Some(profile) = &opts.profile {
+        title.push_str(&format!("\u{b7} {profile} "));
+    }
+    match opts.paused.len() {
+        0 => {}
+        n if n == host_info.len() => title.push_str("\u{b7} \u{23f8} PAUSED "),
+        n => title.push_str(&format!("\u{b7} \u{23f8} {n} PAUSED ")),
+    }
+    let outer = Block::default().borders(Borders::ALL).title(title);
     let inner = outer.inner(area);
     f.render_widget(outer, area);
 
-    if host_info.is_empty() {
+    if rows.is_empty() {
         return;
     }
 
-    // Each host row: a 2-line header plus (when details are on) a sparkline.
+    // Each host row: a 2-line header plus (when details are on) a sparkline. Group
+    // headers take a single line, address rows one more for their RTT summary.
     let graph_h = if opts.show_details {
         opts.graph_height.max(1)
     } else {
         0
     };
     let per_host = 2u16.saturating_add(graph_h);
-    let constraints: Vec<Constraint> = host_info
+    let per_address = if opts.show_details { 2 } else { 1 };
+    let constraints: Vec<Constraint> = rows
         .iter()
-        .map(|_| Constraint::Length(per_host))
+        .map(|r| match r {
+            ListRow::Group { .. } => Constraint::Length(1),
+            ListRow::Address { .. } => Constraint::Length(per_address),
+            ListRow::Host(_) => Constraint::Length(per_host),
+        })
         .collect();
-    let rows = Layout::default()
+    let areas = Layout::default()
         .direction(Direction::Vertical)
         .constraints(constraints)
         .split(inner);
 
-    for (row, (host_id, host_name)) in rows.iter().zip(host_info.iter()) {
+    for (i, (row, list_row)) in areas.iter().zip(rows).enumerate() {
+        let (host_id, host_name) = match list_row {
+            ListRow::Group { name, collapsed } => {
+                let status = opts.groups.iter().find(|g| g.name == *name);
+                render_group_header(f, *row, name, *collapsed, status, i == selected, opts);
+                continue;
+            }
+            ListRow::Address { host, addr } => {
+                let host_id = &host_info[*host].0;
+                if let Some((ip, stat)) = addresses.get(host_id).and_then(|a| a.get(*addr)) {
+                    let state = opts.address_states.get(host_id).and_then(|s| s.get(*addr));
+                    render_address_row(f, *row, *ip, stat, state, i == selected, opts);
+                }
+                continue;
+            }
+            ListRow::Host(h) => &host_info[*h],
+        };
         let state = opts
             .host_states
             .iter()
             .find(|(id, _)| id == host_id)
             .map(|(_, s)| s.clone());
-        let (symbol, color, detail) = match &state {
+        let (mut symbol, mut color, mut detail) = match &state {
             Some(HostState::Up { rtt_ms }) => {
                 ("\u{25cf}", opts.theme.good, format!("{rtt_ms:.0}ms"))
             }
-            Some(HostState::Degraded { loss_pct }) => {
-                ("\u{25d0}", opts.theme.warn, format!("{loss_pct:.0}% loss"))
-            }
+            Some(s @ HostState::Degraded { .. }) => (
+                "\u{25d0}",
+                opts.theme.warn,
+                s.degraded_reason().unwrap_or_default(),
+            ),
             Some(HostState::Down { reason }) => {
                 ("\u{2717}", opts.theme.bad, format!("down: {reason}"))
             }
             _ => ("\u{25cb}", opts.theme.dim, "resolving\u{2026}".to_string()),
         };
 
+        // A host behind a down dependency isn't failing itself; grey it out and say
+        // what it's waiting on instead of adding to the wall of red.
+        if let Some(dep) = opts.blocked.get(host_id) {
+            let dep_name = host_info
+                .iter()
+                .find(|(id, _)| id == dep)
+                .map_or(dep.as_str(), |(_, n)| n.as_str());
+            symbol = "\u{25cc}";
+            color = opts.theme.dim;
+            detail = format!("unreachable \u{2014} {dep_name} is down");
+        }
+
+        // A paused host keeps its last state's color, under a pause symbol.
+        if opts.paused.contains(host_id) {
+            symbol = "\u{23f8}";
+            detail = format!("paused \u{b7} last {detail}");
+        }
+
+        // Probe-specific extras: where an HTTP request spent its time, or a DNS
+        // resolver's error/answer-change tally.
+        if let Some(note) = opts.host_notes.get(host_id) {
+            detail = format!("{detail}  ({note})");
+        }
+
+        if let Some(addrs) = addresses.get(host_id) {
+            detail = format!("{detail}  \u{b7} {} addresses", addrs.len());
+        }
+
+        // The call-quality score only means something for ping replies, and only while
+        // they're coming back.
+        let stat = stats.get(host_id).filter(|_| {
+            opts.icmp_hosts.contains(host_id)
+                && matches!(
+                    state,
+                    Some(HostState::Up { .. } | HostState::Degraded { .. })
+                )
+        });
+
         let sub = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Length(2), Constraint::Min(0)])
             .split(*row);
 
-        let header = Paragraph::new(format!("{symbol} {host_name}\n   {detail}"))
-            .style(Style::default().fg(color));
+        let name_style = if i == selected {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        let header = Paragraph::new(Text::from(vec![
+            Line::from(vec![
+                Span::raw(format!("{symbol} ")),
+                Span::styled(host_name.clone(), name_style),
+                Span::styled(
+                    opts.host_ips
+                        .get(host_id)
+                        .map(|ip| format!("  {ip}"))
+                        .unwrap_or_default(),
+                    Style::default().fg(opts.theme.dim),
+                ),
+            ]),
+            Line::from(vec![
+                Span::raw(format!("   {detail}")),
+                call_quality_span(stat, opts),
+            ]),
+        ]))
+        .style(Style::default().fg(color));
         f.render_widget(header, sub[0]);
 
         if opts.show_details {
             if let Some(stat) = stats.get(host_id) {
-                // Option<u64> preserves gaps (None) for timeouts/errors.
+                // Tail latency and its spread above the graph, as far as room allows.
+                let text: Vec<Line> = [rtt_summary(stat), rtt_histogram_line(stat)]
+                    .into_iter()
+                    .flatten()
+                    .take(sub[1].height.saturating_sub(1) as usize)
+                    .map(Line::from)
+                    .collect();
+                let [text_area, graph_area] = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(text.len() as u16), Constraint::Min(0)])
+                    .areas(sub[1]);
+                f.render_widget(
+                    Paragraph::new(text).style(Style::default().fg(opts.theme.dim)),
+                    text_area,
+                );
                 let spark = Sparkline::default()
-                    .data(stat.rtt_history_for_graph(sub[1].width as usize))
+                    .data(sparkline_bars(stat, graph_area.width as usize, &opts.theme))
                     .style(Style::default().fg(opts.theme.accent));
-                f.render_widget(spark, sub[1]);
+                f.render_widget(spark, graph_area);
             }
         }
     }
 }
 
+/// "· MOS 4.4" after a host's detail, colored by how a call would fare.
+fn call_quality_span(stat: Option<&PingStats>, opts: &RenderOpts) -> Span<'static> {
+    let Some(q) = stat.and_then(PingStats::call_quality) else {
+        return Span::raw("");
+    };
+    let color = match stat.map(PingStats::connection_quality) {
+        Some(ConnectionQuality::Good) => opts.theme.good,
+        Some(ConnectionQuality::Fair) => opts.theme.warn,
+        _ => opts.theme.bad,
+    };
+    Span::styled(
+        format!("  \u{b7} MOS {:.1}", q.mos),
+        Style::default().fg(color),
+    )
+}
+
+/// Median and tail RTTs over the retained history, then jitter and stddev, e.g.
+/// "p50 12ms · p90 18ms · … · jitter 1.2ms · stddev 3.4ms".
+fn rtt_summary(stat: &PingStats) -> Option<String> {
+    if stat.successful_pings() == 0 {
+        return None;
+    }
+    let r = stat.rtt_stats();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    Some(format!(
+        "p50 {:.0}ms \u{b7} p90 {:.0}ms \u{b7} p95 {:.0}ms \u{b7} p99 {:.0}ms \u{b7} max {:.0}ms \u{b7} jitter {:.1}ms \u{b7} stddev {:.1}ms",
+        ms(r.median),
+        ms(r.p90),
+        ms(r.p95),
+        ms(r.p99),
+        ms(r.max),
+        ms(r.jitter),
+        ms(r.stddev)
+    ))
+}
+
+/// The retained RTTs' histogram on one line: each bucket from the fastest to the
+/// slowest one used, as its bound and a bar scaled to the fullest, e.g. "≤5 ▂ ≤10 █".
+fn rtt_histogram_line(stat: &PingStats) -> Option<String> {
+    const BARS: [char; 8] = [
+        '\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}',
+        '\u{2588}',
+    ];
+    let hist = stat.recent_histogram();
+    let first = hist.iter().position(|(_, n)| *n > 0)?;
+    let last = hist.iter().rposition(|(_, n)| *n > 0)?;
+    let peak = hist.iter().map(|(_, n)| *n).max().unwrap_or(1);
+    let cells: Vec<String> = hist[first..=last]
+        .iter()
+        .map(|(le, n)| {
+            let label = if le.is_finite() {
+                format!("\u{2264}{le}")
+            } else {
+                format!(">{}", RTT_BUCKETS_MS[RTT_BUCKETS_MS.len() - 1])
+            };
+            let bar = match *n {
+                0 => ' ',
+                n => BARS[((n * BARS.len() as u64).div_ceil(peak) as usize).max(1) - 1],
+            };
+            format!("{label} {bar}")
+        })
+        .collect();
+    Some(format!("ms {}", cells.join(" ")))
+}
+
+/// One of a host's addresses: its state and stats on a single line, then as much of
+/// its graph as fits, with its RTT summary underneath when details are on.
+fn render_address_row(
+    f: &mut Frame,
+    area: Rect,
+    ip: IpAddr,
+    stat: &PingStats,
+    state: Option<&HostState>,
+    selected: bool,
+    opts: &RenderOpts,
+) {
+    let (symbol, color, detail) = match state {
+        Some(HostState::Up { rtt_ms }) => ("\u{25cf}", opts.theme.good, format!("{rtt_ms:.0}ms")),
+        Some(s @ HostState::Degraded { .. }) => (
+            "\u{25d0}",
+            opts.theme.warn,
+            s.degraded_reason().unwrap_or_default(),
+        ),
+        Some(HostState::Down { reason }) => ("\u{2717}", opts.theme.bad, reason.clone()),
+        _ => ("\u{25cb}", opts.theme.dim, "waiting\u{2026}".to_string()),
+    };
+    let text = format!("   \u{2514} {symbol} {ip}  {detail}  ");
+    let width = (text.chars().count() as u16).min(area.width);
+    let mut style = Style::default().fg(color);
+    if selected {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+    let [top, summary] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .areas(area);
+    let [label, graph] = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(width), Constraint::Min(0)])
+        .areas(top);
+    f.render_widget(Paragraph::new(Span::styled(text, style)), label);
+    if opts.show_details {
+        let spark = Sparkline::default()
+            .data(sparkline_bars(stat, graph.width as usize, &opts.theme))
+            .style(Style::default().fg(opts.theme.accent));
+        f.render_widget(spark, graph);
+        if let Some(line) = rtt_summary(stat) {
+            f.render_widget(
+                Paragraph::new(format!("     {line}")).style(Style::default().fg(opts.theme.dim)),
+                summary,
+            );
+        }
+    }
+}
+
+/// A group's section header: fold marker, then its summary ("LAN 2/2 up · 3ms") in
+/// the color of the group's own connectivity.
+fn render_group_header(
+    f: &mut Frame,
+    area: Rect,
+    name: &str,
+    collapsed: bool,
+    status: Option<&GroupStatus>,
+    selected: bool,
+    opts: &RenderOpts,
+) {
+    let fold = if collapsed { "\u{25b8}" } else { "\u{25be}" };
+    let (text, color) = match status {
+        Some(g) => {
+            let color = match g.conn {
+                ConnectivityState::Online => opts.theme.good,
+                ConnectivityState::Degraded | ConnectivityState::CaptivePortal { .. } => {
+                    opts.theme.warn
+                }
+                ConnectivityState::Offline => opts.theme.bad,
+            };
+            (g.summary(), color)
+        }
+        None => (name.to_string(), opts.theme.dim),
+    };
+    let mut style = Style::default().fg(color).add_modifier(Modifier::BOLD);
+    if selected {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+    f.render_widget(
+        Paragraph::new(Line::from(Span::styled(format!("{fold} {text}"), style))),
+        area,
+    );
+}
+
+/// MTR-style table of per-hop loss and RTT for the host being traced.
+fn render_path_window(f: &mut Frame, area: Rect, panel: &PathPanel, theme: &Theme) {
+    let mut lines = vec![Line::from(Span::styled(
+        format!(
+            "{:>3}  {:<24} {:>6} {:>5} {:>7} {:>7} {:>7} {:>7} {:>7}",
+            "#", "Host", "Loss%", "Sent", "Last", "Avg", "Best", "Worst", "Jitter"
+        ),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    let fmt_ms = |v: f64| format!("{v:.1}");
+    for row in &panel.rows {
+        let color = if row.loss_pct >= 100.0 {
+            theme.bad
+        } else if row.loss_pct > 0.0 {
+            theme.warn
+        } else {
+            theme.good
+        };
+        let answered = row.last_ms.is_some() || row.avg_ms > 0.0;
+        let (last, avg, best, worst, jitter) = if answered {
+            (
+                row.last_ms.map(fmt_ms).unwrap_or_else(|| "-".into()),
+                fmt_ms(row.avg_ms),
+                fmt_ms(row.best_ms),
+                fmt_ms(row.worst_ms),
+                fmt_ms(row.jitter_ms),
+            )
+        } else {
+            ("-".into(), "-".into(), "-".into(), "-".into(), "-".into())
+        };
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{:>3}. {:<24} {:>5.1}% {:>5} {:>7} {:>7} {:>7} {:>7} {:>7}",
+                row.ttl, row.host, row.loss_pct, row.sent, last, avg, best, worst, jitter
+            ),
+            Style::default().fg(color),
+        )));
+    }
+    if panel.rows.is_empty() && panel.error.is_none() {
+        lines.push(Line::from(Span::styled(
+            "tracing\u{2026}",
+            Style::default().fg(theme.dim),
+        )));
+    }
+    if let Some(err) = &panel.error {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            err.clone(),
+            Style::default().fg(theme.bad),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Loss only at a middle hop is usually ICMP rate limiting.",
+        Style::default().fg(theme.dim),
+    )));
+
+    let p = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(panel.title.clone())
+            .title_bottom(" Enter/Esc: back "),
+    );
+    f.render_widget(p, area);
+}
+
+/// Sparkline bars for a host, oldest→newest. Timeouts and errors stay gaps (None),
+/// except refused TCP connects, which draw as full-height bars in the "bad" color so
+/// a closed port reads differently from an unreachable host. Markers take a column of
+/// their own as a full-height line: dim for a pause, the accent color for a network change,
+/// the warning color where the host moved to another address.
+fn sparkline_bars(stat: &PingStats, points: usize, theme: &Theme) -> Vec<SparklineBar> {
+    let rtts = stat.rtt_history_for_graph(points);
+    let peak = rtts.iter().flatten().copied().max().unwrap_or(0).max(1);
+    // recent_results is newest-first; flip it to line up with the oldest-first RTTs.
+    let results = stat.recent_results(points).into_iter().rev();
+    let first = stat.total_pings() - rtts.len() as u64;
+    let marker_bar = |m: Marker| {
+        let color = match m {
+            Marker::Pause => theme.dim,
+            Marker::NetworkChange => theme.accent,
+            Marker::AddressChange => theme.warn,
+        };
+        SparklineBar::from(Some(peak)).style(Some(Style::default().fg(color)))
+    };
+    let mut bars = Vec::new();
+    for (i, (rtt, result)) in rtts.into_iter().zip(results).enumerate() {
+        bars.extend(stat.markers_at(first + i as u64).map(marker_bar));
+        if result.is_refused() {
+            bars.push(SparklineBar::from(Some(peak)).style(Some(Style::default().fg(theme.bad))));
+        } else {
+            bars.push(SparklineBar::from(rtt));
+        }
+    }
+    bars.extend(stat.markers_at(stat.total_pings()).map(marker_bar));
+    // Markers take columns too; keep the newest that fit.
+    let overflow = bars.len().saturating_sub(points);
+    bars.split_off(overflow)
+}
+
 fn render_lore_window(f: &mut Frame, area: Rect, animation_type: AnimationType) {
     let lore_text = match animation_type {
         AnimationType::Plasma => vec![
@@ -1790,31 +2553,137 @@ fn generate_connection_failure_overlay(
     lines.join("\n")
 }
 
-fn render_help(f: &mut Frame) {
+/// Full-screen host list for editing: every configured host, disabled ones dimmed.
+fn render_host_editor(
+    f: &mut Frame,
+    hosts: &[Host],
+    editor: &HostEditor,
+    note: Option<&str>,
+    theme: &Theme,
+) {
+    let mut lines: Vec<Line> = hosts
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            let check = if h.enabled { "[x]" } else { "[ ]" };
+            let interval = match h.interval {
+                Some(v) => format!("every {v}s"),
+                None => "global interval".to_string(),
+            };
+            let mut style = if h.enabled {
+                Style::default()
+            } else {
+                Style::default().fg(theme.dim)
+            };
+            if i == editor.selected {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            let mut probe = format!("{:?}", h.probe).to_lowercase();
+            // Found on the network, so saving leaves it out of the file.
+            if h.discovered {
+                probe.push_str(" auto");
+            }
+            Line::from(Span::styled(
+                format!(
+                    " {check} {:<24} {:<32} {probe:<5} {interval}",
+                    h.name, h.address
+                ),
+                style,
+            ))
+        })
+        .collect();
+    if hosts.is_empty() {
+        lines.push(Line::from(Span::styled(
+            " No hosts yet \u{2014} press 'a' to add one",
+            Style::default().fg(theme.dim),
+        )));
+    }
+    lines.push(Line::from(""));
+    if let Some(prompt) = &editor.prompt {
+        let label = match prompt.field {
+            PromptField::Address => "Address (host, IP or URL)",
+            PromptField::Name => "Name",
+            PromptField::Interval => "Interval in seconds (blank for global)",
+        };
+        lines.push(Line::from(Span::styled(
+            format!(" {label}: {}\u{2588}", prompt.input),
+            Style::default().fg(theme.accent),
+        )));
+        lines.push(Line::from(Span::styled(
+            " Enter - apply   Esc - cancel",
+            Style::default().fg(theme.dim),
+        )));
+    } else {
+        lines.push(Line::from(Span::styled(
+            " a - add   r - rename   i - interval   Space - enable/disable   x - delete",
+            Style::default().fg(theme.dim),
+        )));
+        lines.push(Line::from(Span::styled(
+            " w - write to config file   Esc / e - close",
+            Style::default().fg(theme.dim),
+        )));
+    }
+    if let Some(note) = note {
+        lines.push(Line::from(Span::styled(
+            format!(" {note}"),
+            Style::default().fg(theme.warn),
+        )));
+    }
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Hosts \u{b7} changes apply immediately "),
+    );
+    f.render_widget(paragraph, f.area());
+}
+
+fn render_help(f: &mut Frame, replaying: bool) {
     let area = f.area();
 
-    let help_text = vec![
+    let mut help_text = vec![
         "🏓 Pingpong Help",
         "",
         "CONTROLS:",
-        "  Space       - Pause/resume pings",
+        "  Space       - Pause/resume all pings",
+        "  P           - Pause/resume the selected host",
         "  q / Esc     - Quit application",
         "  Ctrl-C      - Quit application",
         "  h / F1      - Toggle this help",
+        "  \u{2191} / \u{2193}       - Select host",
+        "  Enter       - Open/close path view (traceroute) for selected host",
+        "  g           - Collapse/expand the selected group (or Enter on its header)",
+        "  a           - Show/hide the selected host's addresses (all_addresses)",
+        "  e           - Edit hosts (add, rename, interval, enable, delete)",
+        "  o           - Cycle monitoring profile",
         "  v           - Cycle through visualizations",
         "  p           - Toggle 12/24 hour time format",
         "  l           - Toggle lore window visibility",
         "  d           - Toggle per-host detail graphs",
         "  t           - Cycle color theme (dark/light/auto)",
         "",
+    ];
+    if replaying {
+        help_text.extend([
+            "REPLAY:",
+            "  Space       - Pause/resume playback",
+            "  .           - Pause and step to the next event",
+            "  \u{2190} / \u{2192}       - Seek back/forward 10 seconds",
+            "  [ / ]       - Seek back/forward 1 minute",
+            "",
+        ]);
+    }
+    help_text.extend([
         "INDICATORS:",
         "  \u{25cf}           - Host up (healthy)",
-        "  \u{25d0}           - Host degraded (packet loss)",
+        "  \u{25d0}           - Host degraded (packet loss or low call quality (MOS))",
         "  \u{2717}           - Host down",
         "  \u{25cb}           - Resolving / waiting",
+        "  \u{25cc}           - Unreachable behind a down dependency (depends_on)",
+        "  \u{23f8}           - Paused (gap marked in the graph)",
         "",
         "Press 'h' or F1 to close this help",
-    ];
+    ]);
 
     let help_paragraph = Paragraph::new(help_text.join("\n"))
         .block(Block::default().borders(Borders::ALL).title(" Help "))
@@ -1874,3 +2743,179 @@ mod theme_tests {
         assert_eq!(Theme::cycle_name(""), "dark");
     }
 }
+
+#[cfg(test)]
+mod detail_tests {
+    use super::*;
+    use crate::stats::PingResult;
+
+    #[test]
+    fn histogram_spans_the_used_buckets() {
+        let mut s = PingStats::new(100);
+        assert_eq!(rtt_histogram_line(&s), None);
+        assert_eq!(rtt_summary(&s), None);
+        for ms in [3u64, 4, 4, 4, 30, 9000] {
+            s.add_result(&PingResult::Success {
+                rtt: Duration::from_millis(ms),
+                sequence: 0,
+                timestamp: Instant::now(),
+            });
+        }
+        // Empty buckets between used ones stay as blanks; the fullest gets a full bar.
+        assert_eq!(
+            rtt_histogram_line(&s).unwrap(),
+            "ms \u{2264}5 \u{2588} \u{2264}10   \u{2264}25   \u{2264}50 \u{2582} \u{2264}100   \u{2264}250   \u{2264}500   \u{2264}1000   \u{2264}2500   \u{2264}5000   >5000 \u{2582}"
+        );
+        assert!(rtt_summary(&s).unwrap().starts_with("p50 4ms"));
+    }
+}
+
+#[cfg(test)]
+mod group_tests {
+    use super::*;
+
+    #[test]
+    fn ungrouped_hosts_come_first_then_each_group_unless_collapsed() {
+        let host_info: Vec<(String, String)> = ["gw", "wiki", "dns", "vpn"]
+            .iter()
+            .map(|id| (id.to_string(), id.to_string()))
+            .collect();
+        let groups = HashMap::from([
+            ("gw".to_string(), "LAN".to_string()),
+            ("wiki".to_string(), "Office".to_string()),
+            ("vpn".to_string(), "LAN".to_string()),
+        ]);
+        let none = HashMap::new();
+        let group = |name: &str, collapsed| ListRow::Group {
+            name: name.to_string(),
+            collapsed,
+        };
+        assert_eq!(
+            list_rows(&host_info, &groups, &HashSet::new(), &none, &HashSet::new()),
+            vec![
+                ListRow::Host(2),
+                group("LAN", false),
+                ListRow::Host(0),
+                ListRow::Host(3),
+                group("Office", false),
+                ListRow::Host(1),
+            ]
+        );
+        let collapsed = HashSet::from(["LAN".to_string()]);
+        assert_eq!(
+            list_rows(&host_info, &groups, &collapsed, &none, &HashSet::new()),
+            vec![
+                ListRow::Host(2),
+                group("LAN", true),
+                group("Office", false),
+                ListRow::Host(1),
+            ]
+        );
+        // Without groups the list is just the hosts, as before.
+        assert_eq!(
+            list_rows(
+                &host_info,
+                &HashMap::new(),
+                &HashSet::new(),
+                &none,
+                &HashSet::new()
+            )
+            .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn expanded_hosts_list_their_addresses() {
+        let host_info: Vec<(String, String)> = ["web", "dns"]
+            .iter()
+            .map(|id| (id.to_string(), id.to_string()))
+            .collect();
+        let addresses = HashMap::from([("web".to_string(), 2)]);
+        let rows = |expanded: &[&str]| {
+            let expanded = expanded.iter().map(|id| id.to_string()).collect();
+            list_rows(
+                &host_info,
+                &HashMap::new(),
+                &HashSet::new(),
+                &addresses,
+                &expanded,
+            )
+        };
+        assert_eq!(rows(&[]), vec![ListRow::Host(0), ListRow::Host(1)]);
+        // A host with no address list has nothing to expand.
+        assert_eq!(
+            rows(&["web", "dns"]),
+            vec![
+                ListRow::Host(0),
+                ListRow::Address { host: 0, addr: 0 },
+                ListRow::Address { host: 0, addr: 1 },
+                ListRow::Host(1),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod editor_tests {
+    use super::*;
+
+    fn hosts() -> Vec<Host> {
+        vec![Host {
+            name: "Router".into(),
+            address: "192.168.1.1".into(),
+            ..Host::default()
+        }]
+    }
+
+    fn type_in(editor: &mut HostEditor, text: &str) {
+        for c in text.chars() {
+            editor.handle_key(KeyCode::Char(c), &hosts());
+        }
+    }
+
+    #[test]
+    fn prompts_turn_into_edits() {
+        let mut e = HostEditor::default();
+        e.handle_key(KeyCode::Char('a'), &hosts());
+        type_in(&mut e, "1.1.1.1");
+        assert_eq!(
+            e.handle_key(KeyCode::Enter, &hosts()),
+            Some(UiAction::EditHost(HostEdit::Add("1.1.1.1".into())))
+        );
+        assert_eq!(e.selected, 1);
+
+        e.selected = 0;
+        e.handle_key(KeyCode::Char('i'), &hosts());
+        type_in(&mut e, "fast");
+        // Not a number: nothing happens and the prompt stays open for a fix.
+        assert_eq!(e.handle_key(KeyCode::Enter, &hosts()), Some(UiAction::None));
+        for _ in 0..4 {
+            e.handle_key(KeyCode::Backspace, &hosts());
+        }
+        type_in(&mut e, "2.5");
+        assert_eq!(
+            e.handle_key(KeyCode::Enter, &hosts()),
+            Some(UiAction::EditHost(HostEdit::SetInterval(0, Some(2.5))))
+        );
+        assert!(e.prompt.is_none());
+    }
+
+    #[test]
+    fn keys_map_to_actions_and_esc_closes() {
+        let mut e = HostEditor::default();
+        assert_eq!(
+            e.handle_key(KeyCode::Char(' '), &hosts()),
+            Some(UiAction::EditHost(HostEdit::ToggleEnabled(0)))
+        );
+        assert_eq!(
+            e.handle_key(KeyCode::Char('w'), &hosts()),
+            Some(UiAction::SaveHosts)
+        );
+        // Esc inside a prompt only cancels the prompt.
+        e.handle_key(KeyCode::Char('r'), &hosts());
+        assert_eq!(e.prompt.as_ref().unwrap().input, "Router");
+        assert_eq!(e.handle_key(KeyCode::Esc, &hosts()), Some(UiAction::None));
+        assert_eq!(e.handle_key(KeyCode::Esc, &hosts()), None);
+    }
+}